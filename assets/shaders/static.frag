@@ -5,15 +5,41 @@ out vec3 color;
 uniform vec2 u_atlas_size;
 uniform sampler2D u_atlas;
 uniform sampler2D u_palette;
+uniform sampler2D u_dynamic_lights;
+uniform float u_num_dynamic_lights;
 
 in float v_dist;
+in vec3 v_pos;
 in vec2 v_tile_uv;
 flat in vec2 v_atlas_uv;
 flat in vec2 v_tile_size;
 flat in float v_light;
+in float v_gradient;
 
 const float DIST_SCALE = 0.9;
 const float LIGHT_SCALE = 2.0;
+const float DYNAMIC_TINT = 0.5;
+
+// How many dynamic lights are shaded, defined by the game's rendering quality.
+#ifndef MAX_LIGHTS
+#define MAX_LIGHTS 32
+#endif
+
+// Sums the contribution of every dynamic light in range, falling off linearly to zero at
+// the light's radius.
+vec3 dynamic_light() {
+    vec3 total = vec3(0.0);
+    for (int i = 0; i < MAX_LIGHTS; ++i) {
+        if (float(i) >= u_num_dynamic_lights) {
+            break;
+        }
+        vec4 origin_radius = texelFetch(u_dynamic_lights, ivec2(i, 0), 0);
+        vec3 light_color = texelFetch(u_dynamic_lights, ivec2(i, 1), 0).rgb;
+        float falloff = 1.0 - distance(v_pos, origin_radius.xyz) / origin_radius.w;
+        total += light_color * max(falloff, 0.0);
+    }
+    return total;
+}
 
 void main() {
     vec2 uv = mod(v_tile_uv, v_tile_size) + v_atlas_uv;
@@ -22,7 +48,12 @@ void main() {
         discard;
     } else {
         float dist_term = min(1.0, 1.0 - DIST_SCALE / (v_dist + DIST_SCALE));
-        float light = v_light * LIGHT_SCALE - dist_term;
+        vec3 dynamic = dynamic_light();
+        float boost = max(dynamic.r, max(dynamic.g, dynamic.b));
+        float light = v_light * v_gradient * LIGHT_SCALE - dist_term + boost;
         color = texture(u_palette, vec2(palette_index.r, 1.0 - light)).rgb;
+        if (boost > 0.0) {
+            color = mix(color, color * dynamic / boost, min(boost, 1.0) * DYNAMIC_TINT);
+        }
     }
 }
@@ -4,21 +4,29 @@ uniform mat4 u_modelview;
 
 uniform vec2 u_atlas_size;
 uniform float u_time;
+uniform vec3 u_light_direction;
+uniform float u_light_ambient;
+uniform float u_light_diffuse;
+uniform vec2 u_light_gradient;
 
 in vec3 a_pos;
+in vec3 a_normal;
 in vec2 a_atlas_uv;
 in vec2 a_tile_uv;
 in vec2 a_tile_size;
 in float a_scroll_rate;
 in float a_row_height;
+in float a_height_fraction;
 in int a_num_frames;
 in int a_light;
 
 out float v_dist;
+out vec3 v_pos;
 out vec2 v_tile_uv;
 flat out vec2 v_atlas_uv;
 flat out vec2 v_tile_size;
 flat out float v_light;
+out float v_gradient;
 
 const float ANIM_FPS = 8.0 / 35.0;
 
@@ -40,6 +48,11 @@ void main() {
     v_tile_size = a_tile_size;
     vec4 projected_pos = u_projection * u_modelview * vec4(a_pos, 1);
     v_dist = projected_pos.w;
-    v_light = texelFetch(u_lights, a_light).r;
+    v_pos = a_pos;
+    float diffuse = max(0.0, dot(a_normal, -u_light_direction));
+    v_light = texelFetch(u_lights, a_light).r * (u_light_ambient + u_light_diffuse * diffuse);
+    // Scales the sector light from floor to ceiling; v_light is flat, so this is kept apart to
+    // be interpolated across the surface.
+    v_gradient = mix(u_light_gradient.x, u_light_gradient.y, a_height_fraction);
     gl_Position = projected_pos;
 }
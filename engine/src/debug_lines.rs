@@ -0,0 +1,161 @@
+#![cfg_attr(feature = "cargo-clippy", allow(clippy::forget_copy))]
+
+use super::errors::{Error, Result};
+use super::system::System;
+use super::window::Window;
+use glium::Surface;
+use math::{Mat4, Pnt3f, Vec3f};
+
+#[cfg(debug_assertions)]
+use super::errors::ErrorKind;
+#[cfg(debug_assertions)]
+use glium::index::{NoIndices, PrimitiveType};
+#[cfg(debug_assertions)]
+use glium::{implement_vertex, uniform, Depth, DepthTest, DrawParameters, Program, VertexBuffer};
+
+/// Collects lines in world space to be drawn on top of everything else in the next frame, for
+/// debugging collisions, BSP splits and the like.
+///
+/// Lines are only recorded in debug builds; in release builds adding a line does nothing.
+pub struct DebugLines {
+    line_width: f32,
+
+    #[cfg(debug_assertions)]
+    vertices: Vec<LineVertex>,
+    #[cfg(debug_assertions)]
+    program: Program,
+}
+
+impl DebugLines {
+    /// Queues a line from `start` to `end` for the next frame only.
+    #[cfg(debug_assertions)]
+    pub fn add_line(&mut self, start: Pnt3f, end: Pnt3f, color: Vec3f) {
+        let a_color = [color.x, color.y, color.z];
+        self.vertices.push(LineVertex {
+            a_pos: [start.x, start.y, start.z],
+            a_color,
+        });
+        self.vertices.push(LineVertex {
+            a_pos: [end.x, end.y, end.z],
+            a_color,
+        });
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[inline]
+    pub fn add_line(&mut self, _start: Pnt3f, _end: Pnt3f, _color: Vec3f) {}
+
+    pub fn line_width(&self) -> f32 {
+        self.line_width
+    }
+
+    pub fn set_line_width(&mut self, line_width: f32) {
+        self.line_width = line_width;
+    }
+
+    /// Draws (and clears) the queued lines, `view_projection` taking world space to clip space.
+    #[cfg(debug_assertions)]
+    pub(crate) fn render<S: Surface>(
+        &mut self,
+        window: &Window,
+        surface: &mut S,
+        view_projection: Mat4,
+    ) -> Result<()> {
+        if self.vertices.is_empty() {
+            return Ok(());
+        }
+        let buffer = VertexBuffer::new(window.facade(), &self.vertices)
+            .map_err(ErrorKind::glium("debug_lines"))?;
+        self.vertices.clear();
+
+        let matrix: [[f32; 4]; 4] = view_projection.into();
+        surface
+            .draw(
+                &buffer,
+                NoIndices(PrimitiveType::LinesList),
+                &self.program,
+                &uniform! { u_transform: matrix },
+                &DrawParameters {
+                    depth: Depth {
+                        test: DepthTest::Overwrite,
+                        write: false,
+                        ..Depth::default()
+                    },
+                    line_width: Some(self.line_width),
+                    viewport: Some(window.viewport()),
+                    ..DrawParameters::default()
+                },
+            )
+            .map_err(ErrorKind::glium("debug_lines"))?;
+        Ok(())
+    }
+
+    #[cfg(not(debug_assertions))]
+    #[inline]
+    pub(crate) fn render<S: Surface>(
+        &mut self,
+        _window: &Window,
+        _surface: &mut S,
+        _view_projection: Mat4,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<'context> System<'context> for DebugLines {
+    type Dependencies = &'context Window;
+    type Error = Error;
+
+    fn debug_name() -> &'static str {
+        "debug_lines"
+    }
+
+    #[cfg(debug_assertions)]
+    fn create(window: &Window) -> Result<Self> {
+        Ok(DebugLines {
+            line_width: 1.0,
+            vertices: Vec::with_capacity(1024),
+            program: Program::from_source(window.facade(), VERTEX_SRC, FRAGMENT_SRC, None)
+                .map_err(ErrorKind::glium("debug_lines"))?,
+        })
+    }
+
+    #[cfg(not(debug_assertions))]
+    fn create(_window: &Window) -> Result<Self> {
+        Ok(DebugLines { line_width: 1.0 })
+    }
+}
+
+#[cfg(debug_assertions)]
+const VERTEX_SRC: &str = r#"
+    #version 140
+    uniform mat4 u_transform;
+    in vec3 a_pos;
+    in vec3 a_color;
+    out vec3 v_color;
+    void main() {
+        v_color = a_color;
+        gl_Position = u_transform * vec4(a_pos, 1.0);
+    }
+"#;
+
+#[cfg(debug_assertions)]
+const FRAGMENT_SRC: &str = r#"
+    #version 140
+    in vec3 v_color;
+    out vec4 color;
+    void main() {
+        color = vec4(v_color, 1.0);
+    }
+"#;
+
+#[cfg(debug_assertions)]
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct LineVertex {
+    a_pos: [f32; 3],
+    a_color: [f32; 3],
+}
+
+#[cfg(debug_assertions)]
+implement_vertex!(LineVertex, a_pos, a_color);
@@ -208,7 +208,7 @@ impl<'context> InfallibleSystem<'context> for Entities {
     }
 
     // TODO(cristicbz): Split up into simpler, more self-documenting functions.
-    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cyclomatic_complexity))]
+    #[cfg_attr(feature = "cargo-clippy", allow(clippy::cognitive_complexity))]
     fn update(&mut self, _dependencies: ()) {
         let Self {
             ref mut removed,
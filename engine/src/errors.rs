@@ -15,6 +15,9 @@ pub enum ErrorKind {
     #[fail(display = "I/O error when accessing `{}` for resource `{}`.", 0, 1)]
     ResourceIo(&'static str, &'static str),
 
+    #[fail(display = "Missing or unreadable shader `{}` for `{}`.", path, asset)]
+    MissingShader { path: String, asset: String },
+
     #[fail(
         display = "Linking/compiling shader for `{}` failed with:\n{}",
         needed_by, log
@@ -58,6 +61,9 @@ pub enum ErrorKind {
 
     #[fail(display = "System {} failed for `{}`.", 0, 1)]
     System(&'static str, &'static str),
+
+    #[fail(display = "Input recording error: {}", 0)]
+    InputRecording(String),
 }
 
 impl ChainErrorKind for ErrorKind {
@@ -70,10 +76,10 @@ impl ErrorKind {
         height: u32,
     ) -> (impl FnOnce(glium::backend::glutin::DisplayCreationError) -> Self) {
         move |error| {
-            Self::from(ErrorKind::CreateWindow(format!(
+            ErrorKind::CreateWindow(format!(
                 "Window creation failed with {}x{}: {}",
                 width, height, error
-            )))
+            ))
         }
     }
 
@@ -99,6 +105,8 @@ impl<T: UnsupportedFeature> ConvertGlium for T {
 impl UnsupportedFeature for glium::vertex::BufferCreationError {}
 impl UnsupportedFeature for glium::index::BufferCreationError {}
 impl UnsupportedFeature for glium::texture::TextureCreationError {}
+impl UnsupportedFeature for glium::framebuffer::RenderBufferCreationError {}
+impl UnsupportedFeature for glium::framebuffer::ValidationError {}
 
 impl ConvertGlium for glium::texture::buffer_texture::CreationError {
     fn convert_glium(self, needed_by: String) -> ErrorKind {
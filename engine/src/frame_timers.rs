@@ -1,7 +1,7 @@
 use super::system::InfallibleSystem;
 use super::tick::Tick;
 use idcontain::{Id, IdSlab};
-use log::info;
+use log::debug;
 use std::borrow::Cow;
 use std::fmt::Write;
 use std::mem;
@@ -76,6 +76,8 @@ impl FrameTimers {
             .map(|last_start| duration_to_seconds(last_start.elapsed()))
     }
 
+    /// Logs the timer, drift and sleep summaries every ten seconds, at debug level (so only
+    /// with `RUST_LOG=debug`); the window title shows the frame rate otherwise.
     fn maybe_log(&mut self) {
         let current_time = Instant::now();
         match self
@@ -110,8 +112,8 @@ impl FrameTimers {
                 seconds_since_logged / times_since_logged * 1000.
             );
         }
-        info!("Frame timer summary:{}", self.log_buffer);
-        info!(
+        debug!("Frame timer summary:{}", self.log_buffer);
+        debug!(
             "Drift summary: n={}, min={:.2}ms mean={:.2}ms max={:.2}ms",
             self.num_ticks,
             self.drift_min * 1e3,
@@ -123,7 +125,7 @@ impl FrameTimers {
         self.drift_mean = 0.0;
         self.num_ticks = 0.0;
 
-        info!(
+        debug!(
             "Sleep summary: n={}, min={:.2}ms mean={:.2}ms max={:.2}ms",
             self.num_slept,
             self.slept_min * 1e3,
@@ -1,10 +1,16 @@
 use super::errors::{Error, Result};
+use super::input_recording::{
+    get_bit, set_bit, InputConfig, InputRecorder, InputReplay, InputSnapshot,
+};
 use super::system::System;
 use super::window::Window;
 use crate::internal_derive::DependenciesFrom;
 pub use glium::glutin::MouseButton;
 pub use glium::glutin::VirtualKeyCode as Scancode;
-use glium::glutin::{DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent};
+use glium::glutin::{
+    DeviceEvent, ElementState, Event, KeyboardInput, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+use log::info;
 use math::Vec2f;
 use num_traits::Zero;
 use std::vec::Vec;
@@ -17,6 +23,9 @@ pub enum Gesture {
     KeyTrigger(VirtualKeyCode),
     ButtonHold(MouseButton),
     ButtonTrigger(MouseButton),
+    /// The mouse wheel was scrolled away from / towards the user by (at least) a line.
+    WheelUp,
+    WheelDown,
     AnyOf(Vec<Gesture>),
     AllOf(Vec<Gesture>),
     QuitTrigger,
@@ -51,6 +60,41 @@ impl Input {
         self.mouse_enabled = enable;
     }
 
+    /// Disabling the keyboard releases all held keys and ignores key events until re-enabled,
+    /// while characters are still available through `text_input`.
+    pub fn set_keyboard_enabled(&mut self, enable: bool) {
+        if !enable {
+            for state in &mut self.keyboard_state[..] {
+                if let ButtonState::Down(_) = *state {
+                    *state = ButtonState::Up(self.current_update_index);
+                }
+            }
+        }
+        self.keyboard_enabled = enable;
+    }
+
+    /// Returns the characters typed since the last update, including control characters like
+    /// `'\r'` and `'\u{8}'` for return and backspace.
+    pub fn text_input(&self) -> &str {
+        &self.text_input
+    }
+
+    /// Whether `code` was pressed this update, like polling `Gesture::KeyTrigger(code)`.
+    pub fn just_pressed(&self, code: Scancode) -> bool {
+        match self.keyboard_state[code as usize] {
+            ButtonState::Down(index) => self.current_update_index == index,
+            ButtonState::Up(_) => false,
+        }
+    }
+
+    /// Whether `code` was released this update (including by disabling the keyboard).
+    pub fn just_released(&self, code: Scancode) -> bool {
+        match self.keyboard_state[code as usize] {
+            ButtonState::Down(_) => false,
+            ButtonState::Up(index) => self.current_update_index == index,
+        }
+    }
+
     pub fn poll_gesture(&self, gesture: &Gesture) -> bool {
         match *gesture {
             Gesture::QuitTrigger => self.quit_requested_index == self.current_update_index,
@@ -58,10 +102,7 @@ impl Input {
                 ButtonState::Down(_) => true,
                 ButtonState::Up(_) => false,
             },
-            Gesture::KeyTrigger(code) => match self.keyboard_state[code as usize] {
-                ButtonState::Down(index) => self.current_update_index == index,
-                ButtonState::Up(_) => false,
-            },
+            Gesture::KeyTrigger(code) => self.just_pressed(code),
             Gesture::ButtonHold(button) => {
                 match self.mouse_button_state[mouse_button_to_index(button)] {
                     ButtonState::Down(_) => true,
@@ -74,6 +115,8 @@ impl Input {
                     ButtonState::Up(_) => false,
                 }
             }
+            Gesture::WheelUp => self.wheel_lines > 0,
+            Gesture::WheelDown => self.wheel_lines < 0,
             Gesture::AnyOf(ref subgestures) => subgestures
                 .iter()
                 .any(|subgesture| self.poll_gesture(subgesture)),
@@ -120,6 +163,7 @@ impl Input {
 
 #[derive(DependenciesFrom)]
 pub struct Dependencies<'context> {
+    config: &'context InputConfig,
     window: &'context mut Window,
 }
 
@@ -130,26 +174,56 @@ pub struct Input {
     mouse_button_state: [ButtonState; NUM_MOUSE_BUTTONS],
     quit_requested_index: UpdateIndex,
 
+    keyboard_enabled: bool,
+    text_input: String,
+
     mouse_enabled: bool,
     mouse_grabbed: bool,
     new_mouse_grabbed: bool,
     mouse_rel: Vec2f,
+    wheel_lines: i32,
+    /// Scrolling left over from previous updates, in lines, for touchpads which scroll by pixels.
+    wheel_remainder: f32,
+
+    recorder: Option<InputRecorder>,
+    /// While replaying, keyboard and mouse events are ignored.
+    replay: Option<InputReplay>,
 }
 
 impl<'context> System<'context> for Input {
     type Dependencies = Dependencies<'context>;
     type Error = Error;
 
-    fn create(_deps: Dependencies) -> Result<Self> {
+    fn create(deps: Dependencies) -> Result<Self> {
+        let recorder = match deps.config.record_path {
+            Some(ref path) => {
+                info!("Recording input to {:?}.", path);
+                Some(InputRecorder::create(path)?)
+            }
+            None => None,
+        };
+        let replay = match deps.config.replay_path {
+            Some(ref path) => {
+                info!("Replaying input from {:?}.", path);
+                Some(InputReplay::open(path)?)
+            }
+            None => None,
+        };
         Ok(Input {
             current_update_index: 1,
             keyboard_state: [ButtonState::Up(0); NUM_SCAN_CODES],
             mouse_button_state: [ButtonState::Up(0); NUM_MOUSE_BUTTONS],
             quit_requested_index: 0,
+            keyboard_enabled: true,
+            text_input: String::new(),
             mouse_enabled: true,
             new_mouse_grabbed: true,
             mouse_grabbed: false,
             mouse_rel: Vec2f::zero(),
+            wheel_lines: 0,
+            wheel_remainder: 0.0,
+            recorder,
+            replay,
         })
     }
 
@@ -183,6 +257,9 @@ impl<'context> System<'context> for Input {
         }
         self.current_update_index += 1;
         self.mouse_rel = Vec2f::zero();
+        self.text_input.clear();
+        let mut wheel = self.wheel_remainder;
+        let live = self.replay.is_none();
         deps.window.events().poll_events(|event| match event {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
@@ -190,6 +267,12 @@ impl<'context> System<'context> for Input {
             } => {
                 self.quit_requested_index = self.current_update_index;
             }
+            Event::WindowEvent {
+                event: WindowEvent::ReceivedCharacter(character),
+                ..
+            } => {
+                self.text_input.push(character);
+            }
             Event::DeviceEvent {
                 event:
                     DeviceEvent::Key(KeyboardInput {
@@ -199,6 +282,9 @@ impl<'context> System<'context> for Input {
                     }),
                 ..
             } => {
+                if !self.keyboard_enabled || !live {
+                    return;
+                }
                 self.keyboard_state[virtual_keycode as usize] = match state {
                     ElementState::Pressed => ButtonState::Down(self.current_update_index),
                     ElementState::Released => ButtonState::Up(self.current_update_index),
@@ -208,7 +294,7 @@ impl<'context> System<'context> for Input {
                 event: DeviceEvent::Motion { axis, value },
                 ..
             } => {
-                if self.mouse_enabled && axis < 2 {
+                if live && self.mouse_enabled && axis < 2 {
                     self.mouse_rel[axis as usize] += value as f32;
                 }
             }
@@ -217,21 +303,105 @@ impl<'context> System<'context> for Input {
                 ..
             } => {
                 let button = button as usize;
-                if self.mouse_enabled && button < NUM_MOUSE_BUTTONS {
+                if live && self.mouse_enabled && button < NUM_MOUSE_BUTTONS {
                     self.mouse_button_state[button] = match state {
                         ElementState::Pressed => ButtonState::Down(self.current_update_index),
                         ElementState::Released => ButtonState::Up(self.current_update_index),
                     }
                 }
             }
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseWheel { delta },
+                ..
+            } => {
+                if live && self.mouse_enabled {
+                    wheel += match delta {
+                        MouseScrollDelta::LineDelta(_, y) => y,
+                        MouseScrollDelta::PixelDelta(position) => {
+                            position.y as f32 / PIXELS_PER_WHEEL_LINE
+                        }
+                    };
+                }
+            }
             _ => {}
         });
+        self.wheel_lines = wheel.trunc() as i32;
+        self.wheel_remainder = wheel.fract();
+
+        let replayed = match self.replay {
+            Some(ref mut replay) => Some(replay.next(self.current_update_index)?),
+            None => None,
+        };
+        match replayed {
+            Some(Some(snapshot)) => self.apply_snapshot(&snapshot),
+            Some(None) => {
+                info!("Input replay finished, quitting.");
+                self.replay = None;
+                self.quit_requested_index = self.current_update_index;
+            }
+            None => {}
+        }
+        if self.recorder.is_some() {
+            let snapshot = self.snapshot();
+            if let Some(ref mut recorder) = self.recorder {
+                recorder.write(&snapshot)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn destroy(self, _deps: Dependencies) -> Result<()> {
+        if let Some(recorder) = self.recorder {
+            recorder.finish()?;
+        }
         Ok(())
     }
 }
 
+impl Input {
+    fn snapshot(&self) -> InputSnapshot {
+        let mut snapshot = InputSnapshot::new(self.current_update_index);
+        for (code, state) in self.keyboard_state.iter().enumerate() {
+            set_bit(&mut snapshot.keys, code, state.is_down());
+        }
+        for (index, state) in self.mouse_button_state.iter().enumerate() {
+            set_bit(&mut snapshot.buttons, index, state.is_down());
+        }
+        snapshot.mouse_rel = [self.mouse_rel[0], self.mouse_rel[1]];
+        snapshot.wheel_lines = self.wheel_lines;
+        snapshot.quit_requested = self.quit_requested_index == self.current_update_index;
+        snapshot
+    }
+
+    /// Presses and releases keys and buttons to match a replayed snapshot, as if their events had
+    /// arrived this update.
+    fn apply_snapshot(&mut self, snapshot: &InputSnapshot) {
+        let index = self.current_update_index;
+        let apply = |states: &mut [ButtonState], bits: &[u64]| {
+            for (bit, state) in states.iter_mut().enumerate() {
+                let down = get_bit(bits, bit);
+                if down != state.is_down() {
+                    *state = if down {
+                        ButtonState::Down(index)
+                    } else {
+                        ButtonState::Up(index)
+                    };
+                }
+            }
+        };
+        apply(&mut self.keyboard_state, &snapshot.keys);
+        apply(&mut self.mouse_button_state, &snapshot.buttons);
+        self.mouse_rel = Vec2f::new(snapshot.mouse_rel[0], snapshot.mouse_rel[1]);
+        self.wheel_lines = snapshot.wheel_lines;
+        if snapshot.quit_requested {
+            self.quit_requested_index = index;
+        }
+    }
+}
+
 const NUM_SCAN_CODES: usize = 512;
 const NUM_MOUSE_BUTTONS: usize = 256;
+const PIXELS_PER_WHEEL_LINE: f32 = 20.0;
 
 type UpdateIndex = u32;
 
@@ -241,6 +411,15 @@ enum ButtonState {
     Down(UpdateIndex),
 }
 
+impl ButtonState {
+    fn is_down(self) -> bool {
+        match self {
+            ButtonState::Down(_) => true,
+            ButtonState::Up(_) => false,
+        }
+    }
+}
+
 fn mouse_button_to_index(button: MouseButton) -> usize {
     match button {
         MouseButton::Left => 1,
@@ -0,0 +1,204 @@
+//! Recording of the input state each update to a file, and replaying it in place of the real
+//! keyboard and mouse, for reproducing a playthrough.
+
+use super::errors::{ErrorKind, Result};
+use failchain::{bail, ResultExt};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind as IoErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Where `Input` records to or replays from, if anywhere.
+#[derive(Clone, Debug, Default)]
+pub struct InputConfig {
+    /// Writes the input state of every update to this file.
+    pub record_path: Option<PathBuf>,
+    /// Reads the input state of every update from this file (written by `record_path`) instead
+    /// of the keyboard and mouse. The game quits when the recording ends.
+    pub replay_path: Option<PathBuf>,
+}
+
+/// The input state after an update: which keys and mouse buttons are held and how far the mouse
+/// moved. Typed text isn't included, so console input isn't replayed.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct InputSnapshot {
+    pub update_index: u32,
+    pub keys: [u64; KEY_WORDS],
+    pub buttons: [u64; BUTTON_WORDS],
+    pub mouse_rel: [f32; 2],
+    pub wheel_lines: i32,
+    pub quit_requested: bool,
+}
+
+impl InputSnapshot {
+    pub fn new(update_index: u32) -> Self {
+        InputSnapshot {
+            update_index,
+            keys: [0; KEY_WORDS],
+            buttons: [0; BUTTON_WORDS],
+            mouse_rel: [0.0; 2],
+            wheel_lines: 0,
+            quit_requested: false,
+        }
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        writer.write_all(&self.update_index.to_le_bytes())?;
+        for word in self.keys.iter().chain(&self.buttons) {
+            writer.write_all(&word.to_le_bytes())?;
+        }
+        for &axis in &self.mouse_rel {
+            writer.write_all(&axis.to_bits().to_le_bytes())?;
+        }
+        writer.write_all(&self.wheel_lines.to_le_bytes())?;
+        writer.write_all(&[self.quit_requested as u8])
+    }
+
+    fn read_from(bytes: &[u8; SNAPSHOT_SIZE]) -> Self {
+        let mut offset = 0;
+        let mut next = |size: usize| {
+            let field = &bytes[offset..offset + size];
+            offset += size;
+            field
+        };
+        let u32_at = |field: &[u8]| u32::from_le_bytes([field[0], field[1], field[2], field[3]]);
+        let u64_at = |field: &[u8]| {
+            let mut word = [0; 8];
+            word.copy_from_slice(field);
+            u64::from_le_bytes(word)
+        };
+
+        let mut snapshot = InputSnapshot::new(u32_at(next(4)));
+        for word in snapshot.keys.iter_mut().chain(&mut snapshot.buttons) {
+            *word = u64_at(next(8));
+        }
+        for axis in &mut snapshot.mouse_rel {
+            *axis = f32::from_bits(u32_at(next(4)));
+        }
+        snapshot.wheel_lines = u32_at(next(4)) as i32;
+        snapshot.quit_requested = next(1)[0] != 0;
+        snapshot
+    }
+}
+
+/// Sets or clears `bit` in a bitmask of `u64` words.
+pub(crate) fn set_bit(words: &mut [u64], bit: usize, value: bool) {
+    let mask = 1 << (bit % 64);
+    if value {
+        words[bit / 64] |= mask;
+    } else {
+        words[bit / 64] &= !mask;
+    }
+}
+
+pub(crate) fn get_bit(words: &[u64], bit: usize) -> bool {
+    words[bit / 64] & (1 << (bit % 64)) != 0
+}
+
+pub(crate) struct InputRecorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl InputRecorder {
+    pub fn create(path: &Path) -> Result<Self> {
+        let mut writer = BufWriter::new(File::create(path).chain_err(|| io_error(path))?);
+        writer.write_all(MAGIC).chain_err(|| io_error(path))?;
+        Ok(InputRecorder {
+            path: path.to_owned(),
+            writer,
+        })
+    }
+
+    pub fn write(&mut self, snapshot: &InputSnapshot) -> Result<()> {
+        snapshot
+            .write_to(&mut self.writer)
+            .chain_err(|| io_error(&self.path))
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush().chain_err(|| io_error(&self.path))
+    }
+}
+
+pub(crate) struct InputReplay {
+    path: PathBuf,
+    reader: BufReader<File>,
+}
+
+impl InputReplay {
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(path).chain_err(|| io_error(path))?);
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic).chain_err(|| io_error(path))?;
+        if &magic != MAGIC {
+            bail!(ErrorKind::InputRecording(format!(
+                "`{}` is not an input recording.",
+                path.display()
+            )));
+        }
+        Ok(InputReplay {
+            path: path.to_owned(),
+            reader,
+        })
+    }
+
+    /// Reads the snapshot of the next update, or `None` at the end of the recording.
+    pub fn next(&mut self, update_index: u32) -> Result<Option<InputSnapshot>> {
+        let mut bytes = [0; SNAPSHOT_SIZE];
+        match self.reader.read_exact(&mut bytes) {
+            Ok(()) => {}
+            Err(ref error) if error.kind() == IoErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error).chain_err(|| io_error(&self.path)),
+        }
+        let snapshot = InputSnapshot::read_from(&bytes);
+        if snapshot.update_index != update_index {
+            bail!(ErrorKind::InputRecording(format!(
+                "`{}` has update {} where {} was expected.",
+                self.path.display(),
+                snapshot.update_index,
+                update_index
+            )));
+        }
+        Ok(Some(snapshot))
+    }
+}
+
+fn io_error(path: &Path) -> ErrorKind {
+    ErrorKind::InputRecording(format!("I/O error when accessing `{}`.", path.display()))
+}
+
+/// Identifies input recordings, and their version.
+const MAGIC: &[u8; 8] = b"RDINPUT1";
+
+pub(crate) const KEY_WORDS: usize = 8;
+pub(crate) const BUTTON_WORDS: usize = 4;
+const SNAPSHOT_SIZE: usize = 4 + (KEY_WORDS + BUTTON_WORDS) * 8 + 2 * 4 + 4 + 1;
+
+#[cfg(test)]
+mod test {
+    use super::{get_bit, set_bit, InputSnapshot, SNAPSHOT_SIZE};
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut snapshot = InputSnapshot::new(42);
+        set_bit(&mut snapshot.keys, 17, true);
+        set_bit(&mut snapshot.keys, 511, true);
+        set_bit(&mut snapshot.buttons, 1, true);
+        snapshot.mouse_rel = [-3.5, 12.25];
+        snapshot.wheel_lines = -2;
+        snapshot.quit_requested = true;
+
+        let mut bytes = Vec::new();
+        snapshot.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len(), SNAPSHOT_SIZE);
+        let mut array = [0; SNAPSHOT_SIZE];
+        array.copy_from_slice(&bytes);
+        let read = InputSnapshot::read_from(&array);
+        assert_eq!(read, snapshot);
+        assert!(get_bit(&read.keys, 511));
+        assert!(!get_bit(&read.keys, 16));
+
+        set_bit(&mut snapshot.keys, 17, false);
+        assert!(!get_bit(&snapshot.keys, 17));
+    }
+}
@@ -4,16 +4,20 @@ pub mod context;
 pub mod system;
 pub mod type_list;
 
+mod debug_lines;
 mod entities;
 mod errors;
 mod frame_timers;
 mod input;
+mod input_recording;
 mod materials;
 mod meshes;
 mod pipeline;
 mod platform;
+mod post_process;
 mod projections;
 mod renderer;
+mod renderer2d;
 mod shaders;
 mod text;
 mod tick;
@@ -22,15 +26,21 @@ mod uniforms;
 mod window;
 
 pub use self::context::{Context, ContextBuilder, ControlFlow};
+pub use self::debug_lines::DebugLines;
 pub use self::entities::{Entities, Entity, EntityId};
 pub use self::errors::{Error, ErrorKind, Result};
 pub use self::frame_timers::{FrameTimerId, FrameTimers};
 pub use self::input::{Analog2d, Gesture, Input, MouseButton, Scancode};
+pub use self::input_recording::InputConfig;
 pub use self::materials::{MaterialId, MaterialRefMut, Materials};
 pub use self::meshes::{Mesh, MeshId, Meshes};
-pub use self::pipeline::RenderPipeline;
+pub use self::pipeline::{DrawCallStats, RenderPipeline};
+pub use self::post_process::{
+    GammaCorrect, PaletteFlash, PostProcess, PostProcessUniforms, ScreenWipe,
+};
 pub use self::projections::{Projection, Projections};
 pub use self::renderer::Renderer;
+pub use self::renderer2d::Renderer2D;
 pub use self::shaders::{ShaderConfig, ShaderId, Shaders};
 pub use self::system::{InfallibleSystem, System};
 pub use self::text::{Text, TextId, TextRenderer};
@@ -38,9 +48,9 @@ pub use self::tick::{Config as TickConfig, Tick, TickIndex};
 pub use self::transforms::Transforms;
 pub use self::uniforms::{
     BufferTextureId, FloatUniformId, Mat4UniformId, Texture2dId, UniformId, Uniforms,
-    Vec2fUniformId,
+    Vec2fUniformId, Vec3fUniformId,
 };
-pub use self::window::{Window, WindowConfig};
+pub use self::window::{VSync, Window, WindowConfig};
 pub use glium::texture::buffer_texture::BufferTextureType;
 pub use glium::texture::{ClientFormat, PixelValue};
 pub use glium::uniforms::{
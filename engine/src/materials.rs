@@ -176,6 +176,18 @@ impl<'a> MaterialRef<'a> {
     pub fn shader(&self) -> &Program {
         self.shader
     }
+
+    pub(crate) fn texture_count(&self) -> u32 {
+        self.uniform_values
+            .iter()
+            .take_while(|uniform| uniform.is_some())
+            .filter(|uniform| match **uniform {
+                Some((_, UniformValue::Texture2d(..)))
+                | Some((_, UniformValue::BufferTexture(..))) => true,
+                _ => false,
+            })
+            .count() as u32
+    }
 }
 
 impl<'material> GliumUniforms for MaterialRef<'material> {
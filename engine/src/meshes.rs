@@ -94,6 +94,20 @@ pub struct MeshRef<'a> {
     indices: Option<&'a IndexBuffer<u32>>,
 }
 
+impl<'a> MeshRef<'a> {
+    /// The number of vertices and triangles a draw of the mesh processes.
+    pub(crate) fn draw_counts(&self) -> (u64, u64) {
+        let vertices = match self.indices {
+            Some(indices) => indices.len(),
+            None => match self.vertices.into() {
+                VerticesSource::VertexBuffer(buffer, _, _) => buffer.get_elements_count(),
+                VerticesSource::Marker { len, .. } => len,
+            },
+        } as u64;
+        (vertices, vertices / 3)
+    }
+}
+
 impl<'a, 'b: 'a> Into<IndicesSource<'a>> for &'a MeshRef<'b> {
     fn into(self) -> IndicesSource<'a> {
         self.indices.map_or(
@@ -1,6 +1,7 @@
 use super::entities::{Entities, Entity, EntityId};
 use super::materials::MaterialId;
 use super::meshes::MeshId;
+use super::post_process::PostProcess;
 use super::system::InfallibleSystem;
 use super::uniforms::{Mat4UniformId, Uniforms};
 use crate::internal_derive::DependenciesFrom;
@@ -23,11 +24,45 @@ impl RenderPipeline {
     }
 
     pub fn attach_model(&mut self, entity: EntityId, mesh: MeshId, material: MaterialId) {
+        self.attach_model_in_pass(entity, mesh, material, RenderPass::Opaque);
+    }
+
+    /// Attaches a model which is rendered after all the opaque ones, for surfaces whose shaders
+    /// discard (alpha test) some of their pixels.
+    pub fn attach_masked_model(&mut self, entity: EntityId, mesh: MeshId, material: MaterialId) {
+        self.attach_model_in_pass(entity, mesh, material, RenderPass::Masked);
+    }
+
+    /// What drawing the scene's models took in the last rendered frame.
+    pub fn query_draw_calls(&self) -> DrawCallStats {
+        self.draw_calls
+    }
+
+    /// Adds a screen-space effect applied to the scene after all the models are drawn, following
+    /// any effects added before it.
+    pub fn add_post_process(&mut self, effect: Box<dyn PostProcess>) {
+        self.post_processes.push(effect);
+    }
+
+    fn attach_model_in_pass(
+        &mut self,
+        entity: EntityId,
+        mesh: MeshId,
+        material: MaterialId,
+        pass: RenderPass,
+    ) {
         debug!(
-            "Attaching model to entity {:?}: mesh={:?} material={:?}",
-            entity, mesh, material
+            "Attaching model to entity {:?}: mesh={:?} material={:?} pass={:?}",
+            entity, mesh, material, pass
         );
-        if let Some(old) = self.models.insert(entity, Model { mesh, material }) {
+        if let Some(old) = self.models.insert(
+            entity,
+            Model {
+                mesh,
+                material,
+                pass,
+            },
+        ) {
             error!(
                 "Entity {:?} already had a model attached (mesh={:?}, material={:?}), replacing.",
                 entity, old.mesh, old.material,
@@ -50,6 +85,21 @@ pub struct RenderPipeline {
     pub(crate) root: EntityId,
 
     pub(crate) camera: Option<EntityId>,
+    pub(crate) post_processes: Vec<Box<dyn PostProcess>>,
+    pub(crate) draw_calls: DrawCallStats,
+}
+
+/// Counts of the work submitted to draw the models in a frame, for profiling. Debug lines, text
+/// and post-processing aren't included.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct DrawCallStats {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    /// Textures passed to draw calls; glium skips rebinding a texture which is already bound, so
+    /// the driver may see fewer.
+    pub texture_binds: u32,
+    /// Vertices processed, counting each index of indexed meshes.
+    pub vertices: u64,
 }
 
 impl<'context> InfallibleSystem<'context> for RenderPipeline {
@@ -75,12 +125,15 @@ impl<'context> InfallibleSystem<'context> for RenderPipeline {
             projection,
             modelview,
             camera: None,
+            post_processes: Vec::new(),
+            draw_calls: DrawCallStats::default(),
         }
     }
 
     fn teardown(&mut self, deps: Dependencies) {
         deps.entities.remove(self.root);
         self.camera = None;
+        self.post_processes.clear();
     }
 }
 
@@ -88,4 +141,16 @@ impl<'context> InfallibleSystem<'context> for RenderPipeline {
 pub(crate) struct Model {
     pub(crate) mesh: MeshId,
     pub(crate) material: MaterialId,
+    pub(crate) pass: RenderPass,
+}
+
+/// Models are rendered in pass order, all the models in a pass before any in the next one.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum RenderPass {
+    Opaque,
+    Masked,
+}
+
+impl RenderPass {
+    pub(crate) const ALL: [RenderPass; 2] = [RenderPass::Opaque, RenderPass::Masked];
 }
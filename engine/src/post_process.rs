@@ -0,0 +1,398 @@
+#![cfg_attr(feature = "cargo-clippy", allow(clippy::forget_copy))]
+
+use super::errors::{ErrorKind, Result};
+use super::uniforms::{FloatUniformId, Uniforms, Vec3fUniformId};
+use super::window::Window;
+use glium::framebuffer::SimpleFrameBuffer;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::texture::{ClientFormat, MipmapsOption, RawImage2d, Texture2d, UncompressedFloatFormat};
+use glium::uniforms::{MagnifySamplerFilter, MinifySamplerFilter, Uniforms as GliumUniforms};
+use glium::{implement_vertex, uniform, DrawParameters, Program, Rect, Surface, VertexBuffer};
+use math::Vec3f;
+use std::borrow::Cow;
+
+/// The per-frame values available to post-processing effects.
+pub struct PostProcessUniforms<'a> {
+    /// Effects read their parameters from uniforms, so that other systems can control them after
+    /// the effect has been handed over to the `RenderPipeline`.
+    pub uniforms: &'a Uniforms,
+    /// The part of the window the scene is drawn in.
+    pub viewport: Rect,
+    /// Game time, in seconds.
+    pub time: f32,
+}
+
+/// A screen-space effect applied to the rendered scene, before text is drawn on top.
+///
+/// Effects are chained in the order they were added to the `RenderPipeline`: each one reads the
+/// output of the previous one (or the scene) from `input` and must write every pixel of `output`.
+pub trait PostProcess {
+    fn apply(
+        &mut self,
+        input: &Texture2d,
+        output: &mut SimpleFrameBuffer,
+        uniforms: &PostProcessUniforms,
+    ) -> Result<()>;
+}
+
+/// Raises colors to the power of `1 / gamma`, brightening the image for gammas above 1.
+pub struct GammaCorrect {
+    quad: ScreenQuad,
+    gamma: FloatUniformId,
+}
+
+impl GammaCorrect {
+    pub fn new(window: &Window, gamma: FloatUniformId) -> Result<Self> {
+        Ok(GammaCorrect {
+            quad: ScreenQuad::new(window, GAMMA_FRAGMENT_SRC)?,
+            gamma,
+        })
+    }
+}
+
+impl PostProcess for GammaCorrect {
+    fn apply(
+        &mut self,
+        input: &Texture2d,
+        output: &mut SimpleFrameBuffer,
+        uniforms: &PostProcessUniforms,
+    ) -> Result<()> {
+        let gamma = uniforms.uniforms.get_float(self.gamma).unwrap_or(1.0);
+        self.quad.draw(
+            output,
+            &uniform! {
+                u_input: sample(input),
+                u_gamma: gamma,
+            },
+        )
+    }
+}
+
+/// Tints the whole screen towards a color, like the red flash when the player takes damage.
+pub struct PaletteFlash {
+    quad: ScreenQuad,
+    color: Vec3fUniformId,
+    intensity: FloatUniformId,
+}
+
+impl PaletteFlash {
+    /// `intensity` is the fraction of the final color taken from `color`, `0` disabling the tint.
+    pub fn new(window: &Window, color: Vec3fUniformId, intensity: FloatUniformId) -> Result<Self> {
+        Ok(PaletteFlash {
+            quad: ScreenQuad::new(window, FLASH_FRAGMENT_SRC)?,
+            color,
+            intensity,
+        })
+    }
+}
+
+impl PostProcess for PaletteFlash {
+    fn apply(
+        &mut self,
+        input: &Texture2d,
+        output: &mut SimpleFrameBuffer,
+        uniforms: &PostProcessUniforms,
+    ) -> Result<()> {
+        let intensity = uniforms.uniforms.get_float(self.intensity).unwrap_or(0.0);
+        if intensity <= 0.0 {
+            input
+                .as_surface()
+                .fill(output, MagnifySamplerFilter::Nearest);
+            return Ok(());
+        }
+        let color = uniforms
+            .uniforms
+            .get_vec3f(self.color)
+            .unwrap_or_else(|| Vec3f::new(0.0, 0.0, 0.0));
+        self.quad.draw(
+            output,
+            &uniform! {
+                u_input: sample(input),
+                u_color: [color.x, color.y, color.z],
+                u_intensity: intensity.min(1.0),
+            },
+        )
+    }
+}
+
+/// DOOM's screen melt: the last frame before a wipe was triggered is split into columns which
+/// slide down off the screen at slightly different speeds, revealing the live scene.
+///
+/// A wipe is triggered by changing the value of the `trigger` uniform (incrementing it, say). Like
+/// the original, the melt is simulated at 35 tics per second, with the screen split into 160
+/// columns and 200 rows; it takes a little under a second.
+pub struct ScreenWipe {
+    quad: ScreenQuad,
+    trigger: FloatUniformId,
+    last_trigger: Option<f32>,
+    melt: Option<Melt>,
+    previous: Texture2d,
+    columns: Texture2d,
+}
+
+impl ScreenWipe {
+    pub fn new(window: &Window, trigger: FloatUniformId) -> Result<Self> {
+        let (width, height) = (window.width(), window.height());
+        Ok(ScreenWipe {
+            quad: ScreenQuad::new(window, WIPE_FRAGMENT_SRC)?,
+            trigger,
+            last_trigger: None,
+            melt: None,
+            previous: Texture2d::empty(window.facade(), width, height)
+                .map_err(ErrorKind::glium("screen_wipe"))?,
+            columns: Texture2d::empty_with_format(
+                window.facade(),
+                UncompressedFloatFormat::F32,
+                MipmapsOption::NoMipmap,
+                WIPE_COLUMNS as u32,
+                1,
+            )
+            .map_err(ErrorKind::glium("screen_wipe"))?,
+        })
+    }
+}
+
+impl PostProcess for ScreenWipe {
+    fn apply(
+        &mut self,
+        input: &Texture2d,
+        output: &mut SimpleFrameBuffer,
+        uniforms: &PostProcessUniforms,
+    ) -> Result<()> {
+        let trigger = uniforms.uniforms.get_float(self.trigger);
+        if trigger != self.last_trigger {
+            if self.last_trigger.is_some() {
+                let seed = trigger.unwrap_or(0.0).to_bits() ^ uniforms.time.to_bits();
+                self.melt = Some(Melt::new(uniforms.time, seed));
+            }
+            self.last_trigger = trigger;
+        }
+
+        if let Some(ref mut melt) = self.melt {
+            let tics = ((uniforms.time - melt.start_time) * WIPE_TIC_RATE) as u32;
+            while melt.tics < tics && !melt.done {
+                melt.step();
+            }
+            if !melt.done {
+                let offsets = melt
+                    .columns
+                    .iter()
+                    .map(|&y| y.max(0) as f32 / WIPE_ROWS as f32)
+                    .collect::<Vec<_>>();
+                self.columns.write(
+                    Rect {
+                        left: 0,
+                        bottom: 0,
+                        width: WIPE_COLUMNS as u32,
+                        height: 1,
+                    },
+                    RawImage2d {
+                        data: Cow::Owned(offsets),
+                        width: WIPE_COLUMNS as u32,
+                        height: 1,
+                        format: ClientFormat::F32,
+                    },
+                );
+                return self.quad.draw(
+                    output,
+                    &uniform! {
+                        u_input: sample(input),
+                        u_previous: sample(&self.previous),
+                        u_columns: sample(&self.columns),
+                    },
+                );
+            }
+        }
+
+        // Keep a copy of every frame while idle, since the frame before the trigger changes is the
+        // one the wipe starts from.
+        self.melt = None;
+        let input = input.as_surface();
+        input.fill(output, MagnifySamplerFilter::Nearest);
+        input.fill(&self.previous.as_surface(), MagnifySamplerFilter::Nearest);
+        Ok(())
+    }
+}
+
+const WIPE_COLUMNS: usize = 160;
+const WIPE_ROWS: i32 = 200;
+const WIPE_TIC_RATE: f32 = 35.0;
+
+/// The state of a melt, as in `wipe_initMelt` and `wipe_doMelt` from the original source.
+struct Melt {
+    start_time: f32,
+    tics: u32,
+    done: bool,
+    /// How far down each column has moved, in rows; negative values are tics left to wait before
+    /// the column starts to move.
+    columns: Vec<i32>,
+}
+
+impl Melt {
+    fn new(start_time: f32, seed: u32) -> Self {
+        let mut random = XorShift(seed | 1);
+        let mut columns = Vec::with_capacity(WIPE_COLUMNS);
+        columns.push(-((random.next() % 16) as i32));
+        for i_column in 1..WIPE_COLUMNS {
+            let shift = (random.next() % 3) as i32 - 1;
+            columns.push((columns[i_column - 1] + shift).min(0).max(-15));
+        }
+        Melt {
+            start_time,
+            tics: 0,
+            done: false,
+            columns,
+        }
+    }
+
+    fn step(&mut self) {
+        self.tics += 1;
+        for y in &mut self.columns {
+            if *y < 0 {
+                *y += 1;
+            } else if *y < WIPE_ROWS {
+                let dy = if *y < 16 { *y + 1 } else { 8 };
+                *y = (*y + dy).min(WIPE_ROWS);
+            }
+        }
+        self.done = self.columns.iter().all(|&y| y == WIPE_ROWS);
+    }
+}
+
+struct XorShift(u32);
+
+impl XorShift {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
+
+/// A quad covering the whole output, drawn with a fragment shader which gets the texture
+/// coordinate in `v_tex_coord`.
+struct ScreenQuad {
+    vertices: VertexBuffer<QuadVertex>,
+    program: Program,
+}
+
+impl ScreenQuad {
+    fn new(window: &Window, fragment_src: &str) -> Result<Self> {
+        let vertices = VertexBuffer::new(
+            window.facade(),
+            &[
+                QuadVertex {
+                    a_pos: [-1.0, -1.0],
+                },
+                QuadVertex { a_pos: [1.0, -1.0] },
+                QuadVertex { a_pos: [-1.0, 1.0] },
+                QuadVertex { a_pos: [1.0, 1.0] },
+            ],
+        )
+        .map_err(ErrorKind::glium("post_process"))?;
+        let program = Program::from_source(window.facade(), QUAD_VERTEX_SRC, fragment_src, None)
+            .map_err(ErrorKind::glium("post_process"))?;
+        Ok(ScreenQuad { vertices, program })
+    }
+
+    fn draw<U: GliumUniforms>(&self, output: &mut SimpleFrameBuffer, uniforms: &U) -> Result<()> {
+        output
+            .draw(
+                &self.vertices,
+                NoIndices(PrimitiveType::TriangleStrip),
+                &self.program,
+                uniforms,
+                &DrawParameters::default(),
+            )
+            .map_err(ErrorKind::glium("post_process"))
+    }
+}
+
+fn sample(texture: &Texture2d) -> glium::uniforms::Sampler<Texture2d> {
+    texture
+        .sampled()
+        .magnify_filter(MagnifySamplerFilter::Nearest)
+        .minify_filter(MinifySamplerFilter::Nearest)
+}
+
+const QUAD_VERTEX_SRC: &str = r#"
+    #version 140
+    in vec2 a_pos;
+    out vec2 v_tex_coord;
+    void main() {
+        v_tex_coord = a_pos * 0.5 + 0.5;
+        gl_Position = vec4(a_pos, 0.0, 1.0);
+    }
+"#;
+
+const GAMMA_FRAGMENT_SRC: &str = r#"
+    #version 140
+    uniform sampler2D u_input;
+    uniform float u_gamma;
+    in vec2 v_tex_coord;
+    out vec4 color;
+    void main() {
+        vec3 scene = texture(u_input, v_tex_coord).rgb;
+        color = vec4(pow(scene, vec3(1.0 / u_gamma)), 1.0);
+    }
+"#;
+
+const FLASH_FRAGMENT_SRC: &str = r#"
+    #version 140
+    uniform sampler2D u_input;
+    uniform vec3 u_color;
+    uniform float u_intensity;
+    in vec2 v_tex_coord;
+    out vec4 color;
+    void main() {
+        vec3 scene = texture(u_input, v_tex_coord).rgb;
+        color = vec4(mix(scene, u_color, u_intensity), 1.0);
+    }
+"#;
+
+const WIPE_FRAGMENT_SRC: &str = r#"
+    #version 140
+    uniform sampler2D u_input;
+    uniform sampler2D u_previous;
+    uniform sampler2D u_columns;
+    in vec2 v_tex_coord;
+    out vec4 color;
+    void main() {
+        int column = min(int(v_tex_coord.x * 160.0), 159);
+        float offset = texelFetch(u_columns, ivec2(column, 0), 0).r;
+        vec2 previous_coord = v_tex_coord + vec2(0.0, offset);
+        if (previous_coord.y < 1.0) {
+            color = texture(u_previous, previous_coord);
+        } else {
+            color = texture(u_input, v_tex_coord);
+        }
+    }
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct QuadVertex {
+    a_pos: [f32; 2],
+}
+
+implement_vertex!(QuadVertex, a_pos);
+
+#[cfg(test)]
+mod test {
+    use super::{Melt, WIPE_ROWS, WIPE_TIC_RATE};
+
+    #[test]
+    fn test_melt_takes_about_a_second() {
+        for seed in 0..16 {
+            let mut melt = Melt::new(0.0, seed);
+            assert!(melt.columns.iter().all(|&y| y <= 0 && y > -16));
+            while !melt.done {
+                melt.step();
+            }
+            assert!(melt.columns.iter().all(|&y| y == WIPE_ROWS));
+            let seconds = melt.tics as f32 / WIPE_TIC_RATE;
+            assert!(seconds > 0.5 && seconds < 1.25, "melt took {}s", seconds);
+        }
+    }
+}
@@ -1,8 +1,11 @@
+use super::debug_lines::DebugLines;
 use super::errors::{Error, ErrorKind, Result};
 use super::materials::Materials;
 use super::meshes::Meshes;
-use super::pipeline::{Model, RenderPipeline};
+use super::pipeline::{DrawCallStats, Model, RenderPass, RenderPipeline};
+use super::post_process::PostProcessUniforms;
 use super::projections::Projections;
+use super::renderer2d::Renderer2D;
 use super::shaders::Shaders;
 use super::system::System;
 use super::text::TextRenderer;
@@ -12,10 +15,13 @@ use super::uniforms::Uniforms;
 use super::window::Window;
 use crate::internal_derive::DependenciesFrom;
 use failchain::ResultExt;
-use glium::{BackfaceCullingMode, Depth, DepthTest, DrawParameters, Surface};
+use glium::framebuffer::{DepthRenderBuffer, SimpleFrameBuffer};
+use glium::texture::{DepthFormat, DepthTexture2dMultisample, Texture2d, Texture2dMultisample};
+use glium::uniforms::MagnifySamplerFilter;
+use glium::{BackfaceCullingMode, BlitTarget, Depth, DepthTest, DrawParameters, Frame, Surface};
 use log::{error, info};
 use math::prelude::*;
-use math::Mat4;
+use math::{Mat4, Trans3};
 
 #[derive(DependenciesFrom)]
 pub struct Dependencies<'context> {
@@ -24,6 +30,8 @@ pub struct Dependencies<'context> {
     materials: &'context Materials,
     shaders: &'context Shaders,
     text: &'context TextRenderer,
+    renderer_2d: &'context mut Renderer2D,
+    debug_lines: &'context mut DebugLines,
     window: &'context Window,
     transforms: &'context Transforms,
     projections: &'context Projections,
@@ -31,9 +39,18 @@ pub struct Dependencies<'context> {
     tick: &'context Tick,
 }
 
+/// How much closer (as a fraction of the depth range) masked surfaces are drawn, so that they win
+/// against opaque surfaces which are coplanar with them instead of Z-fighting.
+///
+/// glium doesn't expose `glPolygonOffset`, so this is implemented by shrinking the depth range of
+/// the masked pass, which biases depth values proportionally to their distance.
+const MASKED_DEPTH_BIAS: f32 = 1e-5;
+
 pub struct Renderer {
-    draw_parameters: DrawParameters<'static>,
+    opaque_draw_parameters: DrawParameters<'static>,
+    masked_draw_parameters: DrawParameters<'static>,
     removed: Vec<usize>,
+    targets: Option<PostProcessTargets>,
 }
 
 impl<'context> System<'context> for Renderer {
@@ -44,31 +61,40 @@ impl<'context> System<'context> for Renderer {
         "renderer"
     }
 
-    fn create(_deps: Dependencies) -> Result<Self> {
-        Ok(Renderer {
-            draw_parameters: DrawParameters {
-                depth: Depth {
-                    test: DepthTest::IfLess,
-                    write: true,
-                    ..Depth::default()
-                },
-                backface_culling: BackfaceCullingMode::CullClockwise,
-                ..DrawParameters::default()
+    fn create(deps: Dependencies) -> Result<Self> {
+        let opaque_draw_parameters = DrawParameters {
+            depth: Depth {
+                test: DepthTest::IfLess,
+                write: true,
+                ..Depth::default()
             },
+            backface_culling: BackfaceCullingMode::CullClockwise,
+            viewport: Some(deps.window.viewport()),
+            ..DrawParameters::default()
+        };
+        let masked_draw_parameters = DrawParameters {
+            depth: Depth {
+                range: (0.0, 1.0 - MASKED_DEPTH_BIAS),
+                ..opaque_draw_parameters.depth
+            },
+            ..opaque_draw_parameters.clone()
+        };
+        Ok(Renderer {
+            opaque_draw_parameters,
+            masked_draw_parameters,
             removed: Vec::with_capacity(32),
+            targets: None,
         })
     }
 
-    fn update(&mut self, deps: Dependencies) -> Result<()> {
+    fn update(&mut self, mut deps: Dependencies) -> Result<()> {
         // If the current tick isn't a frame, skip all rendering.
         if !deps.tick.is_frame() {
             return Ok(());
         }
 
-        let pipe = deps.pipe;
-
         // If no camera is given, skip rendering.
-        let camera_id = if let Some(camera_id) = pipe.camera {
+        let camera_id = if let Some(camera_id) = deps.pipe.camera {
             camera_id
         } else {
             return Ok(());
@@ -81,62 +107,135 @@ impl<'context> System<'context> for Renderer {
                 .expect("singular view transform")
         } else {
             info!("Camera transform missing, disabling renderer.");
-            pipe.camera = None;
+            deps.pipe.camera = None;
             return Ok(());
         };
-        let view_matrix = view_transform.into();
 
         // Set projection.
-        *deps
-            .uniforms
-            .get_mat4_mut(pipe.projection)
-            .expect("projection uniform missing") = *deps
+        let projection = *deps
             .projections
             .get_matrix(camera_id)
             .expect("camera projection missing");
+        *deps
+            .uniforms
+            .get_mat4_mut(deps.pipe.projection)
+            .expect("projection uniform missing") = projection;
 
-        // Render all the models in turn.
         let mut frame = deps.window.draw();
-        for (index, &Model { mesh, material }) in pipe.models.access().iter().enumerate() {
-            // For each model we need to assemble three things to render it: transform, mesh and
-            // material. We get the entity id and query the corresponding systems for it.
-            let entity = pipe
-                .models
-                .index_to_id(index)
-                .expect("bad index enumerating models: mesh");
-
-            // If the mesh is missing, the entity was (probably) removed. So we add it to the
-            // removed stack and continue.
-            let mesh = if let Some(mesh) = deps.meshes.get(mesh) {
-                mesh
-            } else {
-                info!(
-                    "Mesh missing {:?} in model for entity {:?}, removing.",
-                    mesh, entity
-                );
-                self.removed.push(index);
-                continue;
+        if deps.pipe.post_processes.is_empty() {
+            self.render_scene(&mut frame, &mut deps, view_transform, projection)?;
+        } else {
+            // Render into a texture instead, then run it through the effects, alternating between
+            // the two textures for the input and output of each effect.
+            if self.targets.is_none() {
+                self.targets = Some(PostProcessTargets::new(deps.window)?);
+            }
+            let targets = self.targets.take().expect("post-process targets missing");
+            let result = self.render_post_processed(
+                &mut frame,
+                &mut deps,
+                &targets,
+                view_transform,
+                projection,
+            );
+            self.targets = Some(targets);
+            result?;
+        }
+
+        // Render the 2D overlays, then text on top of them.
+        deps.renderer_2d
+            .flush(deps.window, deps.uniforms, &mut frame)
+            .chain_err(|| ErrorKind::System("render bypass", Renderer2D::debug_name()))?;
+
+        // Render text. TODO(cristicbz): text should render itself :(
+        deps.text
+            .render(&mut frame)
+            .chain_err(|| ErrorKind::System("render bypass", TextRenderer::debug_name()))?;
+
+        // TODO(cristicbz): Re-architect a little bit to support rebuilding the context.
+        frame
+            .finish()
+            .expect("Cannot handle context loss currently :(");
+
+        // Remove any missing models.
+        for &index in self.removed.iter().rev() {
+            deps.pipe.models.remove_by_index(index);
+        }
+        self.removed.clear();
+        Ok(())
+    }
+}
+
+impl Renderer {
+    /// Renders all the models and debug lines to `surface`.
+    fn render_scene<S: Surface>(
+        &mut self,
+        surface: &mut S,
+        deps: &mut Dependencies,
+        view_transform: Trans3,
+        projection: Mat4,
+    ) -> Result<()> {
+        let view_matrix = Mat4::from(view_transform);
+        let mut draw_calls = DrawCallStats::default();
+
+        // Render all the models in turn, one pass at a time: masked models go last, so that
+        // the opaque geometry in front of them is already in the depth buffer.
+        for &pass in &RenderPass::ALL {
+            let draw_parameters = match pass {
+                RenderPass::Opaque => &self.opaque_draw_parameters,
+                RenderPass::Masked => &self.masked_draw_parameters,
             };
+            for (index, model) in deps.pipe.models.access().iter().enumerate() {
+                let Model {
+                    mesh,
+                    material,
+                    pass: model_pass,
+                } = *model;
+                if model_pass != pass {
+                    continue;
+                }
 
-            // If the model has a transform, then multiply it with the view transform to get the
-            // modelview matrix. If there is no transform, model is assumed to be in world space, so
-            // modelview = view.
-            *deps
-                .uniforms
-                .get_mat4_mut(pipe.modelview)
-                .expect("modelview uniform missing") =
-                if let Some(model_transform) = deps.transforms.get_absolute(entity) {
-                    Mat4::from(view_transform.concat(model_transform))
+                // For each model we need to assemble three things to render it: transform, mesh
+                // and material. We get the entity id and query the corresponding systems for it.
+                let entity = deps
+                    .pipe
+                    .models
+                    .index_to_id(index)
+                    .expect("bad index enumerating models: mesh");
+
+                // If the mesh is missing, the entity was (probably) removed. So we add it to the
+                // removed stack and continue.
+                let mesh = if let Some(mesh) = deps.meshes.get(mesh) {
+                    mesh
                 } else {
-                    view_matrix
+                    info!(
+                        "Mesh missing {:?} in model for entity {:?}, removing.",
+                        mesh, entity
+                    );
+                    self.removed.push(index);
+                    continue;
                 };
 
-            let material =
-                if let Some(material) = deps.materials.get(deps.shaders, deps.uniforms, material) {
+                // If the model has a transform, then multiply it with the view transform to get
+                // the modelview matrix. If there is no transform, model is assumed to be in world
+                // space, so modelview = view.
+                *deps
+                    .uniforms
+                    .get_mat4_mut(deps.pipe.modelview)
+                    .expect("modelview uniform missing") =
+                    if let Some(model_transform) = deps.transforms.get_absolute(entity) {
+                        Mat4::from(view_transform.concat(model_transform))
+                    } else {
+                        view_matrix
+                    };
+
+                let material = if let Some(material) =
+                    deps.materials.get(deps.shaders, deps.uniforms, material)
+                {
                     material
                 } else {
-                    // If there is a mesh but no material, the model is badly set up. This is an
-                    // error.
+                    // If there is a mesh but no material, the model is badly set up. This is
+                    // an error.
                     error!(
                         "Material missing {:?} in model for entity {:?}, removing.",
                         material, entity
@@ -145,32 +244,118 @@ impl<'context> System<'context> for Renderer {
                     continue;
                 };
 
-            frame
-                .draw(
-                    &mesh,
-                    &mesh,
-                    material.shader(),
-                    &material,
-                    &self.draw_parameters,
-                )
-                .map_err(ErrorKind::glium("renderer"))?;
+                surface
+                    .draw(&mesh, &mesh, material.shader(), &material, draw_parameters)
+                    .map_err(ErrorKind::glium("renderer"))?;
+                let (vertices, triangles) = mesh.draw_counts();
+                draw_calls.draw_calls += 1;
+                draw_calls.vertices += vertices;
+                draw_calls.triangles += triangles;
+                draw_calls.texture_binds += material.texture_count();
+            }
         }
+        deps.pipe.draw_calls = draw_calls;
 
-        // Render text. TODO(cristicbz): text should render itself :(
-        deps.text
-            .render(&mut frame)
-            .chain_err(|| ErrorKind::System("render bypass", TextRenderer::debug_name()))?;
+        // Render debug lines on top of the world.
+        deps.debug_lines
+            .render(deps.window, surface, projection * view_matrix)
+            .chain_err(|| ErrorKind::System("render bypass", DebugLines::debug_name()))?;
+        Ok(())
+    }
 
-        // TODO(cristicbz): Re-architect a little bit to support rebuilding the context.
-        frame
-            .finish()
-            .expect("Cannot handle context loss currently :(");
+    fn render_post_processed(
+        &mut self,
+        frame: &mut Frame,
+        deps: &mut Dependencies,
+        targets: &PostProcessTargets,
+        view_transform: Trans3,
+        projection: Mat4,
+    ) -> Result<()> {
+        let facade = deps.window.facade();
+        match targets.scene {
+            SceneTarget::Single(ref depth) => {
+                let mut scene =
+                    SimpleFrameBuffer::with_depth_buffer(facade, &targets.colors[0], depth)
+                        .map_err(ErrorKind::glium("renderer"))?;
+                deps.window.clear(&mut scene);
+                self.render_scene(&mut scene, deps, view_transform, projection)?;
+            }
+            SceneTarget::Multisample(ref color, ref depth) => {
+                let mut scene = SimpleFrameBuffer::with_depth_buffer(facade, color, depth)
+                    .map_err(ErrorKind::glium("renderer"))?;
+                deps.window.clear(&mut scene);
+                self.render_scene(&mut scene, deps, view_transform, projection)?;
+                // Resolve the samples into the first effect's input.
+                let resolved = SimpleFrameBuffer::new(facade, &targets.colors[0])
+                    .map_err(ErrorKind::glium("renderer"))?;
+                scene.fill(&resolved, MagnifySamplerFilter::Nearest);
+            }
+        }
 
-        // Remove any missing models.
-        for &index in self.removed.iter().rev() {
-            pipe.models.remove_by_index(index);
+        let uniforms = PostProcessUniforms {
+            uniforms: deps.uniforms,
+            viewport: deps.window.viewport(),
+            time: deps.tick.index().0 as f32 * deps.tick.timestep(),
+        };
+        let mut input = 0;
+        for effect in &mut deps.pipe.post_processes {
+            let mut output = SimpleFrameBuffer::new(facade, &targets.colors[1 - input])
+                .map_err(ErrorKind::glium("renderer"))?;
+            effect.apply(&targets.colors[input], &mut output, &uniforms)?;
+            input = 1 - input;
         }
-        self.removed.clear();
+
+        let viewport = deps.window.viewport();
+        targets.colors[input].as_surface().blit_color(
+            &viewport,
+            frame,
+            &BlitTarget {
+                left: viewport.left,
+                bottom: viewport.bottom,
+                width: viewport.width as i32,
+                height: viewport.height as i32,
+            },
+            MagnifySamplerFilter::Nearest,
+        );
         Ok(())
     }
 }
+
+/// Window-sized textures the scene is rendered to when there are post-processing effects.
+struct PostProcessTargets {
+    colors: [Texture2d; 2],
+    scene: SceneTarget,
+}
+
+/// The scene is rendered into the first of the `colors` textures, unless the window is
+/// multisampled; then it's rendered with the same number of samples and resolved into it.
+enum SceneTarget {
+    Single(DepthRenderBuffer),
+    Multisample(Texture2dMultisample, DepthTexture2dMultisample),
+}
+
+impl PostProcessTargets {
+    fn new(window: &Window) -> Result<Self> {
+        let facade = window.facade();
+        let (width, height, samples) = (window.width(), window.height(), window.msaa_samples());
+        let color =
+            || Texture2d::empty(facade, width, height).map_err(ErrorKind::glium("renderer"));
+        let scene = if samples > 1 {
+            SceneTarget::Multisample(
+                Texture2dMultisample::empty(facade, width, height, samples)
+                    .map_err(ErrorKind::glium("renderer"))?,
+                DepthTexture2dMultisample::empty(facade, width, height, samples)
+                    .map_err(ErrorKind::glium("renderer"))?,
+            )
+        } else {
+            SceneTarget::Single(
+                DepthRenderBuffer::new(facade, DepthFormat::I24, width, height)
+                    .map_err(ErrorKind::glium("renderer"))?,
+            )
+        };
+        Ok(PostProcessTargets {
+            colors: [color()?, color()?],
+            scene,
+        })
+    }
+}
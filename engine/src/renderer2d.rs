@@ -0,0 +1,250 @@
+#![cfg_attr(feature = "cargo-clippy", allow(clippy::forget_copy))]
+
+use super::errors::{Error, ErrorKind, Result};
+use super::system::System;
+use super::uniforms::{Texture2dId, UniformId, Uniforms};
+use super::window::Window;
+use glium::index::{NoIndices, PrimitiveType};
+use glium::uniforms::{UniformValue, Uniforms as GliumUniforms};
+use glium::{implement_vertex, Blend, DrawParameters, Program, Surface, VertexBuffer};
+use log::error;
+
+/// Collects lines, filled rectangles and images in screen space to be drawn over the scene in the
+/// next frame, for overlays like the automap and the HUD.
+///
+/// Coordinates are in pixels, from the top-left corner of the window. Everything is drawn in the
+/// order it was queued, with alpha blending and no depth test.
+pub struct Renderer2D {
+    vertices: Vec<Vertex2D>,
+    batches: Vec<Batch>,
+    shape_program: Program,
+    image_program: Program,
+}
+
+impl Renderer2D {
+    /// Queues a line from `(x1, y1)` to `(x2, y2)` for the next frame only.
+    pub fn draw_line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, color: [f32; 4]) {
+        self.push(
+            BatchKind::Lines,
+            &[
+                vertex(x1, y1, 0.0, 0.0, color),
+                vertex(x2, y2, 0.0, 0.0, color),
+            ],
+        );
+    }
+
+    /// Queues a rectangle filled with `color`, with its top-left corner at `(x, y)`.
+    pub fn draw_rect(&mut self, x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) {
+        self.push(BatchKind::Triangles, &quad(x, y, w, h, color));
+    }
+
+    /// Queues `texture` stretched over a rectangle, with its top-left corner at `(x, y)`. The
+    /// texture is looked up in `Uniforms` when drawing, and skipped if it's been removed.
+    pub fn draw_image(&mut self, texture: Texture2dId, x: f32, y: f32, w: f32, h: f32) {
+        self.push(BatchKind::Image(texture), &quad(x, y, w, h, [1.0; 4]));
+    }
+
+    /// Draws (and clears) everything queued since the last flush.
+    pub(crate) fn flush<S: Surface>(
+        &mut self,
+        window: &Window,
+        uniforms: &Uniforms,
+        surface: &mut S,
+    ) -> Result<()> {
+        if self.vertices.is_empty() {
+            return Ok(());
+        }
+        let buffer = VertexBuffer::new(window.facade(), &self.vertices)
+            .map_err(ErrorKind::glium("renderer_2d"))?;
+        self.vertices.clear();
+
+        let draw_parameters = DrawParameters {
+            blend: Blend::alpha_blending(),
+            viewport: Some(window.viewport()),
+            ..DrawParameters::default()
+        };
+        let screen_size = [window.width() as f32, window.height() as f32];
+        for batch in self.batches.drain(..) {
+            let vertices = buffer
+                .slice(batch.start..batch.start + batch.len)
+                .expect("batch out of vertex buffer");
+            let (primitive, program, texture) = match batch.kind {
+                BatchKind::Lines => (PrimitiveType::LinesList, &self.shape_program, None),
+                BatchKind::Triangles => (PrimitiveType::TrianglesList, &self.shape_program, None),
+                BatchKind::Image(texture) => {
+                    let value = uniforms.get_value(UniformId::Texture2d(texture));
+                    if value.is_none() {
+                        error!("Texture {:?} missing, skipping image.", texture);
+                        continue;
+                    }
+                    (PrimitiveType::TrianglesList, &self.image_program, value)
+                }
+            };
+            surface
+                .draw(
+                    vertices,
+                    NoIndices(primitive),
+                    program,
+                    &Uniforms2D {
+                        screen_size,
+                        texture,
+                    },
+                    &draw_parameters,
+                )
+                .map_err(ErrorKind::glium("renderer_2d"))?;
+        }
+        Ok(())
+    }
+
+    /// Appends `vertices`, extending the last batch if it's of the same kind.
+    fn push(&mut self, kind: BatchKind, vertices: &[Vertex2D]) {
+        let start = self.vertices.len();
+        self.vertices.extend_from_slice(vertices);
+        if let Some(batch) = self.batches.last_mut() {
+            if batch.kind == kind {
+                batch.len += vertices.len();
+                return;
+            }
+        }
+        self.batches.push(Batch {
+            kind,
+            start,
+            len: vertices.len(),
+        });
+    }
+}
+
+impl<'context> System<'context> for Renderer2D {
+    type Dependencies = &'context Window;
+    type Error = Error;
+
+    fn debug_name() -> &'static str {
+        "renderer_2d"
+    }
+
+    fn create(window: &Window) -> Result<Self> {
+        Ok(Renderer2D {
+            vertices: Vec::with_capacity(1024),
+            batches: Vec::with_capacity(16),
+            shape_program: Program::from_source(
+                window.facade(),
+                VERTEX_SRC,
+                SHAPE_FRAGMENT_SRC,
+                None,
+            )
+            .map_err(ErrorKind::glium("renderer_2d"))?,
+            image_program: Program::from_source(
+                window.facade(),
+                VERTEX_SRC,
+                IMAGE_FRAGMENT_SRC,
+                None,
+            )
+            .map_err(ErrorKind::glium("renderer_2d"))?,
+        })
+    }
+}
+
+/// A run of consecutive vertices drawn with a single draw call.
+struct Batch {
+    kind: BatchKind,
+    start: usize,
+    len: usize,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum BatchKind {
+    Lines,
+    Triangles,
+    Image(Texture2dId),
+}
+
+struct Uniforms2D<'a> {
+    screen_size: [f32; 2],
+    texture: Option<UniformValue<'a>>,
+}
+
+impl<'a> GliumUniforms for Uniforms2D<'a> {
+    fn visit_values<'b, F>(&'b self, mut set_uniform: F)
+    where
+        F: FnMut(&str, UniformValue<'b>),
+    {
+        set_uniform("u_screen_size", UniformValue::Vec2(self.screen_size));
+        if let Some(texture) = self.texture {
+            set_uniform("u_tex", texture);
+        }
+    }
+}
+
+const VERTEX_SRC: &str = r#"
+    #version 140
+    uniform vec2 u_screen_size;
+    in vec2 a_pos;
+    in vec2 a_uv;
+    in vec4 a_color;
+    out vec2 v_uv;
+    out vec4 v_color;
+    void main() {
+        v_uv = a_uv;
+        v_color = a_color;
+        gl_Position = vec4(a_pos / u_screen_size * vec2(2.0, -2.0) + vec2(-1.0, 1.0), 0.0, 1.0);
+    }
+"#;
+
+const SHAPE_FRAGMENT_SRC: &str = r#"
+    #version 140
+    in vec2 v_uv;
+    in vec4 v_color;
+    out vec4 color;
+    void main() {
+        color = v_color;
+    }
+"#;
+
+const IMAGE_FRAGMENT_SRC: &str = r#"
+    #version 140
+    uniform sampler2D u_tex;
+    in vec2 v_uv;
+    in vec4 v_color;
+    out vec4 color;
+    void main() {
+        color = texture(u_tex, v_uv) * v_color;
+    }
+"#;
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct Vertex2D {
+    a_pos: [f32; 2],
+    a_uv: [f32; 2],
+    a_color: [f32; 4],
+}
+
+implement_vertex!(Vertex2D, a_pos, a_uv, a_color);
+
+fn vertex(x: f32, y: f32, u: f32, v: f32, color: [f32; 4]) -> Vertex2D {
+    Vertex2D {
+        a_pos: [x, y],
+        a_uv: [u, v],
+        a_color: color,
+    }
+}
+
+/// Two triangles covering a rectangle, with the texture's first row at the top.
+fn quad(x: f32, y: f32, w: f32, h: f32, color: [f32; 4]) -> [Vertex2D; 6] {
+    let (top_left, top_right) = (
+        vertex(x, y, 0.0, 0.0, color),
+        vertex(x + w, y, 1.0, 0.0, color),
+    );
+    let (bottom_left, bottom_right) = (
+        vertex(x, y + h, 0.0, 1.0, color),
+        vertex(x + w, y + h, 1.0, 1.0, color),
+    );
+    [
+        top_left,
+        bottom_left,
+        top_right,
+        top_right,
+        bottom_left,
+        bottom_right,
+    ]
+}
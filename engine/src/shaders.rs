@@ -8,22 +8,48 @@ use crate::internal_derive::DependenciesFrom;
 use failchain::ResultExt;
 use glium::program::{Program, ProgramCreationInput};
 use idcontain::IdMapVec;
-use log::{debug, error};
-use std::fs::File;
+use log::{debug, error, info};
+use std::fs::{self, File};
 use std::io::Read;
 use std::io::Result as IoResult;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Copy, Clone)]
 pub struct ShaderId(pub EntityId);
 
 pub struct ShaderConfig {
     pub root_path: PathBuf,
+    pub hot_reload: bool,
+    /// `(name, value)` pairs defined as macros at the top of every shader, so one source file
+    /// can be compiled in several variants.
+    pub defines: Vec<(String, String)>,
+}
+
+impl ShaderConfig {
+    /// Checks that the vertex and fragment sources for every asset exist and are readable.
+    ///
+    /// This is cheap to call before any expensive setup (like parsing a WAD), so that a broken
+    /// install reports the missing path straight away instead of failing halfway through.
+    pub fn validate(&self, asset_paths: &[&str]) -> Result<()> {
+        for &asset_path in asset_paths {
+            let (vertex_path, fragment_path) = source_paths(&self.root_path, asset_path);
+            for path in &[vertex_path, fragment_path] {
+                File::open(path).map_err(|_| ErrorKind::MissingShader {
+                    path: path.display().to_string(),
+                    asset: asset_path.to_owned(),
+                })?;
+            }
+        }
+        Ok(())
+    }
 }
 
 pub struct Shaders {
     map: IdMapVec<Entity, Shader>,
     root: PathBuf,
+    prelude: String,
+    watcher: Option<ShaderWatcher>,
 }
 
 impl Shaders {
@@ -35,43 +61,21 @@ impl Shaders {
         name: &'static str,
         asset_path: &'static str,
     ) -> Result<ShaderId> {
-        let mut fragment_path = self.root.clone();
-        fragment_path.push(asset_path);
-
-        let mut vertex_path = fragment_path.clone();
-        fragment_path.set_extension("frag");
-        vertex_path.set_extension("vert");
-
-        let mut fragment_source = format!("#version {}\n", platform::GLSL_VERSION_STRING);
-        let mut vertex_source = fragment_source.clone();
+        let (vertex_path, fragment_path) = source_paths(&self.root, asset_path);
 
         debug!(
             "Loading shader {:?} (from {}, fragment={:?} and vert={:?})",
             name, asset_path, fragment_path, vertex_path
         );
-        read_utf8_file(&fragment_path, &mut fragment_source)
-            .chain_err(|| ErrorKind::ResourceIo("fragment shader", name))?;
-        read_utf8_file(&vertex_path, &mut vertex_source)
-            .chain_err(|| ErrorKind::ResourceIo("vertex shader", name))?;
-
-        let program = Program::new(
-            window.facade(),
-            ProgramCreationInput::SourceCode {
-                vertex_shader: &vertex_source,
-                tessellation_control_shader: None,
-                tessellation_evaluation_shader: None,
-                geometry_shader: None,
-                fragment_shader: &fragment_source,
-                transform_feedback_varyings: None,
-                // TODO(cristicbz): More configurable things! SRGB should not be hard coded.
-                outputs_srgb: true,
-                uses_point_size: false,
-            },
-        )
-        .map_err(ErrorKind::glium(name))?;
+        let program = compile_program(window, name, &self.prelude, &vertex_path, &fragment_path)?;
         debug!("Shader {:?} loaded successfully", name);
         let id = entities.add(parent, name)?;
-        self.map.insert(id, Shader { program });
+        let source = if self.watcher.is_some() {
+            Some(ShaderSource::new(name, vertex_path, fragment_path))
+        } else {
+            None
+        };
+        self.map.insert(id, Shader { program, source });
         debug!("Added shader {:?} {:?} as child of {:?}.", name, id, parent);
         Ok(ShaderId(id))
     }
@@ -79,15 +83,102 @@ impl Shaders {
     pub fn get(&self, shader_id: ShaderId) -> Option<&Program> {
         self.map.get(shader_id.0).map(|shader| &shader.program)
     }
+
+    fn reload_modified(&mut self, window: &Window) {
+        for shader in self.map.access_mut() {
+            let source = match shader.source {
+                Some(ref mut source) => source,
+                None => continue,
+            };
+            let modified = source.last_modified();
+            if modified == source.modified {
+                continue;
+            }
+            source.modified = modified;
+
+            info!("Shader {:?} changed, recompiling...", source.name);
+            match compile_program(
+                window,
+                source.name,
+                &self.prelude,
+                &source.vertex_path,
+                &source.fragment_path,
+            ) {
+                Ok(program) => {
+                    shader.program = program;
+                    info!("Shader {:?} reloaded.", source.name);
+                }
+                Err(error) => error!(
+                    "Shader {:?} failed to reload, keeping old version: {}",
+                    source.name, error
+                ),
+            }
+        }
+    }
 }
 
 pub struct Shader {
     program: Program,
+    source: Option<ShaderSource>,
+}
+
+/// Polls shader sources for changes when hot-reloading is enabled.
+///
+/// Checking modification times is cheap, but not free, so it only happens every
+/// `POLL_INTERVAL` updates.
+struct ShaderWatcher {
+    updates_until_poll: u32,
+}
+
+impl ShaderWatcher {
+    const POLL_INTERVAL: u32 = 30;
+
+    fn new() -> Self {
+        Self {
+            updates_until_poll: Self::POLL_INTERVAL,
+        }
+    }
+
+    fn should_poll(&mut self) -> bool {
+        if self.updates_until_poll == 0 {
+            self.updates_until_poll = Self::POLL_INTERVAL;
+            true
+        } else {
+            self.updates_until_poll -= 1;
+            false
+        }
+    }
+}
+
+struct ShaderSource {
+    name: &'static str,
+    vertex_path: PathBuf,
+    fragment_path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl ShaderSource {
+    fn new(name: &'static str, vertex_path: PathBuf, fragment_path: PathBuf) -> Self {
+        let mut source = Self {
+            name,
+            vertex_path,
+            fragment_path,
+            modified: None,
+        };
+        source.modified = source.last_modified();
+        source
+    }
+
+    fn last_modified(&self) -> Option<SystemTime> {
+        let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        modified(&self.vertex_path).max(modified(&self.fragment_path))
+    }
 }
 
 #[derive(DependenciesFrom)]
 pub struct Dependencies<'context> {
     config: &'context ShaderConfig,
+    window: &'context Window,
     entities: &'context Entities,
 }
 
@@ -102,6 +193,16 @@ impl<'context> InfallibleSystem<'context> for Shaders {
         Shaders {
             map: IdMapVec::with_capacity(32),
             root: deps.config.root_path.clone(),
+            prelude: prelude(&deps.config.defines),
+            watcher: if deps.config.hot_reload {
+                info!(
+                    "Shader hot-reloading enabled for {:?}.",
+                    deps.config.root_path
+                );
+                Some(ShaderWatcher::new())
+            } else {
+                None
+            },
         }
     }
 
@@ -111,6 +212,14 @@ impl<'context> InfallibleSystem<'context> for Shaders {
                 debug!("Removed shader {:?}.", entity);
             }
         }
+
+        if self
+            .watcher
+            .as_mut()
+            .map_or(false, ShaderWatcher::should_poll)
+        {
+            self.reload_modified(deps.window);
+        }
     }
 
     fn teardown(&mut self, deps: Dependencies) {
@@ -125,6 +234,57 @@ impl<'context> InfallibleSystem<'context> for Shaders {
     }
 }
 
+/// The lines every shader source starts with: the GLSL version, which must come first, then
+/// the configured defines.
+fn prelude(defines: &[(String, String)]) -> String {
+    let mut prelude = format!("#version {}\n", platform::GLSL_VERSION_STRING);
+    for (name, value) in defines {
+        prelude.push_str(&format!("#define {} {}\n", name, value));
+    }
+    prelude
+}
+
+fn compile_program(
+    window: &Window,
+    name: &'static str,
+    prelude: &str,
+    vertex_path: &Path,
+    fragment_path: &Path,
+) -> Result<Program> {
+    let mut fragment_source = prelude.to_owned();
+    let mut vertex_source = fragment_source.clone();
+    read_utf8_file(fragment_path, &mut fragment_source)
+        .chain_err(|| ErrorKind::ResourceIo("fragment shader", name))?;
+    read_utf8_file(vertex_path, &mut vertex_source)
+        .chain_err(|| ErrorKind::ResourceIo("vertex shader", name))?;
+
+    Ok(Program::new(
+        window.facade(),
+        ProgramCreationInput::SourceCode {
+            vertex_shader: &vertex_source,
+            tessellation_control_shader: None,
+            tessellation_evaluation_shader: None,
+            geometry_shader: None,
+            fragment_shader: &fragment_source,
+            transform_feedback_varyings: None,
+            // TODO(cristicbz): More configurable things! SRGB should not be hard coded.
+            outputs_srgb: true,
+            uses_point_size: false,
+        },
+    )
+    .map_err(ErrorKind::glium(name))?)
+}
+
+fn source_paths(root: &Path, asset_path: &str) -> (PathBuf, PathBuf) {
+    let mut fragment_path = root.to_path_buf();
+    fragment_path.push(asset_path);
+
+    let mut vertex_path = fragment_path.clone();
+    fragment_path.set_extension("frag");
+    vertex_path.set_extension("vert");
+    (vertex_path, fragment_path)
+}
+
 fn read_utf8_file(path: &Path, into: &mut String) -> IoResult<()> {
     File::open(path)?.read_to_string(into).map(|_| ())
 }
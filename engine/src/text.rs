@@ -47,7 +47,60 @@ impl ChainErrorKind for ErrorKind {
 
 impl TextRenderer {
     pub fn insert(&mut self, win: &Window, text: &str, pos: Pnt2f, padding: u32) -> TextId {
+        self.insert_with(win, text, padding, |_, _| pos)
+    }
+
+    /// Like `insert`, but `center` is where the middle of the text goes rather than its top-left
+    /// corner.
+    pub fn insert_centered(
+        &mut self,
+        win: &Window,
+        text: &str,
+        center: Pnt2f,
+        padding: u32,
+    ) -> TextId {
+        self.insert_with(win, text, padding, |w, h| {
+            Pnt2f::new(center.x - w * 0.25, center.y - h * 0.25)
+        })
+    }
+
+    /// Changes what an existing text says, keeping its id, visibility, colour and background.
+    /// Returns `false` (and does nothing) if the text was removed.
+    pub fn replace(
+        &mut self,
+        win: &Window,
+        id: TextId,
+        text: &str,
+        pos: Pnt2f,
+        padding: u32,
+    ) -> bool {
+        if !self.slab.contains(id.0) {
+            return false;
+        }
+        let new = self.build(win, text, padding, |_, _| pos);
+        let old = &mut self.slab[id.0];
+        old.texture = new.texture;
+        old.buffer = new.buffer;
+        true
+    }
+
+    /// Creates a text, placed at the position returned by `position` given its size (in
+    /// normalized device coordinates, so twice its size as a fraction of the window).
+    fn insert_with<F>(&mut self, win: &Window, text: &str, padding: u32, position: F) -> TextId
+    where
+        F: FnOnce(f32, f32) -> Pnt2f,
+    {
         debug!("Creating text...");
+        let text = self.build(win, text, padding, position);
+        let id = self.slab.insert(text);
+        debug!("Created text {:?}.", id);
+        TextId(id)
+    }
+
+    fn build<F>(&mut self, win: &Window, text: &str, padding: u32, position: F) -> Text
+    where
+        F: FnOnce(f32, f32) -> Pnt2f,
+    {
         let (width, height) = self.rasterise(text, padding).unwrap();
         let texture = Texture2d::new(
             win.facade(),
@@ -63,8 +116,9 @@ impl TextRenderer {
             width as f32 / win.width() as f32 * 2.0,
             height as f32 / win.height() as f32 * 2.0,
         );
+        let pos = position(w, h);
         let (x, y) = (pos.x * 2.0 - 1.0, 1.0 - pos.y * 2.0 - h);
-        let text = Text {
+        Text {
             buffer: VertexBuffer::immutable(
                 win.facade(),
                 &[
@@ -77,10 +131,9 @@ impl TextRenderer {
             .unwrap(),
             texture,
             visible: true,
-        };
-        let id = self.slab.insert(text);
-        debug!("Created text {:?}.", id);
-        TextId(id)
+            color: [1.0; 4],
+            background: true,
+        }
     }
 
     pub fn remove(&mut self, id: TextId) -> bool {
@@ -103,6 +156,8 @@ impl TextRenderer {
             }
             let uniforms = uniform! {
                 u_tex: &text.texture,
+                u_color: text.color,
+                u_background: text.background,
             };
             frame
                 .draw(
@@ -245,12 +300,24 @@ pub struct Text {
     texture: Texture2d,
     buffer: VertexBuffer<TextVertex>,
     visible: bool,
+    color: [f32; 4],
+    background: bool,
 }
 
 impl Text {
     pub fn set_visible(&mut self, visible: bool) {
         self.visible = visible;
     }
+
+    /// Tints the text, white by default; the alpha applies to the background too.
+    pub fn set_color(&mut self, color: [f32; 4]) {
+        self.color = color;
+    }
+
+    /// Whether to draw the translucent box behind the text, as all texts do by default.
+    pub fn set_background(&mut self, background: bool) {
+        self.background = background;
+    }
 }
 
 struct LayoutIter<'a> {
@@ -299,11 +366,14 @@ const VERTEX_SRC: &str = r#"
 const FRAGMENT_SRC: &str = r#"
     #version 140
     uniform sampler2D u_tex;
+    uniform vec4 u_color;
+    uniform bool u_background;
     in vec2 v_uv;
     out vec4 color;
     void main() {
         vec4 tex_color = texture(u_tex, v_uv);
-        color = vec4(tex_color.g, tex_color.g, tex_color.g, tex_color.r);
+        float alpha = u_background ? tex_color.r : tex_color.g;
+        color = vec4(tex_color.g * u_color.rgb, alpha * u_color.a);
     }
 "#;
 
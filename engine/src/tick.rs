@@ -1,9 +1,17 @@
 use super::system::InfallibleSystem;
+use log::warn;
 use std::thread;
 use std::time::{Duration, Instant};
 
 pub struct Config {
     pub timestep: f32,
+    /// If set, frames are rendered on at most this many ticks per second. Since at most one frame
+    /// is rendered per tick, the frame rate is never higher than the tick rate anyway.
+    ///
+    /// Frames are only rendered every n-th tick, so the limit is rounded down to the tick rate
+    /// divided by a whole number: at 60 ticks per second, 50 becomes 30 and 45 becomes 30, while
+    /// divisors of 60 like 30, 20 or 15 are kept as they are.
+    pub fps_limit: Option<u32>,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug)]
@@ -53,6 +61,9 @@ pub struct Tick {
     slept: f32,
     last_time: Option<Instant>,
     is_frame: bool,
+
+    min_frame_ticks: u64,
+    last_frame_index: TickIndex,
 }
 
 impl<'context> InfallibleSystem<'context> for Tick {
@@ -71,6 +82,22 @@ impl<'context> InfallibleSystem<'context> for Tick {
             slept: 0.0,
             last_time: None,
             is_frame: true,
+
+            min_frame_ticks: config.fps_limit.map_or(1, |fps_limit| {
+                let min_frame_ticks = (1.0 / (fps_limit.max(1) as f32 * config.timestep) - 1e-3)
+                    .ceil()
+                    .max(1.0) as u64;
+                let actual_limit = 1.0 / (min_frame_ticks as f32 * config.timestep);
+                if (actual_limit - fps_limit as f32).abs() > 1e-2 {
+                    warn!(
+                        "Frames can only be limited to the tick rate divided by a whole number, \
+                         limiting to {:.0} FPS instead of {}.",
+                        actual_limit, fps_limit
+                    );
+                }
+                min_frame_ticks
+            }),
+            last_frame_index: TickIndex(0),
         }
     }
 
@@ -116,11 +143,16 @@ impl<'context> InfallibleSystem<'context> for Tick {
         }
         self.last_time = Some(current_time);
 
-        // Render a frame this tick iff the drift is less than one timestep.
-        self.is_frame = self.drift <= self.timestep;
-
         // Update the deterministic tick index.
         self.index.0 += 1;
+
+        // Render a frame this tick iff the drift is less than one timestep and enough ticks
+        // have passed since the last frame to respect the frame rate limit.
+        self.is_frame = self.drift <= self.timestep
+            && self.index.0 - self.last_frame_index.0 >= self.min_frame_ticks;
+        if self.is_frame {
+            self.last_frame_index = self.index;
+        }
     }
 }
 
@@ -5,13 +5,17 @@ use super::window::Window;
 use failchain::bail;
 use glium::buffer::Content as BufferContent;
 use glium::texture::buffer_texture::{BufferTexture, BufferTextureType};
-use glium::texture::{ClientFormat, PixelValue, RawImage2d, Texture2d as GliumTexture2d};
+use glium::texture::{
+    ClientFormat, MipmapsOption, PixelValue, RawImage2d, Texture2d as GliumTexture2d,
+    TextureCreationError, UncompressedFloatFormat,
+};
 use glium::uniforms::{AsUniformValue, SamplerBehavior, UniformValue};
 use idcontain::IdMapVec;
 use log::{debug, error};
-use math::{Mat4, Vec2, Vec2f};
+use math::{Mat4, Vec2, Vec2f, Vec3f};
 use std::borrow::Cow;
 use std::marker::PhantomData;
+use std::result::Result as StdResult;
 
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Copy, Clone)]
 pub struct Texture2dId(EntityId);
@@ -35,6 +39,9 @@ pub struct Mat4UniformId(EntityId);
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Copy, Clone)]
 pub struct Vec2fUniformId(EntityId);
 
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Copy, Clone)]
+pub struct Vec3fUniformId(EntityId);
+
 #[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Copy, Clone)]
 pub enum UniformId {
     Texture2d(Texture2dId),
@@ -42,6 +49,7 @@ pub enum UniformId {
     BufferTextureU8(BufferTextureId<u8>),
     Mat4(Mat4UniformId),
     Vec2f(Vec2fUniformId),
+    Vec3f(Vec3fUniformId),
 }
 
 pub struct Uniforms {
@@ -51,6 +59,7 @@ pub struct Uniforms {
     buffer_textures_u8: IdMapVec<Entity, BufferTexture<u8>>,
     mat4s: IdMapVec<Entity, Mat4>,
     vec2fs: IdMapVec<Entity, Vec2f>,
+    vec3fs: IdMapVec<Entity, Vec3f>,
 }
 
 impl Uniforms {
@@ -63,6 +72,7 @@ impl Uniforms {
             ref mut buffer_textures_u8,
             ref mut mat4s,
             ref mut vec2fs,
+            ref mut vec3fs,
         } = *self;
         for &entity in entities.last_removed() {
             if texture2ds.remove(entity).is_some() {
@@ -80,6 +90,9 @@ impl Uniforms {
             if vec2fs.remove(entity).is_some() {
                 debug!("Removed uniform<vec2> {:?}.", entity);
             }
+            if vec3fs.remove(entity).is_some() {
+                debug!("Removed uniform<vec3> {:?}.", entity);
+            }
         }
     }
 
@@ -99,6 +112,10 @@ impl Uniforms {
         Ok(FloatUniformId(id))
     }
 
+    pub fn get_float(&self, id: FloatUniformId) -> Option<f32> {
+        self.floats.get(id.0).cloned()
+    }
+
     pub fn get_float_mut(&mut self, id: FloatUniformId) -> Option<&mut f32> {
         self.floats.get_mut(id.0)
     }
@@ -143,6 +160,30 @@ impl Uniforms {
         self.vec2fs.get_mut(id.0)
     }
 
+    pub fn add_vec3f(
+        &mut self,
+        entities: &mut Entities,
+        parent: EntityId,
+        name: &'static str,
+        initial: Vec3f,
+    ) -> Result<Vec3fUniformId> {
+        let id = entities.add(parent, name)?;
+        self.vec3fs.insert(id, initial);
+        debug!(
+            "Added vec3f uniform {:?} {:?} as child of {:?}.",
+            name, id, parent
+        );
+        Ok(Vec3fUniformId(id))
+    }
+
+    pub fn get_vec3f(&self, id: Vec3fUniformId) -> Option<Vec3f> {
+        self.vec3fs.get(id.0).cloned()
+    }
+
+    pub fn get_vec3f_mut(&mut self, id: Vec3fUniformId) -> Option<&mut Vec3f> {
+        self.vec3fs.get_mut(id.0)
+    }
+
     pub fn add_texture_2d<'a, PixelT: PixelValue>(
         &mut self,
         window: &Window,
@@ -182,6 +223,33 @@ impl Uniforms {
         Ok(Texture2dId(id))
     }
 
+    /// Like `add_texture_2d`, but stores unclamped RGBA `f32` texels, for passing arbitrary data
+    /// (positions, colours etc.) to shaders through `texelFetch`.
+    pub fn add_float_texture_2d(
+        &mut self,
+        window: &Window,
+        entities: &mut Entities,
+        parent: EntityId,
+        name: &'static str,
+        pixels: &[(f32, f32, f32, f32)],
+        size: Vec2<usize>,
+    ) -> Result<Texture2dId> {
+        debug!(
+            "Creating float texture {:?}: pixels={}, size={:?}",
+            name,
+            pixels.len(),
+            size,
+        );
+        let gl = new_float_texture(window, pixels, size).map_err(ErrorKind::glium(name))?;
+        let id = entities.add(parent, name)?;
+        self.texture2ds.insert(id, Texture2d { gl, sampler: None });
+        debug!(
+            "Added float texture {:?} {:?} as child of {:?}.",
+            name, id, parent
+        );
+        Ok(Texture2dId(id))
+    }
+
     pub fn get_texture_2d_mut(&mut self, texture_id: Texture2dId) -> Option<Texture2dRefMut> {
         self.texture2ds
             .get_mut(texture_id.0)
@@ -270,6 +338,10 @@ impl Uniforms {
                 .vec2fs
                 .get(id.0)
                 .map(|vec2| UniformValue::Vec2([vec2[0], vec2[1]])),
+            UniformId::Vec3f(id) => self
+                .vec3fs
+                .get(id.0)
+                .map(|vec3| UniformValue::Vec3([vec3[0], vec3[1], vec3[2]])),
             UniformId::Mat4(id) => self.mat4s.get(id.0).map(|mat4| {
                 UniformValue::Mat4([
                     [mat4[0][0], mat4[0][1], mat4[0][2], mat4[0][3]],
@@ -326,6 +398,17 @@ impl<'uniforms> Texture2dRefMut<'uniforms> {
         debug!("Replaced texture {:?} successfully.", self.texture_id,);
         Ok(())
     }
+
+    pub fn replace_float_pixels(
+        &mut self,
+        window: &Window,
+        pixels: &[(f32, f32, f32, f32)],
+        size: Vec2<usize>,
+    ) -> Result<()> {
+        self.texture.gl = new_float_texture(window, pixels, size)
+            .map_err(ErrorKind::glium("texture2d.replace_float_pixels"))?;
+        Ok(())
+    }
 }
 
 impl<'context> InfallibleSystem<'context> for Uniforms {
@@ -342,6 +425,7 @@ impl<'context> InfallibleSystem<'context> for Uniforms {
             buffer_textures_u8: IdMapVec::with_capacity(32),
             mat4s: IdMapVec::with_capacity(32),
             vec2fs: IdMapVec::with_capacity(32),
+            vec3fs: IdMapVec::with_capacity(32),
         }
     }
 
@@ -354,6 +438,7 @@ impl<'context> InfallibleSystem<'context> for Uniforms {
             ref mut buffer_textures_u8,
             ref mut mat4s,
             ref mut vec2fs,
+            ref mut vec3fs,
         } = *self;
         for &entity in entities.last_removed() {
             if texture2ds.remove(entity).is_some() {
@@ -371,6 +456,9 @@ impl<'context> InfallibleSystem<'context> for Uniforms {
             if vec2fs.remove(entity).is_some() {
                 debug!("Removed uniform<vec2> {:?}.", entity);
             }
+            if vec3fs.remove(entity).is_some() {
+                debug!("Removed uniform<vec3> {:?}.", entity);
+            }
         }
     }
 
@@ -405,6 +493,10 @@ impl<'context> InfallibleSystem<'context> for Uniforms {
         if !self.vec2fs.is_empty() {
             error!("Uniforms <vec2> leaked, {} instances.", self.vec2fs.len());
         }
+
+        if !self.vec3fs.is_empty() {
+            error!("Uniforms <vec3> leaked, {} instances.", self.vec3fs.len());
+        }
     }
 }
 
@@ -413,6 +505,24 @@ struct Texture2d {
     sampler: Option<SamplerBehavior>,
 }
 
+fn new_float_texture(
+    window: &Window,
+    pixels: &[(f32, f32, f32, f32)],
+    size: Vec2<usize>,
+) -> StdResult<GliumTexture2d, TextureCreationError> {
+    GliumTexture2d::with_format(
+        window.facade(),
+        RawImage2d {
+            data: Cow::Borrowed(pixels),
+            width: size[0] as u32,
+            height: size[1] as u32,
+            format: ClientFormat::F32F32F32F32,
+        },
+        UncompressedFloatFormat::F32F32F32F32,
+        MipmapsOption::NoMipmap,
+    )
+}
+
 impl From<Texture2dId> for UniformId {
     fn from(other: Texture2dId) -> Self {
         UniformId::Texture2d(other)
@@ -442,3 +552,9 @@ impl From<Vec2fUniformId> for UniformId {
         UniformId::Vec2f(other)
     }
 }
+
+impl From<Vec3fUniformId> for UniformId {
+    fn from(other: Vec3fUniformId) -> Self {
+        UniformId::Vec3f(other)
+    }
+}
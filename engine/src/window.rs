@@ -2,7 +2,8 @@ use super::errors::{Error, ErrorKind, Result};
 use super::platform;
 use super::system::System;
 use glium::glutin::{Api, ContextBuilder, EventsLoop, GlProfile, GlRequest, WindowBuilder};
-use glium::{Display, Frame, Surface};
+use glium::{Display, Frame, Rect, Surface};
+use log::warn;
 
 const OPENGL_DEPTH_SIZE: u8 = 24;
 
@@ -10,6 +11,28 @@ pub struct WindowConfig {
     pub width: u32,
     pub height: u32,
     pub title: String,
+    pub vsync: VSync,
+    /// If set, the scene is drawn in the largest centered viewport with this aspect ratio, with
+    /// black bars filling the rest of the window.
+    pub viewport_aspect_ratio: Option<f32>,
+    /// Samples per pixel for multisample anti-aliasing, `1` meaning no anti-aliasing. If
+    /// multisampling isn't supported, the window is created without it.
+    pub msaa_samples: u8,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum VSync {
+    Off,
+    On,
+    /// Like `On`, but tears instead of waiting when a frame is late. glutin can't request this,
+    /// so it currently falls back to `On`.
+    Adaptive,
+}
+
+impl Default for VSync {
+    fn default() -> Self {
+        VSync::On
+    }
 }
 
 pub struct Window {
@@ -17,9 +40,15 @@ pub struct Window {
     events: EventsLoop,
     width: u32,
     height: u32,
+    viewport: Rect,
+    msaa_samples: u32,
+    clear_color: [f32; 3],
 }
 
 impl Window {
+    /// The colour the scene is drawn over until `set_clear_color` is called.
+    pub const DEFAULT_CLEAR_COLOR: [f32; 3] = [0.06, 0.07, 0.09];
+
     pub fn width(&self) -> u32 {
         self.width
     }
@@ -28,16 +57,59 @@ impl Window {
         self.height
     }
 
+    /// The aspect ratio of the viewport the scene is drawn in.
     pub fn aspect_ratio(&self) -> f32 {
-        self.width as f32 / self.height as f32
+        self.viewport.width as f32 / self.viewport.height as f32
+    }
+
+    pub fn viewport(&self) -> Rect {
+        self.viewport
+    }
+
+    /// The number of samples per pixel of the window's framebuffer, `1` without multisampling.
+    pub fn msaa_samples(&self) -> u32 {
+        self.msaa_samples
+    }
+
+    pub fn clear_color(&self) -> [f32; 3] {
+        self.clear_color
+    }
+
+    /// Sets the (sRGB) colour the viewport is cleared to each frame, which shows wherever
+    /// nothing is drawn.
+    pub fn set_clear_color(&mut self, r: f32, g: f32, b: f32) {
+        self.clear_color = [r, g, b];
     }
 
     pub fn draw(&self) -> Frame {
         let mut frame = self.display.draw();
-        frame.clear_all_srgb((0.06, 0.07, 0.09, 0.0), 1.0, 0);
+        self.clear(&mut frame);
         frame
     }
 
+    /// Clears a window-sized surface the same way `draw` clears the frame.
+    pub(crate) fn clear<S: Surface>(&self, surface: &mut S) {
+        if self.viewport.width < self.width || self.viewport.height < self.height {
+            surface.clear_all_srgb((0.0, 0.0, 0.0, 0.0), 1.0, 0);
+        }
+        surface.clear(
+            Some(&self.viewport),
+            Some((
+                self.clear_color[0],
+                self.clear_color[1],
+                self.clear_color[2],
+                0.0,
+            )),
+            true,
+            Some(1.0),
+            Some(0),
+        );
+    }
+
+    pub fn set_title(&self, title: &str) {
+        self.display.gl_window().set_title(title);
+    }
+
     pub fn events(&mut self) -> &mut EventsLoop {
         &mut self.events
     }
@@ -52,29 +124,49 @@ impl<'context> System<'context> for Window {
     type Error = Error;
 
     fn create(config: &'context WindowConfig) -> Result<Self> {
+        let vsync = match config.vsync {
+            VSync::Off => false,
+            VSync::On => true,
+            VSync::Adaptive => {
+                warn!("Adaptive vsync is not supported, using regular vsync instead.");
+                true
+            }
+        };
         let events = EventsLoop::new();
 
         let window = WindowBuilder::new()
             .with_dimensions((config.width, config.height).into())
             .with_title(config.title.clone());
 
-        let context = ContextBuilder::new()
-            .with_gl_profile(GlProfile::Core)
-            .with_gl(GlRequest::Specific(
-                Api::OpenGl,
-                (platform::GL_MAJOR_VERSION, platform::GL_MINOR_VERSION),
-            ))
-            .with_vsync(true)
-            .with_depth_buffer(OPENGL_DEPTH_SIZE);
-
-        let display = Display::new(window, context, &events)
-            .map_err(ErrorKind::create_window(config.width, config.height))?;
+        let samples = match config.msaa_samples {
+            0 | 1 => 0,
+            samples if samples.is_power_of_two() && samples <= 8 => u16::from(samples),
+            samples => {
+                warn!("Invalid MSAA sample count {}, disabling MSAA.", samples);
+                0
+            }
+        };
+        let (display, samples) =
+            match Display::new(window.clone(), context(vsync, samples), &events) {
+                Err(ref error) if samples > 0 => {
+                    warn!(
+                        "Could not create window with {}x MSAA ({}), disabling MSAA.",
+                        samples, error
+                    );
+                    (Display::new(window, context(vsync, 0), &events), 0)
+                }
+                result => (result, samples),
+            };
+        let display = display.map_err(ErrorKind::create_window(config.width, config.height))?;
 
         Ok(Window {
             display,
             events,
             width: config.width,
             height: config.height,
+            viewport: viewport(config.width, config.height, config.viewport_aspect_ratio),
+            msaa_samples: u32::from(samples.max(1)),
+            clear_color: Window::DEFAULT_CLEAR_COLOR,
         })
     }
 
@@ -82,3 +174,35 @@ impl<'context> System<'context> for Window {
         "window"
     }
 }
+
+fn context<'a>(vsync: bool, msaa_samples: u16) -> ContextBuilder<'a> {
+    ContextBuilder::new()
+        .with_gl_profile(GlProfile::Core)
+        .with_gl(GlRequest::Specific(
+            Api::OpenGl,
+            (platform::GL_MAJOR_VERSION, platform::GL_MINOR_VERSION),
+        ))
+        .with_vsync(vsync)
+        .with_depth_buffer(OPENGL_DEPTH_SIZE)
+        .with_multisampling(msaa_samples)
+}
+
+fn viewport(width: u32, height: u32, aspect_ratio: Option<f32>) -> Rect {
+    let full = Rect {
+        left: 0,
+        bottom: 0,
+        width,
+        height,
+    };
+    match aspect_ratio {
+        Some(aspect_ratio) if width as f32 > height as f32 * aspect_ratio => {
+            let viewport_width = (height as f32 * aspect_ratio).round() as u32;
+            Rect {
+                left: (width - viewport_width) / 2,
+                width: viewport_width,
+                ..full
+            }
+        }
+        _ => full,
+    }
+}
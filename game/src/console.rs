@@ -0,0 +1,394 @@
+use super::hud::{CrosshairStyle, Hud};
+use super::player::{Config as PlayerConfig, Player};
+use super::wad_system::WadSystem;
+use engine::{
+    ControlFlow, DependenciesFrom, Gesture, InfallibleSystem, Input, Scancode, TextId,
+    TextRenderer, Window,
+};
+use log::info;
+use math::prelude::*;
+use math::Pnt2f;
+use std::collections::{BTreeMap, VecDeque};
+
+pub struct Bindings {
+    pub toggle: Gesture,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            toggle: Gesture::KeyTrigger(Scancode::Grave),
+        }
+    }
+}
+
+#[derive(DependenciesFrom)]
+pub struct Dependencies<'context> {
+    bindings: &'context Bindings,
+    window: &'context mut Window,
+    input: &'context mut Input,
+    text: &'context mut TextRenderer,
+
+    pub control_flow: &'context mut ControlFlow,
+    pub wad: &'context mut WadSystem,
+    pub player: &'context mut Player,
+    pub player_config: &'context PlayerConfig,
+    pub hud: &'context mut Hud,
+}
+
+/// A console command: takes the arguments after the command name and returns the message to
+/// print back.
+pub type Command = Box<dyn Fn(&mut Dependencies, &[&str]) -> String>;
+
+pub struct CommandRegistry {
+    commands: BTreeMap<&'static str, Command>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        CommandRegistry {
+            commands: BTreeMap::new(),
+        }
+    }
+
+    pub fn register<F>(&mut self, name: &'static str, command: F)
+    where
+        F: Fn(&mut Dependencies, &[&str]) -> String + 'static,
+    {
+        self.commands.insert(name, Box::new(command));
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.commands.keys().cloned()
+    }
+
+    fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.get(name)
+    }
+}
+
+impl Default for CommandRegistry {
+    fn default() -> Self {
+        let mut registry = CommandRegistry::new();
+        registry.register("map", |deps, args| {
+            let name = match args {
+                [name] => name,
+                _ => return "usage: map <level name>".to_owned(),
+            };
+            match deps.wad.level_index_by_name(name) {
+                Some(index) => {
+                    deps.wad.change_level(index);
+                    format!("changing level to {}", name.to_uppercase())
+                }
+                None => format!("no level named {:?}", name),
+            }
+        });
+        registry.register("noclip", |deps, _| {
+            if deps.player.toggle_clip() {
+                "noclip off".to_owned()
+            } else {
+                "noclip on".to_owned()
+            }
+        });
+        registry.register("give", |deps, args| match args {
+            ["all"] => {
+                deps.player.give_all(deps.player_config);
+                "gave all".to_owned()
+            }
+            _ => "usage: give all".to_owned(),
+        });
+        registry.register("crosshair", |deps, args| {
+            let usage = "usage: crosshair on|off|plus|cross|dot|color <r> <g> <b> [a]";
+            let mut crosshair = deps.hud.crosshair();
+            match args.split_first() {
+                Some((&"on", [])) => crosshair.enabled = true,
+                Some((&"off", [])) => crosshair.enabled = false,
+                Some((&"color", components)) => {
+                    let components = components
+                        .iter()
+                        .map(|component| component.parse::<f32>())
+                        .collect::<Result<Vec<_>, _>>();
+                    match components.as_ref().map(|components| &components[..]) {
+                        Ok(&[r, g, b]) => crosshair.color = [r, g, b, crosshair.color[3]],
+                        Ok(&[r, g, b, a]) => crosshair.color = [r, g, b, a],
+                        _ => return usage.to_owned(),
+                    }
+                }
+                Some((style, [])) => match CrosshairStyle::from_name(style) {
+                    Some(style) => {
+                        crosshair.style = style;
+                        crosshair.enabled = true;
+                    }
+                    None => return usage.to_owned(),
+                },
+                _ => return usage.to_owned(),
+            }
+            deps.hud.set_crosshair(crosshair);
+            "crosshair changed".to_owned()
+        });
+        registry.register("clear_color", |deps, args| {
+            let components = args
+                .iter()
+                .map(|component| component.parse::<f32>())
+                .collect::<Result<Vec<_>, _>>();
+            match components.as_ref().map(|components| &components[..]) {
+                Ok(&[r, g, b]) => {
+                    deps.window.set_clear_color(r, g, b);
+                    format!("clear colour set to {} {} {}", r, g, b)
+                }
+                _ => "usage: clear_color <r> <g> <b>".to_owned(),
+            }
+        });
+        registry.register("quit", |deps, _| {
+            deps.control_flow.quit_requested = true;
+            "quitting".to_owned()
+        });
+        registry
+    }
+}
+
+/// A quake-style drop-down console, toggled with the backtick key.
+///
+/// While the console is open, the keyboard is disabled for the other systems and typed
+/// characters go to the console's input line instead. Besides the commands in the
+/// `CommandRegistry`, the console implements `bind <key> <command...>` and `help` itself.
+pub struct Console {
+    history: VecDeque<String>,
+    input: String,
+    registry: CommandRegistry,
+    key_binds: Vec<KeyBind>,
+
+    open: bool,
+    text: Option<TextId>,
+}
+
+impl Console {
+    pub fn registry_mut(&mut self) -> &mut CommandRegistry {
+        &mut self.registry
+    }
+
+    fn execute(&mut self, deps: &mut Dependencies, line: &str) {
+        info!("Console: {}", line);
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let (name, args) = match words.split_first() {
+            Some((name, args)) => (*name, args),
+            None => return,
+        };
+        let output = match name {
+            "bind" => self.bind(args),
+            "help" => format!(
+                "commands: bind help {}",
+                self.registry.names().collect::<Vec<_>>().join(" ")
+            ),
+            _ => match self.registry.get(name) {
+                Some(command) => command(deps, args),
+                None => format!("unknown command {:?}, try 'help'", name),
+            },
+        };
+        self.print(output);
+    }
+
+    fn bind(&mut self, args: &[&str]) -> String {
+        let (key_name, command) = match args.split_first() {
+            Some((key_name, command)) if !command.is_empty() => (*key_name, command.join(" ")),
+            _ => return "usage: bind <key> <command...>".to_owned(),
+        };
+        let key = match parse_key(key_name) {
+            Some(key) => key,
+            None => return format!("unknown key {:?}", key_name),
+        };
+        let output = format!("bound {} to {:?}", key_name.to_uppercase(), command);
+        self.key_binds.retain(|bind| bind.key != key);
+        self.key_binds.push(KeyBind { key, command });
+        output
+    }
+
+    fn print(&mut self, line: String) {
+        if self.history.len() == MAX_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(line);
+    }
+
+    fn set_open(&mut self, deps: &mut Dependencies, open: bool) {
+        self.open = open;
+        self.input.clear();
+        deps.input.set_keyboard_enabled(!open);
+        self.refresh_text(deps);
+    }
+
+    /// Rebuilds the console text, since `TextRenderer` texts can't be edited in place.
+    fn refresh_text(&mut self, deps: &mut Dependencies) {
+        if let Some(text) = self.text.take() {
+            deps.text.remove(text);
+        }
+        if !self.open {
+            return;
+        }
+        let skip = self.history.len().saturating_sub(VISIBLE_LINES);
+        let mut contents = String::new();
+        for line in self.history.iter().skip(skip) {
+            contents.push_str(line);
+            contents.push('\n');
+        }
+        contents.push_str("> ");
+        contents.push_str(&self.input);
+        contents.push('_');
+        let text = deps
+            .text
+            .insert(deps.window, &contents, Pnt2f::origin(), CONSOLE_PADDING);
+        self.text = Some(text);
+    }
+}
+
+impl<'context> InfallibleSystem<'context> for Console {
+    type Dependencies = Dependencies<'context>;
+
+    fn debug_name() -> &'static str {
+        "console"
+    }
+
+    fn create(_deps: Dependencies) -> Self {
+        Console {
+            history: VecDeque::with_capacity(MAX_HISTORY),
+            input: String::new(),
+            registry: CommandRegistry::default(),
+            key_binds: Vec::new(),
+
+            open: false,
+            text: None,
+        }
+    }
+
+    fn update(&mut self, mut deps: Dependencies) {
+        if !self.open {
+            if deps.input.poll_gesture(&deps.bindings.toggle) {
+                // The character for the toggle key is typed this update too, so skip the input.
+                self.set_open(&mut deps, true);
+                return;
+            }
+            let triggered = self
+                .key_binds
+                .iter()
+                .filter(|bind| deps.input.poll_gesture(&Gesture::KeyTrigger(bind.key)))
+                .map(|bind| bind.command.clone())
+                .collect::<Vec<_>>();
+            for command in triggered {
+                self.execute(&mut deps, &command);
+            }
+            return;
+        }
+
+        let typed = deps.input.text_input().to_owned();
+        if typed.is_empty() {
+            return;
+        }
+        for character in typed.chars() {
+            match character {
+                '`' | '~' | '\u{1b}' => {
+                    self.set_open(&mut deps, false);
+                    return;
+                }
+                '\r' | '\n' => {
+                    let line = self.input.split_off(0);
+                    self.print(format!("> {}", line));
+                    self.execute(&mut deps, &line);
+                }
+                '\u{8}' | '\u{7f}' => {
+                    self.input.pop();
+                }
+                character if !character.is_control() => self.input.push(character),
+                _ => {}
+            }
+        }
+        self.refresh_text(&mut deps);
+    }
+
+    fn teardown(&mut self, deps: Dependencies) {
+        if let Some(text) = self.text.take() {
+            deps.text.remove(text);
+        }
+    }
+}
+
+struct KeyBind {
+    key: Scancode,
+    command: String,
+}
+
+fn parse_key(name: &str) -> Option<Scancode> {
+    let name = name.to_uppercase();
+    KEY_NAMES
+        .iter()
+        .find(|&&(key_name, _)| key_name == name)
+        .map(|&(_, key)| key)
+}
+
+const MAX_HISTORY: usize = 64;
+const VISIBLE_LINES: usize = 12;
+const CONSOLE_PADDING: u32 = 6;
+
+const KEY_NAMES: &[(&str, Scancode)] = &[
+    ("A", Scancode::A),
+    ("B", Scancode::B),
+    ("C", Scancode::C),
+    ("D", Scancode::D),
+    ("E", Scancode::E),
+    ("F", Scancode::F),
+    ("G", Scancode::G),
+    ("H", Scancode::H),
+    ("I", Scancode::I),
+    ("J", Scancode::J),
+    ("K", Scancode::K),
+    ("L", Scancode::L),
+    ("M", Scancode::M),
+    ("N", Scancode::N),
+    ("O", Scancode::O),
+    ("P", Scancode::P),
+    ("Q", Scancode::Q),
+    ("R", Scancode::R),
+    ("S", Scancode::S),
+    ("T", Scancode::T),
+    ("U", Scancode::U),
+    ("V", Scancode::V),
+    ("W", Scancode::W),
+    ("X", Scancode::X),
+    ("Y", Scancode::Y),
+    ("Z", Scancode::Z),
+    ("0", Scancode::Key0),
+    ("1", Scancode::Key1),
+    ("2", Scancode::Key2),
+    ("3", Scancode::Key3),
+    ("4", Scancode::Key4),
+    ("5", Scancode::Key5),
+    ("6", Scancode::Key6),
+    ("7", Scancode::Key7),
+    ("8", Scancode::Key8),
+    ("9", Scancode::Key9),
+    ("F1", Scancode::F1),
+    ("F2", Scancode::F2),
+    ("F3", Scancode::F3),
+    ("F4", Scancode::F4),
+    ("F5", Scancode::F5),
+    ("F6", Scancode::F6),
+    ("F7", Scancode::F7),
+    ("F8", Scancode::F8),
+    ("F9", Scancode::F9),
+    ("F10", Scancode::F10),
+    ("F11", Scancode::F11),
+    ("F12", Scancode::F12),
+    ("TAB", Scancode::Tab),
+    ("SPACE", Scancode::Space),
+    ("ENTER", Scancode::Return),
+    ("BACKSPACE", Scancode::Back),
+    ("INSERT", Scancode::Insert),
+    ("DELETE", Scancode::Delete),
+    ("HOME", Scancode::Home),
+    ("END", Scancode::End),
+    ("PAGEUP", Scancode::PageUp),
+    ("PAGEDOWN", Scancode::PageDown),
+    ("UP", Scancode::Up),
+    ("DOWN", Scancode::Down),
+    ("LEFT", Scancode::Left),
+    ("RIGHT", Scancode::Right),
+];
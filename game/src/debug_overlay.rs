@@ -0,0 +1,106 @@
+use super::hud::Bindings;
+use super::level::Level;
+use super::wad_system::WadSystem;
+use engine::{
+    DependenciesFrom, Entities, InfallibleSystem, Input, RenderPipeline, TextId, TextRenderer,
+    Tick, Window,
+};
+use math::Pnt2f;
+use std::fmt::Write;
+use std::mem;
+
+#[derive(DependenciesFrom)]
+pub struct Dependencies<'context> {
+    bindings: &'context Bindings,
+    tick: &'context Tick,
+    window: &'context Window,
+    input: &'context mut Input,
+    text: &'context mut TextRenderer,
+    entities: &'context Entities,
+    pipe: &'context RenderPipeline,
+    wad: &'context WadSystem,
+    level: &'context Level,
+}
+
+/// Level and rendering statistics, updated every frame while shown. F4 toggles it.
+///
+/// The text is created once, hidden, and only re-rasterised when the statistics change; the
+/// strings it's formatted into are reused between frames.
+pub struct DebugOverlay {
+    text: TextId,
+    enabled: bool,
+    contents: String,
+    shown: String,
+}
+
+impl<'context> InfallibleSystem<'context> for DebugOverlay {
+    type Dependencies = Dependencies<'context>;
+
+    fn debug_name() -> &'static str {
+        "debug_overlay"
+    }
+
+    fn create(deps: Dependencies) -> Self {
+        let text = deps
+            .text
+            .insert(deps.window, " ", OVERLAY_POSITION, PADDING);
+        deps.text[text].set_visible(false);
+        DebugOverlay {
+            text,
+            enabled: false,
+            contents: String::with_capacity(OVERLAY_CAPACITY),
+            shown: String::with_capacity(OVERLAY_CAPACITY),
+        }
+    }
+
+    fn update(&mut self, deps: Dependencies) {
+        if deps.input.poll_gesture(&deps.bindings.toggle_debug_overlay) {
+            self.enabled = !self.enabled;
+            deps.text[self.text].set_visible(self.enabled);
+        }
+        if !self.enabled || !deps.tick.is_frame() {
+            return;
+        }
+
+        self.contents.clear();
+        let geometry = &deps.wad.level;
+        let monsters = deps.level.monsters();
+        let alive = monsters.iter().filter(|monster| !monster.is_dead()).count();
+        let stats = deps.pipe.query_draw_calls();
+        // Writing to a `String` can't fail.
+        let _ = write!(
+            self.contents,
+            "Sectors: {}\nLinedefs: {}\nBSP nodes: {}\nMoving sectors: {}\n\
+             Monsters: {} of {}\nProjectiles: {}\nEntities: {}\n\
+             Draw calls: {}, {} triangles",
+            geometry.sectors.len(),
+            geometry.linedefs.len(),
+            geometry.nodes.len(),
+            deps.level.num_active_effects(),
+            alive,
+            monsters.len(),
+            deps.level.num_projectiles(),
+            deps.entities.len(),
+            stats.draw_calls,
+            stats.triangles,
+        );
+        if self.contents != self.shown {
+            deps.text.replace(
+                deps.window,
+                self.text,
+                &self.contents,
+                OVERLAY_POSITION,
+                PADDING,
+            );
+            mem::swap(&mut self.contents, &mut self.shown);
+        }
+    }
+
+    fn teardown(&mut self, deps: Dependencies) {
+        deps.text.remove(self.text);
+    }
+}
+
+const OVERLAY_POSITION: Pnt2f = Pnt2f::new(0.01, 0.08);
+const OVERLAY_CAPACITY: usize = 256;
+const PADDING: u32 = 6;
@@ -3,8 +3,14 @@ use failure::Fail;
 use std::result::Result as StdResult;
 
 #[derive(Clone, Eq, PartialEq, Debug, Fail)]
-#[fail(display = "Game error: {}", 0)]
-pub struct ErrorKind(pub(crate) String);
+pub enum ErrorKind {
+    #[fail(display = "Game error: {}", 0)]
+    Context(String),
+
+    /// A `GameConfig` value is out of range, see `GameConfig::validate`.
+    #[fail(display = "Invalid config: {}", 0)]
+    InvalidConfig(String),
+}
 
 pub type Error = UnboxedError<ErrorKind>;
 pub type Result<T> = StdResult<T, Error>;
@@ -1,22 +1,44 @@
+use super::console::{Bindings as ConsoleBindings, Console};
+use super::debug_overlay::DebugOverlay;
 use super::errors::{ErrorKind, Result};
-use super::game_shaders::GameShaders;
+use super::game_shaders::{
+    ClearColor, GameShaders, LightGradient, LightUniform, RenderQuality, SHADER_ASSETS,
+};
 use super::hud::{Bindings as HudBindings, Hud};
 use super::level::Level;
-use super::player::{Bindings as PlayerBindings, Config as PlayerConfig, Player};
+use super::player::{
+    Bindings as PlayerBindings, Config as PlayerConfig, Player, WidescreenMode,
+    CLASSIC_ASPECT_RATIO,
+};
 use super::wad_system::{Config as WadConfig, WadSystem};
 use super::SHADER_ROOT;
 use engine::type_list::Peek;
 use engine::{
-    Context, ContextBuilder, Entities, FrameTimers, Input, Materials, Meshes, Projections,
-    RenderPipeline, Renderer, ShaderConfig, Shaders, System, TextRenderer, Tick, TickConfig,
-    Transforms, Uniforms, Window, WindowConfig,
+    Context, ContextBuilder, DebugLines, Entities, FrameTimers, Input, InputConfig, Materials,
+    Meshes, Projections, RenderPipeline, Renderer, Renderer2D, ShaderConfig, Shaders, System,
+    TextRenderer, Tick, TickConfig, Transforms, Uniforms, VSync, Window, WindowConfig,
 };
-use failchain::ResultExt;
+use failchain::{ensure, ResultExt};
+use math::Deg;
 use std::marker::PhantomData;
 use std::path::PathBuf;
 
+/// Whether the game wants to keep running after a frame, see `Game::run_frame`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EngineState {
+    Running,
+    Quit,
+}
+
 pub trait Game {
+    /// Runs frames until quit is requested, then shuts down.
     fn run(&mut self) -> Result<()>;
+
+    /// Runs a single tick of every system, for callers which drive their own loop. Ticks are a
+    /// fixed timestep, kept in step with real time by the `Tick` system, so there's no delta to
+    /// pass in. Once it returns `Quit`, the caller should call `destroy`.
+    fn run_frame(&mut self) -> Result<EngineState>;
+
     fn destroy(&mut self) -> Result<()>;
     fn num_levels(&self) -> usize;
     fn load_level(&mut self, level_index: usize) -> Result<()>;
@@ -25,28 +47,128 @@ pub trait Game {
 #[derive(Clone)]
 pub struct GameConfig {
     pub wad_file: PathBuf,
+    pub pwad_files: Vec<PathBuf>,
     pub metadata_file: PathBuf,
     pub fov: f32,
     pub width: u32,
     pub height: u32,
     pub version: &'static str,
     pub initial_level_index: usize,
+    pub thing_flags_mask: u16,
+    pub fps_limit: Option<u32>,
+    pub vsync: VSync,
+    pub widescreen_mode: WidescreenMode,
+    pub freelook: bool,
+    pub msaa_samples: u8,
+    pub hot_shaders: bool,
+    /// How much darker than floors ceilings are lit, as a fraction of the sector light level.
+    pub light_gradient: f32,
+    /// Writes the input state of every tic to this file.
+    pub record_input: Option<PathBuf>,
+    /// Plays the input recorded in this file instead of reading the keyboard and mouse.
+    pub replay_input: Option<PathBuf>,
+    pub clear_color: ClearColor,
+    pub rendering_quality: RenderQuality,
 }
 
+impl GameConfig {
+    /// Checks the settings the window and projection can't work with, so that they are reported
+    /// up front instead of producing a broken window or view.
+    ///
+    /// The level index is checked once the WAD is open, since that's when the number of levels
+    /// is known.
+    pub fn validate(&self) -> Result<()> {
+        ensure!(
+            self.fov >= MIN_FOV && self.fov <= MAX_FOV,
+            ErrorKind::InvalidConfig,
+            "Field of view {} is outside the supported {}..={} degrees.",
+            self.fov,
+            MIN_FOV,
+            MAX_FOV
+        );
+        for &(name, size) in &[("width", self.width), ("height", self.height)] {
+            ensure!(
+                size >= MIN_WINDOW_SIZE,
+                ErrorKind::InvalidConfig,
+                "Window {} {} is too small, it must be at least {} pixels.",
+                name,
+                size,
+                MIN_WINDOW_SIZE
+            );
+            ensure!(
+                size % 2 == 0,
+                ErrorKind::InvalidConfig,
+                "Window {} {} must be an even number of pixels.",
+                name,
+                size
+            );
+        }
+        ensure!(
+            self.light_gradient >= 0.0 && self.light_gradient <= 1.0,
+            ErrorKind::InvalidConfig,
+            "Light gradient {} is outside 0..=1.",
+            self.light_gradient
+        );
+        if let ClearColor::Fixed(color) = self.clear_color {
+            ensure!(
+                color
+                    .iter()
+                    .all(|&component| component >= 0.0 && component <= 1.0),
+                ErrorKind::InvalidConfig,
+                "Clear colour {:?} has components outside 0..=1.",
+                color
+            );
+        }
+        Ok(())
+    }
+}
+
+/// In degrees, the range of supported fields of view.
+const MIN_FOV: f32 = 10.0;
+const MAX_FOV: f32 = 170.0;
+
+/// In pixels, the smallest window width or height.
+const MIN_WINDOW_SIZE: u32 = 16;
+
 pub fn create(config: &GameConfig) -> Result<impl Game> {
+    config.validate()?;
+
+    let shader_config = ShaderConfig {
+        root_path: SHADER_ROOT.into(),
+        hot_reload: config.hot_shaders,
+        defines: config.rendering_quality.shader_defines(),
+    };
+    shader_config
+        .validate(SHADER_ASSETS)
+        .chain_err(|| ErrorKind::Context("while validating shaders".to_owned()))?;
+
+    let mut player_config = PlayerConfig::default();
+    player_config.widescreen_mode = config.widescreen_mode;
+    player_config.freelook = config.freelook;
+    player_config.fov = Deg(config.fov);
+
     let context = (|| {
         ContextBuilder::new()
             // Engine configs and systems.
             .inject(TickConfig {
                 timestep: 1.0 / 60.0,
+                fps_limit: config.fps_limit,
             })
             .inject(WindowConfig {
                 width: config.width,
                 height: config.height,
                 title: format!("Rusty Doom v{}", config.version),
+                vsync: config.vsync,
+                viewport_aspect_ratio: match config.widescreen_mode {
+                    WidescreenMode::Pillarbox => Some(CLASSIC_ASPECT_RATIO),
+                    WidescreenMode::Stretch | WidescreenMode::Expand => None,
+                },
+                msaa_samples: config.msaa_samples,
             })
-            .inject(ShaderConfig {
-                root_path: SHADER_ROOT.into(),
+            .inject(shader_config)
+            .inject(InputConfig {
+                record_path: config.record_input.clone(),
+                replay_path: config.replay_input.clone(),
             })
             .system(Tick::bind())?
             .system(FrameTimers::bind())?
@@ -61,24 +183,36 @@ pub fn create(config: &GameConfig) -> Result<impl Game> {
             .system(Materials::bind())?
             .system(RenderPipeline::bind())?
             .system(TextRenderer::bind())?
+            .system(Renderer2D::bind())?
+            .system(DebugLines::bind())?
             // Game configs and systems.
             .inject(WadConfig {
                 wad_path: config.wad_file.clone(),
+                patch_paths: config.pwad_files.clone(),
                 metadata_path: config.metadata_file.clone(),
                 initial_level_index: config.initial_level_index,
+                thing_flags_mask: config.thing_flags_mask,
             })
             .inject(HudBindings::default())
+            .inject(ConsoleBindings::default())
             .inject(PlayerBindings::default())
-            .inject(PlayerConfig::default())
+            .inject(player_config)
+            .inject(LightUniform {
+                gradient: LightGradient::with_strength(config.light_gradient),
+                ..LightUniform::default()
+            })
+            .inject(config.clear_color)
             .system(WadSystem::bind())?
             .system(GameShaders::bind())?
             .system(Level::bind())?
             .system(Hud::bind())?
+            .system(DebugOverlay::bind())?
             .system(Player::bind())?
+            .system(Console::bind())?
             .system(Renderer::bind())?
             .build()
     })()
-    .chain_err(|| ErrorKind("during setup".to_owned()))?;
+    .chain_err(|| ErrorKind::Context("during setup".to_owned()))?;
 
     Ok(GameImpl::new(context))
 }
@@ -108,15 +242,26 @@ where
     ContextT: Context + Peek<WadSystem, WadIndexT>,
 {
     fn run(&mut self) -> Result<()> {
-        self.context
-            .run()
-            .chain_err(|| ErrorKind("during run".to_owned()))?;
+        while self.run_frame()? == EngineState::Running {}
         self.context
             .destroy()
-            .chain_err(|| ErrorKind("during shutdown".to_owned()))?;
+            .chain_err(|| ErrorKind::Context("during shutdown".to_owned()))?;
         Ok(())
     }
 
+    fn run_frame(&mut self) -> Result<EngineState> {
+        if !self.context.quit_requested() {
+            self.context
+                .step()
+                .chain_err(|| ErrorKind::Context("during run".to_owned()))?;
+        }
+        Ok(if self.context.quit_requested() {
+            EngineState::Quit
+        } else {
+            EngineState::Running
+        })
+    }
+
     fn num_levels(&self) -> usize {
         let wad = self.context.peek();
         wad.archive.num_levels()
@@ -127,17 +272,17 @@ where
         wad.change_level(level_index);
         self.context
             .step()
-            .chain_err(|| ErrorKind("during load_level first step".to_owned()))?;
+            .chain_err(|| ErrorKind::Context("during load_level first step".to_owned()))?;
         self.context
             .step()
-            .chain_err(|| ErrorKind("during load_level second step".to_owned()))?;
+            .chain_err(|| ErrorKind::Context("during load_level second step".to_owned()))?;
         Ok(())
     }
 
     fn destroy(&mut self) -> Result<()> {
         self.context
             .destroy()
-            .chain_err(|| ErrorKind("during explicit destroy".to_owned()))?;
+            .chain_err(|| ErrorKind::Context("during explicit destroy".to_owned()))?;
         Ok(())
     }
 }
@@ -150,3 +295,88 @@ where
         let _ = self.context.destroy();
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::GameConfig;
+    use crate::errors::ErrorKind;
+    use crate::game_shaders::{ClearColor, RenderQuality};
+    use crate::player::WidescreenMode;
+    use engine::VSync;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_validate_config() {
+        let config = GameConfig {
+            wad_file: PathBuf::from("doom1.wad"),
+            pwad_files: Vec::new(),
+            metadata_file: PathBuf::from("doom.toml"),
+            fov: 65.0,
+            width: 1280,
+            height: 720,
+            version: "test",
+            initial_level_index: 0,
+            thing_flags_mask: 0,
+            fps_limit: None,
+            vsync: VSync::On,
+            widescreen_mode: WidescreenMode::default(),
+            freelook: false,
+            msaa_samples: 0,
+            hot_shaders: false,
+            light_gradient: 0.0,
+            record_input: None,
+            replay_input: None,
+            clear_color: ClearColor::Sky,
+            rendering_quality: RenderQuality::High,
+        };
+        assert!(config.validate().is_ok());
+        let error = GameConfig {
+            fov: 0.0,
+            ..config.clone()
+        }
+        .validate()
+        .unwrap_err();
+        match error.kind() {
+            ErrorKind::InvalidConfig(_) => {}
+            kind => panic!("expected an invalid config error, got {:?}", kind),
+        }
+        for &fov in &[0.0, -65.0, 5.0, 175.0] {
+            assert!(GameConfig {
+                fov,
+                ..config.clone()
+            }
+            .validate()
+            .is_err());
+        }
+        assert!(GameConfig {
+            width: 0,
+            ..config.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(GameConfig {
+            height: 8,
+            ..config.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(GameConfig {
+            width: 1281,
+            ..config.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(GameConfig {
+            light_gradient: 1.5,
+            ..config.clone()
+        }
+        .validate()
+        .is_err());
+        assert!(GameConfig {
+            clear_color: ClearColor::Fixed([1.0, 0.0, 2.0]),
+            ..config
+        }
+        .validate()
+        .is_err());
+    }
+}
@@ -2,15 +2,114 @@ use super::wad_system::WadSystem;
 use engine::{
     BufferTextureId, BufferTextureType, ClientFormat, DependenciesFrom, Entities, EntityId, Error,
     FloatUniformId, MagnifySamplerFilter, MaterialId, Materials, MinifySamplerFilter,
-    RenderPipeline, Result, SamplerBehavior, SamplerWrapFunction, ShaderId, Shaders, System,
-    Texture2dId, Tick, Uniforms, Window,
+    RenderPipeline, Result, SamplerBehavior, SamplerWrapFunction, ScreenWipe, ShaderId, Shaders,
+    System, Texture2dId, Tick, Uniforms, Vec2fUniformId, Vec3fUniformId, Window,
 };
-use log::{error, info};
-use math::Vec2;
+use log::{error, info, warn};
+use math::prelude::*;
+use math::{Vec2, Vec2f, Vec3f};
 use wad::tex::BoundsLookup;
 use wad::types::{COLORMAP_SIZE, PALETTE_SIZE};
 use wad::util::{is_sky_flat, is_untextured};
-use wad::{OpaqueImage as WadOpaqueImage, TransparentImage as WadTransparentImage, WadName};
+use wad::{
+    DynamicLight, OpaqueImage as WadOpaqueImage, TransparentImage as WadTransparentImage, WadName,
+};
+
+/// Shader assets (relative to the shader root) loaded by `GameShaders`.
+pub const SHADER_ASSETS: &[&str] = &["static", "sky", "sprite"];
+
+/// Maximum number of dynamic lights per level, the size of the lights texture. Lower rendering
+/// qualities only shade with the first few.
+pub const MAX_DYNAMIC_LIGHTS: usize = 32;
+
+/// Trades shading detail for speed by compiling the shaders with different defines.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderQuality {
+    /// No dynamic lights.
+    Low,
+    /// Only the first eight of the level's dynamic lights.
+    Medium,
+    /// All of `MAX_DYNAMIC_LIGHTS`.
+    High,
+}
+
+impl RenderQuality {
+    /// The `ShaderConfig::defines` for this quality.
+    pub fn shader_defines(self) -> Vec<(String, String)> {
+        let max_lights = match self {
+            RenderQuality::Low => 0,
+            RenderQuality::Medium => 8,
+            RenderQuality::High => MAX_DYNAMIC_LIGHTS,
+        };
+        vec![("MAX_LIGHTS".to_owned(), max_lights.to_string())]
+    }
+}
+
+impl Default for RenderQuality {
+    fn default() -> Self {
+        RenderQuality::High
+    }
+}
+
+/// Directional light applied to walls and flats on top of sector lighting.
+///
+/// The defaults leave the original sector lighting untouched; raise `diffuse` (and lower `ambient`)
+/// to shade surfaces by how much they face away from `direction`.
+#[derive(Copy, Clone, Debug)]
+pub struct LightUniform {
+    pub direction: Vec3f,
+    pub ambient: f32,
+    pub diffuse: f32,
+    pub gradient: LightGradient,
+}
+
+impl Default for LightUniform {
+    fn default() -> Self {
+        LightUniform {
+            direction: Vec3f::new(0.5, -1.0, 0.3).normalize(),
+            ambient: 1.0,
+            diffuse: 0.0,
+            gradient: LightGradient::with_strength(0.0),
+        }
+    }
+}
+
+/// Scales the light of walls and flats by their height within their sector, interpolating from
+/// `floor_scale` at the floor to `ceiling_scale` at the ceiling, as a cheap stand-in for ambient
+/// occlusion.
+#[derive(Copy, Clone, Debug)]
+pub struct LightGradient {
+    pub floor_scale: f32,
+    pub ceiling_scale: f32,
+}
+
+impl LightGradient {
+    /// A gradient leaving floors as they are and darkening ceilings by `strength` (a fraction of
+    /// the sector's light); zero is the original flat lighting.
+    pub fn with_strength(strength: f32) -> Self {
+        LightGradient {
+            floor_scale: 1.0,
+            ceiling_scale: 1.0 - strength,
+        }
+    }
+}
+
+/// The colour the window is cleared to, which shows wherever the level doesn't cover the view
+/// (like gaps in broken geometry).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ClearColor {
+    /// The average colour of the level's sky texture, or `Window::DEFAULT_CLEAR_COLOR` if it
+    /// doesn't have one.
+    Sky,
+    /// An (sRGB) colour for every level, e.g. magenta to spot what isn't drawn.
+    Fixed([f32; 3]),
+}
+
+impl Default for ClearColor {
+    fn default() -> Self {
+        ClearColor::Sky
+    }
+}
 
 pub struct AtlasMaterial {
     pub material: MaterialId,
@@ -36,12 +135,48 @@ impl GameShaders {
     pub fn level_materials(&self) -> &LevelMaterials {
         &self.level
     }
+
+    /// Uploads the dynamic lights for the current level, dropping any beyond `MAX_DYNAMIC_LIGHTS`.
+    pub fn set_dynamic_lights(
+        &self,
+        window: &Window,
+        uniforms: &mut Uniforms,
+        lights: &[DynamicLight],
+    ) -> Result<()> {
+        if lights.len() > MAX_DYNAMIC_LIGHTS {
+            warn!(
+                "Too many dynamic lights ({}), only the first {} are used.",
+                lights.len(),
+                MAX_DYNAMIC_LIGHTS
+            );
+        }
+        // The first row holds origins and radii, the second colours.
+        let mut texels = vec![(0.0, 0.0, 0.0, 0.0); MAX_DYNAMIC_LIGHTS * 2];
+        for (i_light, light) in lights.iter().take(MAX_DYNAMIC_LIGHTS).enumerate() {
+            let DynamicLight {
+                origin,
+                radius,
+                color,
+            } = *light;
+            texels[i_light] = (origin.x, origin.y, origin.z, radius);
+            texels[MAX_DYNAMIC_LIGHTS + i_light] = (color.x, color.y, color.z, 1.0);
+        }
+        uniforms
+            .get_texture_2d_mut(self.globals.dynamic_lights)
+            .expect("missing dynamic lights texture")
+            .replace_float_pixels(window, &texels, Vec2::new(MAX_DYNAMIC_LIGHTS, 2))?;
+        *uniforms
+            .get_float_mut(self.globals.num_dynamic_lights)
+            .expect("missing number of dynamic lights") =
+            lights.len().min(MAX_DYNAMIC_LIGHTS) as f32;
+        Ok(())
+    }
 }
 
 #[derive(DependenciesFrom)]
 pub struct Dependencies<'context> {
     tick: &'context Tick,
-    window: &'context Window,
+    window: &'context mut Window,
     entities: &'context mut Entities,
     shaders: &'context mut Shaders,
     uniforms: &'context mut Uniforms,
@@ -49,6 +184,8 @@ pub struct Dependencies<'context> {
     materials: &'context mut Materials,
 
     wad: &'context mut WadSystem,
+    light: &'context LightUniform,
+    clear_color: &'context ClearColor,
 }
 
 impl<'context> System<'context> for GameShaders {
@@ -65,6 +202,10 @@ impl<'context> System<'context> for GameShaders {
 
         let globals = deps.load_globals(globals_id)?;
         let level = deps.load_level(&globals, level_id)?;
+        deps.render.add_post_process(Box::new(ScreenWipe::new(
+            deps.window,
+            globals.wipe_trigger,
+        )?));
 
         Ok(GameShaders {
             globals_id,
@@ -85,6 +226,10 @@ impl<'context> System<'context> for GameShaders {
                 .uniforms
                 .get_float_mut(self.globals.time)
                 .expect("missing time") = 0.0;
+            *deps
+                .uniforms
+                .get_float_mut(self.globals.wipe_trigger)
+                .expect("missing wipe trigger") += 1.0;
         } else {
             *deps
                 .uniforms
@@ -92,6 +237,21 @@ impl<'context> System<'context> for GameShaders {
                 .expect("missing time") += deps.tick.timestep();
         }
 
+        let light = deps.light;
+        let light_uniforms = &self.globals.light;
+        *deps
+            .uniforms
+            .get_vec3f_mut(light_uniforms.direction)
+            .expect("missing light direction") = light.direction;
+        *deps
+            .uniforms
+            .get_float_mut(light_uniforms.ambient)
+            .expect("missing light ambient") = light.ambient;
+        *deps
+            .uniforms
+            .get_float_mut(light_uniforms.diffuse)
+            .expect("missing light diffuse") = light.diffuse;
+
         Ok(())
     }
 
@@ -112,13 +272,24 @@ pub struct GameShaders {
 
 struct Globals {
     time: FloatUniformId,
+    light: LightUniformIds,
+    dynamic_lights: Texture2dId,
+    num_dynamic_lights: FloatUniformId,
     lights_buffer_texture: BufferTextureId<u8>,
+    wipe_trigger: FloatUniformId,
     palette: Texture2dId,
     static_shader: ShaderId,
     sky_shader: ShaderId,
     sprite_shader: ShaderId,
 }
 
+struct LightUniformIds {
+    direction: Vec3fUniformId,
+    ambient: FloatUniformId,
+    diffuse: FloatUniformId,
+    gradient: Vec2fUniformId,
+}
+
 impl<'context> Dependencies<'context> {
     fn load_palette(&mut self, parent: EntityId) -> Result<Texture2dId> {
         let palette = self.wad.textures.build_palette_texture(0, 0, 32);
@@ -149,6 +320,46 @@ impl<'context> Dependencies<'context> {
         let time = self
             .uniforms
             .add_float(self.entities, parent, "time_uniform", 0.0)?;
+        let light = LightUniformIds {
+            direction: self.uniforms.add_vec3f(
+                self.entities,
+                parent,
+                "light_direction_uniform",
+                self.light.direction,
+            )?,
+            ambient: self.uniforms.add_float(
+                self.entities,
+                parent,
+                "light_ambient_uniform",
+                self.light.ambient,
+            )?,
+            diffuse: self.uniforms.add_float(
+                self.entities,
+                parent,
+                "light_diffuse_uniform",
+                self.light.diffuse,
+            )?,
+            gradient: self.uniforms.add_vec2f(
+                self.entities,
+                parent,
+                "light_gradient_uniform",
+                Vec2f::new(
+                    self.light.gradient.floor_scale,
+                    self.light.gradient.ceiling_scale,
+                ),
+            )?,
+        };
+        let dynamic_lights = self.uniforms.add_float_texture_2d(
+            self.window,
+            self.entities,
+            parent,
+            "dynamic_lights_texture",
+            &[(0.0, 0.0, 0.0, 0.0); MAX_DYNAMIC_LIGHTS * 2],
+            Vec2::new(MAX_DYNAMIC_LIGHTS, 2),
+        )?;
+        let num_dynamic_lights =
+            self.uniforms
+                .add_float(self.entities, parent, "num_dynamic_lights_uniform", 0.0)?;
         let lights_buffer_texture = self.uniforms.add_persistent_buffer_texture_u8(
             self.window,
             self.entities,
@@ -158,6 +369,10 @@ impl<'context> Dependencies<'context> {
             BufferTextureType::Float,
         )?;
 
+        let wipe_trigger =
+            self.uniforms
+                .add_float(self.entities, parent, "wipe_trigger_uniform", 0.0)?;
+
         let static_shader = self.load_shader(parent, "static_shader", "static")?;
         let sky_shader = self.load_shader(parent, "sky_shader", "sky")?;
         let sprite_shader = self.load_shader(parent, "sprite_shader", "sprite")?;
@@ -165,7 +380,11 @@ impl<'context> Dependencies<'context> {
         Ok(Globals {
             palette,
             time,
+            light,
+            dynamic_lights,
+            num_dynamic_lights,
             lights_buffer_texture,
+            wipe_trigger,
             static_shader,
             sky_shader,
             sprite_shader,
@@ -189,6 +408,12 @@ impl<'context> Dependencies<'context> {
             .add_uniform("u_projection", projection)
             .add_uniform("u_time", globals.time)
             .add_uniform("u_lights", globals.lights_buffer_texture)
+            .add_uniform("u_light_direction", globals.light.direction)
+            .add_uniform("u_light_ambient", globals.light.ambient)
+            .add_uniform("u_light_diffuse", globals.light.diffuse)
+            .add_uniform("u_light_gradient", globals.light.gradient)
+            .add_uniform("u_dynamic_lights", globals.dynamic_lights)
+            .add_uniform("u_num_dynamic_lights", globals.num_dynamic_lights)
             .add_uniform("u_palette", globals.palette)
             .add_uniform("u_atlas", flats_atlas.texture)
             .add_uniform(
@@ -214,6 +439,12 @@ impl<'context> Dependencies<'context> {
             .add_uniform("u_projection", projection)
             .add_uniform("u_time", globals.time)
             .add_uniform("u_lights", globals.lights_buffer_texture)
+            .add_uniform("u_light_direction", globals.light.direction)
+            .add_uniform("u_light_ambient", globals.light.ambient)
+            .add_uniform("u_light_diffuse", globals.light.diffuse)
+            .add_uniform("u_light_gradient", globals.light.gradient)
+            .add_uniform("u_dynamic_lights", globals.dynamic_lights)
+            .add_uniform("u_num_dynamic_lights", globals.num_dynamic_lights)
             .add_uniform("u_palette", globals.palette)
             .add_uniform("u_atlas", walls_atlas.texture)
             .add_uniform(
@@ -236,6 +467,21 @@ impl<'context> Dependencies<'context> {
             .add_uniform("u_texture", sky_uniforms.texture)
             .add_uniform("u_tiled_band_size", sky_uniforms.tiled_band_size)
             .id();
+        let [r, g, b] = match *self.clear_color {
+            ClearColor::Sky => {
+                sky_uniforms
+                    .average_color
+                    .map_or(Window::DEFAULT_CLEAR_COLOR, |color| {
+                        [
+                            f32::from(color[0]) / 255.0,
+                            f32::from(color[1]) / 255.0,
+                            f32::from(color[2]) / 255.0,
+                        ]
+                    })
+            }
+            ClearColor::Fixed(color) => color,
+        };
+        self.window.set_clear_color(r, g, b);
 
         let decor_atlas = self.load_decor_atlas(parent)?;
         let decor_material = self
@@ -337,13 +583,10 @@ impl<'context> Dependencies<'context> {
                 .things
                 .iter()
                 .filter_map(|thing| wad.archive.metadata().find_thing(thing.thing_type))
-                .flat_map(|decor| {
-                    let mut sprite0 = decor.sprite;
-                    let _ = sprite0.push(decor.sequence.as_bytes()[0]);
-                    let mut sprite1 = sprite0;
-                    let sprite0 = sprite0.push(b'0').ok().map(|_| sprite0);
-                    let sprite1 = sprite1.push(b'1').ok().map(|_| sprite1);
-                    sprite0.into_iter().chain(sprite1)
+                .filter_map(|decor| {
+                    wad.textures
+                        .sprite(decor.sprite, decor.sequence.as_bytes()[0], 1)
+                        .map(|(name, _)| name)
                 });
             wad.textures.build_texture_atlas(names)
         };
@@ -371,7 +614,13 @@ impl<'context> Dependencies<'context> {
                 },
                 |meta| (meta.texture_name, meta.tiled_band_size),
             );
+        let average_color = self
+            .wad
+            .textures
+            .texture(texture_name)
+            .and_then(|image| image.average_color(self.wad.textures.palette(0)));
         Ok(SkyUniforms {
+            average_color,
             texture: self.load_wad_texture(
                 parent,
                 "sky_texture",
@@ -467,6 +716,7 @@ impl<'context> Dependencies<'context> {
 struct SkyUniforms {
     tiled_band_size: FloatUniformId,
     texture: Texture2dId,
+    average_color: Option<[u8; 3]>,
 }
 
 struct Atlas {
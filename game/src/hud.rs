@@ -1,10 +1,12 @@
 use super::wad_system::WadSystem;
 use engine::{
-    DependenciesFrom, ControlFlow, Gesture, InfallibleSystem, Input, Scancode, TextId,
-    TextRenderer, Window,
+    ControlFlow, DependenciesFrom, Gesture, InfallibleSystem, Input, RenderPipeline, Scancode,
+    TextId, TextRenderer, Tick, Window, WindowConfig,
 };
 use math::prelude::*;
 use math::Pnt2f;
+use std::time::Instant;
+use wad::WadName;
 
 pub struct Bindings {
     pub quit: Gesture,
@@ -12,6 +14,10 @@ pub struct Bindings {
     pub previous_level: Gesture,
     pub toggle_mouse: Gesture,
     pub toggle_help: Gesture,
+    pub toggle_crosshair: Gesture,
+    pub toggle_debug_overlay: Gesture,
+    /// Only in debug builds.
+    pub reload_level: Gesture,
 }
 
 impl Default for Bindings {
@@ -29,8 +35,56 @@ impl Default for Bindings {
                 Gesture::KeyHold(Scancode::LControl),
                 Gesture::KeyTrigger(Scancode::P),
             ]),
-            toggle_mouse: Gesture::KeyTrigger(Scancode::Grave),
+            toggle_mouse: Gesture::KeyTrigger(Scancode::M),
             toggle_help: Gesture::KeyTrigger(Scancode::H),
+            toggle_crosshair: Gesture::KeyTrigger(Scancode::X),
+            toggle_debug_overlay: Gesture::KeyTrigger(Scancode::F4),
+            reload_level: Gesture::KeyTrigger(Scancode::F5),
+        }
+    }
+}
+
+/// The characters a crosshair can be drawn with.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CrosshairStyle {
+    Plus,
+    Cross,
+    Dot,
+}
+
+impl CrosshairStyle {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "plus" => CrosshairStyle::Plus,
+            "cross" => CrosshairStyle::Cross,
+            "dot" => CrosshairStyle::Dot,
+            _ => return None,
+        })
+    }
+
+    fn glyph(self) -> &'static str {
+        match self {
+            CrosshairStyle::Plus => "+",
+            CrosshairStyle::Cross => "x",
+            CrosshairStyle::Dot => "\u{b7}",
+        }
+    }
+}
+
+/// A crosshair drawn at the center of the screen, off by default as in vanilla DOOM.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Crosshair {
+    pub style: CrosshairStyle,
+    pub color: [f32; 4],
+    pub enabled: bool,
+}
+
+impl Default for Crosshair {
+    fn default() -> Self {
+        Crosshair {
+            style: CrosshairStyle::Plus,
+            color: [1.0, 1.0, 1.0, 0.8],
+            enabled: false,
         }
     }
 }
@@ -38,10 +92,13 @@ impl Default for Bindings {
 #[derive(DependenciesFrom)]
 pub struct Dependencies<'context> {
     bindings: &'context Bindings,
+    tick: &'context Tick,
+    window_config: &'context WindowConfig,
     window: &'context Window,
     input: &'context mut Input,
     text: &'context mut TextRenderer,
     control_flow: &'context mut ControlFlow,
+    pipe: &'context RenderPipeline,
 
     wad: &'context mut WadSystem,
 }
@@ -51,6 +108,14 @@ pub struct Hud {
     current_help: HelpState,
     prompt_text: TextId,
     help_text: TextId,
+    crosshair: Crosshair,
+    crosshair_text: Option<TextId>,
+    crosshair_changed: bool,
+    loading_text: Option<TextId>,
+    loading_updates: usize,
+
+    title_frames: u32,
+    title_updated: Instant,
 }
 
 impl<'context> InfallibleSystem<'context> for Hud {
@@ -77,18 +142,54 @@ impl<'context> InfallibleSystem<'context> for Hud {
             help_text,
             mouse_grabbed: true,
             current_help: HelpState::Prompt,
+            crosshair: Crosshair::default(),
+            crosshair_text: None,
+            crosshair_changed: false,
+            loading_text: None,
+            loading_updates: 0,
+
+            title_frames: 0,
+            title_updated: Instant::now(),
         }
     }
 
     fn update(&mut self, deps: Dependencies) {
+        self.update_title(&deps);
+
         let Dependencies {
             input,
             text,
             control_flow,
             bindings,
+            window,
             ..
         } = deps;
 
+        if input.poll_gesture(&bindings.toggle_crosshair) {
+            let mut crosshair = self.crosshair;
+            crosshair.enabled = !crosshair.enabled;
+            self.set_crosshair(crosshair);
+        }
+        if self.crosshair_changed {
+            self.crosshair_changed = false;
+            if let Some(id) = self.crosshair_text.take() {
+                text.remove(id);
+            }
+            if self.crosshair.enabled {
+                let id = text.insert_centered(
+                    window,
+                    self.crosshair.style.glyph(),
+                    Pnt2f::new(CROSSHAIR_POSITION[0], CROSSHAIR_POSITION[1]),
+                    0,
+                );
+                text[id].set_color(self.crosshair.color);
+                text[id].set_background(false);
+                self.crosshair_text = Some(id);
+            }
+        }
+
+        self.update_loading_text(text, window, deps.wad.loading_level_name());
+
         if input.poll_gesture(&bindings.quit) {
             control_flow.quit_requested = true
         }
@@ -126,31 +227,128 @@ impl<'context> InfallibleSystem<'context> for Hud {
                 deps.wad.change_level(index - 1);
             }
         }
+        if cfg!(debug_assertions) && input.poll_gesture(&bindings.reload_level) {
+            deps.wad.reopen_level();
+        }
     }
 
     fn teardown(&mut self, deps: Dependencies) {
+        if let Some(id) = self.crosshair_text.take() {
+            deps.text.remove(id);
+        }
+        if let Some(id) = self.loading_text.take() {
+            deps.text.remove(id);
+        }
         deps.text.remove(self.help_text);
         deps.text.remove(self.prompt_text);
     }
 }
 
+impl Hud {
+    pub fn crosshair(&self) -> Crosshair {
+        self.crosshair
+    }
+
+    /// Changes the crosshair, which is redrawn on the next update.
+    pub fn set_crosshair(&mut self, crosshair: Crosshair) {
+        self.crosshair = crosshair;
+        self.crosshair_changed = true;
+    }
+
+    /// Shows which level is loading in the background, with a spinner so it's clear the game
+    /// hasn't frozen, and hides it once the level is in.
+    fn update_loading_text(
+        &mut self,
+        text: &mut TextRenderer,
+        window: &Window,
+        loading: Option<WadName>,
+    ) {
+        let name = match loading {
+            Some(name) => name,
+            None => {
+                if let Some(id) = self.loading_text.take() {
+                    text.remove(id);
+                }
+                self.loading_updates = 0;
+                return;
+            }
+        };
+        // Texts can't be edited in place, so only rebuild it when the spinner turns.
+        if self.loading_updates % LOADING_SPINNER_UPDATES == 0 {
+            if let Some(id) = self.loading_text.take() {
+                text.remove(id);
+            }
+            let spinner = LOADING_SPINNER[self.loading_updates / LOADING_SPINNER_UPDATES % 4];
+            let id = text.insert_centered(
+                window,
+                &format!("Loading {} {}", name, spinner),
+                Pnt2f::new(LOADING_POSITION[0], LOADING_POSITION[1]),
+                HELP_PADDING,
+            );
+            self.loading_text = Some(id);
+        }
+        self.loading_updates += 1;
+    }
+
+    /// Counts rendered frames and shows the level name and frame rate in the window title every
+    /// `TITLE_UPDATE_SECONDS`, followed in debug builds by the last frame's draw call counts.
+    fn update_title(&mut self, deps: &Dependencies) {
+        if deps.tick.is_frame() {
+            self.title_frames += 1;
+        }
+        let elapsed = self.title_updated.elapsed().f64_seconds();
+        if elapsed < TITLE_UPDATE_SECONDS {
+            return;
+        }
+        let mut title = format!(
+            "{} [{}] {:.0} FPS",
+            deps.window_config.title,
+            deps.wad.level_name(),
+            f64::from(self.title_frames) / elapsed
+        );
+        if cfg!(debug_assertions) {
+            let stats = deps.pipe.query_draw_calls();
+            title.push_str(&format!(
+                ", {} draws, {} triangles, {} vertices, {} textures",
+                stats.draw_calls, stats.triangles, stats.vertices, stats.texture_binds
+            ));
+        }
+        deps.window.set_title(&title);
+        self.title_frames = 0;
+        self.title_updated = Instant::now();
+    }
+}
+
 enum HelpState {
     Prompt,
     Shown,
     Hidden,
 }
 
+const TITLE_UPDATE_SECONDS: f64 = 2.0;
 const HELP_PADDING: u32 = 6;
+const CROSSHAIR_POSITION: [f32; 2] = [0.5, 0.5];
+const LOADING_POSITION: [f32; 2] = [0.5, 0.7];
+const LOADING_SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+/// How many updates each frame of the loading spinner is shown for.
+const LOADING_SPINNER_UPDATES: usize = 8;
 const PROMPT_TEXT: &str = "WASD and mouse, 'E' to push/use, LB to shoot or 'h' for help.";
-const HELP_TEXT: &str = r"Use WASD to move and the mouse or arrow keys to aim.
+const HELP_TEXT: &str =
+    r"Use WASD to move (or ',' and '.' to strafe) and the mouse or arrow keys to aim.
 Other keys:
     ESC - to quit
     SPACEBAR - jump
-    E - push/interact/use
-    Left Click - shoot (only effect is to trigger gun-activated things)
-    ` - to toggle mouse grab (backtick)
+    E or Middle Click - push/interact/use
+    Left Click - fire the current weapon (also triggers gun-activated things)
+    1-7 or Mouse Wheel - switch weapon
+    ` - to toggle the console (backtick), try 'help', 'map E1M2', 'give all',
+        'noclip', 'crosshair' or 'clear_color'
+    m - to toggle mouse grab
+    x - to toggle the crosshair
     f - to toggle fly mode
     c - to toggle clipping (wall collisions)
     Ctrl-N - to change to next level (though using the exit will also do this!)
     Ctrl-P - to change to previous level
+    F4 - toggle level and rendering stats
+    F5 - reload the level from disk (debug builds only)
     h - toggle this help message";
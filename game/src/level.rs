@@ -1,7 +1,11 @@
 use super::game_shaders::{GameShaders, LevelMaterials};
 use super::lights::Lights;
+use super::monster::{Monster, MonsterAttack};
+use super::pickup::{KeyInventory, Pickup};
+use super::sector_special::SectorSpecial;
 use super::vertex::{SkyVertex, SpriteVertex, StaticVertex};
 use super::wad_system::WadSystem;
+use super::weapon::{Explosion, Projectile};
 use super::world::{World, WorldBuilder};
 use engine::{
     DependenciesFrom, Entities, EntityId, Error, Meshes, RenderPipeline, Result, System, Tick,
@@ -9,13 +13,19 @@ use engine::{
 };
 use log::{debug, error, info, warn};
 use math::prelude::*;
-use math::{vec2, Line2f, Pnt2f, Pnt3f, Rad, Trans3, Vec3f};
+use math::{vec2, Line2f, Pnt2f, Pnt3f, Rad, Sphere, Trans3, Vec2f, Vec3f};
+use std::collections::VecDeque;
+use std::mem;
+use std::rc::Rc;
 use std::time::Instant;
 use vec_map::VecMap;
 use wad::tex::Bounds as WadBounds;
+use wad::types::{SectorId, SectorTag, SectorType, ThingType, WadSector};
+use wad::util::from_wad_height;
 use wad::{
-    Decor, LevelVisitor, LightInfo, Marker, MoveEffect, ObjectId, SkyPoly, SkyQuad, StaticPoly,
-    StaticQuad, Trigger, TriggerType,
+    Decor, DynamicLight, ExitEffectDef, Level as WadLevel, LevelVisitor, LightInfo, Lock, Marker,
+    MoveEffect, ObjectId, SkyPoly, SkyQuad, StaticPoly, StaticQuad, ThingSpawn, Trigger,
+    TriggerType,
 };
 
 pub struct Level {
@@ -23,14 +33,41 @@ pub struct Level {
     objects: Vec<EntityId>,
     triggers: Vec<Trigger>,
     removed: Vec<usize>,
-    effects: VecMap<MoveEffect>,
-    exit_triggered: bool,
+    effects: VecMap<ActiveEffect>,
+    crushing: Vec<(SectorId, f32)>,
+    exit_triggered: Option<ExitEffectDef>,
     level_changed: bool,
-
-    start_pos: Pnt3f,
-    start_yaw: Rad<f32>,
+    level_reopened: bool,
+    projectiles: Vec<(Projectile, f32)>,
+    explosions: Vec<Explosion>,
+
+    monsters: Vec<Monster>,
+    /// The sprite entity of each monster, removed when it dies.
+    monster_entities: Vec<Option<EntityId>>,
+    target: Option<Sphere>,
+    monster_damage: f32,
+    /// The damage of this tick's monster attacks, as a vector pointing away from the attackers.
+    monster_knockback: Vec2f,
+    pickups: Vec<Pickup>,
+    pickup_entities: Vec<Option<EntityId>>,
+    tic: u32,
+    tic_time: f32,
+
+    start_pos: (Pnt3f, Rad<f32>),
+    player_starts: Vec<(Pnt3f, Rad<f32>)>,
+    deathmatch_starts: Vec<(Pnt3f, Rad<f32>)>,
     lights: Lights,
     volume: World,
+
+    geometry: Rc<WadLevel>,
+    visited_sectors: Vec<bool>,
+    /// For each sector, the sectors on the other side of its two-sided linedefs.
+    neighbours: Vec<Vec<Neighbour>>,
+    /// The sector of the last noise the player made, until the monsters have had a tic to hear it.
+    noise: Option<SectorId>,
+    floor_ids: Vec<ObjectId>,
+    ceiling_ids: Vec<ObjectId>,
+    object_offsets: Vec<f32>,
 }
 
 #[derive(DependenciesFrom)]
@@ -47,6 +84,107 @@ pub struct Dependencies<'context> {
     game_shaders: &'context GameShaders,
 }
 
+/// Damage per second taken by anything caught under a crushing ceiling. DOOM deals 10 damage
+/// every four tics.
+const CRUSH_DAMAGE_PER_SECOND: f32 = 87.5;
+
+/// Rays are swept as tiny spheres, since that's the only query `World` supports.
+const RAY_RADIUS: f32 = 1e-3;
+
+/// Projectiles which haven't hit anything after this long (having left the map, say) are removed.
+const MAX_PROJECTILE_SECONDS: f32 = 10.0;
+const EXPLOSION_SURFACE_OFFSET: f32 = 0.01;
+
+/// The steepest slope autoaim will aim up or down at, the same as DOOM's (which is the slope
+/// from the middle of its 200 pixel tall view to the top, at its 160 pixel focal length).
+const AUTOAIM_SLOPE: f32 = 100.0 / 160.0;
+
+/// The horizontal unit vector from `source` towards `target`, or zero if one is above the other.
+fn away_from(source: Pnt3f, target: Pnt3f) -> Vec2f {
+    Vec2f::new(target.x - source.x, target.z - source.z).normalize_or_zero()
+}
+
+const OUTSIDE_LIGHT_LEVEL: u8 = 160;
+
+/// Sectors of this type are icy: `friction_at` returns `ICE_FRICTION` inside them.
+const SECTOR_TYPE_ICE: SectorType = 15;
+const ICE_FRICTION: f32 = 0.1;
+
+/// Monsters think at DOOM's fixed tic rate, regardless of the tick rate.
+const TIC_SECONDS: f32 = 1.0 / 35.0;
+
+const SPRITE_QUAD_INDICES: [u32; 6] = [0, 1, 3, 1, 2, 3];
+
+/// A running move effect, alongside the effect as it was started, so that repeating effects can
+/// restart their cycle.
+struct ActiveEffect {
+    effect: MoveEffect,
+    original: MoveEffect,
+}
+
+/// Moves an effect's object (whose height offset is `current_offset`) along by `timestep`.
+/// Returns whether the effect is done and should be removed. Repeating effects never are.
+#[cfg_attr(feature = "cargo-clippy", allow(clippy::float_cmp))]
+fn advance_effect(
+    i_effect: usize,
+    active: &mut ActiveEffect,
+    current_offset: &mut f32,
+    mut timestep: f32,
+) -> bool {
+    let effect = &mut active.effect;
+    loop {
+        if effect.first_height_offset != *current_offset {
+            let offset_difference = effect.first_height_offset - *current_offset;
+            let sign = offset_difference.signum();
+            let time_left = offset_difference.abs() / effect.speed;
+            if time_left > timestep {
+                *current_offset += sign * effect.speed * timestep;
+                return false;
+            } else {
+                *current_offset = effect.first_height_offset;
+                timestep -= time_left;
+                effect.first_height_offset = *current_offset;
+                debug!("Effect {}: finished first offset.", i_effect);
+            }
+        }
+
+        if effect.wait > timestep {
+            effect.wait -= timestep;
+            return false;
+        } else {
+            debug!("Effect {}: finished waiting.", i_effect);
+            timestep -= effect.wait;
+            effect.wait = 0.0;
+        }
+
+        if let Some(offset) = effect.second_height_offset.take() {
+            effect.first_height_offset = offset;
+            debug!(
+                "Effect {}: moved second offset {} into first.",
+                i_effect, offset
+            );
+            continue;
+        }
+
+        if effect.repeat {
+            debug!("Effect {}: done, repeating.", i_effect);
+            *effect = active.original;
+            return false;
+        }
+
+        debug!("Effect {}: done, removing.", i_effect);
+        return true;
+    }
+}
+
+/// Where a ray cast with `Level::raycast` hit the level geometry.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RayHit {
+    pub point: Pnt3f,
+    pub normal: Vec3f,
+    pub distance: f32,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum PlayerAction {
     Push,
@@ -58,28 +196,556 @@ impl Level {
         self.level_changed
     }
 
+    /// Whether the level changed because it was reloaded from disk (see
+    /// `WadSystem::reopen_level`). Implies `level_changed`.
+    pub fn level_reopened(&self) -> bool {
+        self.level_reopened
+    }
+
     pub fn root(&self) -> EntityId {
         self.root
     }
 
-    pub fn start_pos(&self) -> &Pnt3f {
-        &self.start_pos
+    /// Where player 1 starts, and the yaw they start facing.
+    pub fn start_pos(&self) -> (Pnt3f, Rad<f32>) {
+        self.start_pos
     }
 
-    pub fn start_yaw(&self) -> Rad<f32> {
-        self.start_yaw
+    /// The starts of players 1 to 4 which are in the level, in player order.
+    pub fn start_positions(&self) -> &[(Pnt3f, Rad<f32>)] {
+        &self.player_starts
+    }
+
+    pub fn deathmatch_starts(&self) -> &[(Pnt3f, Rad<f32>)] {
+        &self.deathmatch_starts
     }
 
     pub fn volume(&self) -> &World {
         &self.volume
     }
 
+    /// Finds the first wall, floor or ceiling hit by a ray from `origin` along `direction`, up to
+    /// `max_distance` away.
+    pub fn raycast(&self, origin: Pnt3f, direction: Vec3f, max_distance: f32) -> Option<RayHit> {
+        let displacement = direction.normalize_or_zero() * max_distance;
+        let ray = Sphere {
+            center: origin,
+            radius: RAY_RADIUS,
+        };
+        self.volume
+            .sweep_sphere(ray, displacement)
+            .filter(|contact| contact.time <= 1.0)
+            .map(|contact| RayHit {
+                point: origin + displacement * contact.time,
+                normal: contact.normal,
+                distance: max_distance * contact.time,
+            })
+    }
+
+    /// Adds a projectile, which moves every tick until it hits something and explodes.
+    pub fn spawn_projectile(&mut self, projectile: Projectile) {
+        self.projectiles.push((projectile, 0.0));
+    }
+
+    /// Returns the damage dealt by explosions this tick to a sphere, from those explosions in
+    /// range which have a clear line to its center.
+    pub fn explosion_damage(&self, target: &Sphere) -> f32 {
+        self.explosions_reaching(target)
+            .map(|explosion| explosion.damage_at(target.center, target.radius))
+            .sum()
+    }
+
+    /// Returns the damage dealt to a sphere this tick by explosions and monster attacks, as a
+    /// horizontal vector pointing away from where it came from, for knocking it back.
+    pub fn damage_knockback(&self, target: &Sphere) -> Vec2f {
+        self.explosions_reaching(target)
+            .map(|explosion| {
+                away_from(explosion.origin, target.center)
+                    * explosion.damage_at(target.center, target.radius)
+            })
+            .fold(self.monster_knockback, |total, knockback| total + knockback)
+    }
+
+    fn explosions_reaching<'a>(
+        &'a self,
+        target: &'a Sphere,
+    ) -> impl Iterator<Item = &'a Explosion> + 'a {
+        self.explosions.iter().filter(move |explosion| {
+            let to_target = target.center - explosion.origin;
+            let distance = to_target.magnitude();
+            self.raycast(explosion.origin, to_target, distance)
+                .is_none()
+        })
+    }
+
+    fn update_projectiles(&mut self, timestep: f32) {
+        self.explosions.clear();
+        let mut i_projectile = 0;
+        while i_projectile < self.projectiles.len() {
+            let (ref mut projectile, ref mut age) = self.projectiles[i_projectile];
+            *age += timestep;
+            let displacement = projectile.velocity * timestep;
+            let wall_hit = self
+                .volume
+                .sweep_sphere(
+                    Sphere {
+                        center: projectile.position,
+                        radius: RAY_RADIUS,
+                    },
+                    displacement,
+                )
+                .filter(|contact| contact.time <= 1.0)
+                .map(|contact| (contact.time, contact.normal));
+            // Projectiles also hit the player and monsters, except whoever they started inside.
+            let backwards = -displacement.normalize_or_zero();
+            let hit = self
+                .monsters
+                .iter()
+                .filter(|monster| !monster.is_dead())
+                .map(Monster::body)
+                .chain(self.target)
+                .filter_map(|body| body.intersect_segment(projectile.position, displacement))
+                .map(|time| (time, backwards))
+                .chain(wall_hit)
+                .min_by(|(left, _), (right, _)| left.partial_cmp(right).expect("NaN hit time"));
+            let origin = match hit {
+                Some((time, normal)) => {
+                    // Explode just in front of the surface, so it doesn't block its own blast.
+                    projectile.position + displacement * time + normal * EXPLOSION_SURFACE_OFFSET
+                }
+                _ if *age > MAX_PROJECTILE_SECONDS => {
+                    self.projectiles.swap_remove(i_projectile);
+                    continue;
+                }
+                _ => {
+                    projectile.position += displacement;
+                    projectile.velocity.y -= projectile.gravity * timestep;
+                    i_projectile += 1;
+                    continue;
+                }
+            };
+            debug!("Projectile exploded at {:?}.", origin);
+            self.explosions.push(Explosion {
+                origin,
+                radius: projectile.blast_radius,
+                damage: projectile.blast_damage,
+            });
+            self.projectiles.swap_remove(i_projectile);
+        }
+    }
+
+    pub fn monsters(&self) -> &[Monster] {
+        &self.monsters
+    }
+
+    /// How many sectors are moving (doors, lifts, crushers and the like) right now.
+    pub fn num_active_effects(&self) -> usize {
+        self.effects.len()
+    }
+
+    pub fn num_projectiles(&self) -> usize {
+        self.projectiles.len()
+    }
+
+    /// Sets where the player is, for monsters to chase and missiles to hit.
+    pub fn set_target(&mut self, target: Sphere) {
+        self.target = Some(target);
+    }
+
+    /// Returns the damage dealt to the target by monsters' hitscan and melee attacks this tick.
+    pub fn monster_damage(&self) -> f32 {
+        self.monster_damage
+    }
+
+    /// Finds the closest living monster hit by a ray, returning its index and distance.
+    pub fn hit_monster(
+        &self,
+        origin: Pnt3f,
+        direction: Vec3f,
+        max_distance: f32,
+    ) -> Option<(usize, f32)> {
+        let displacement = direction.normalize_or_zero() * max_distance;
+        self.monsters
+            .iter()
+            .enumerate()
+            .filter(|(_, monster)| !monster.is_dead())
+            .filter_map(|(i_monster, monster)| {
+                monster
+                    .body()
+                    .intersect_segment(origin, displacement)
+                    .map(|time| (i_monster, time * max_distance))
+            })
+            .min_by(|(_, left), (_, right)| left.partial_cmp(right).expect("NaN hit distance"))
+    }
+
+    /// Finds the pitch at which to fire from `origin_z` above `origin`, looking along `yaw`, to hit
+    /// the closest monster in range on that vertical plane, like DOOM's `P_AimLineAttack`.
+    ///
+    /// Only monsters within `AUTOAIM_SLOPE` of level and in sight are aimed at; the pitch is
+    /// towards the middle of the monster's height, or as close to it as the slope limit allows.
+    pub fn autoaim(&self, origin: Pnt2f, origin_z: f32, yaw: Rad<f32>, range: f32) -> Option<f32> {
+        let direction = vec2(-yaw.0.sin(), -yaw.0.cos());
+        self.monsters
+            .iter()
+            .filter(|monster| !monster.is_dead())
+            .filter_map(|monster| {
+                let position = monster.position();
+                let target = Pnt2f::new(position.x, position.z);
+                let to_target = target - origin;
+                let distance = to_target.dot(direction);
+                if distance <= 0.0
+                    || distance > range
+                    || to_target.perp_dot(direction).abs() > monster.radius()
+                {
+                    return None;
+                }
+                let bottom = (position.y - origin_z) / distance;
+                let top = (position.y + monster.height() - origin_z) / distance;
+                if bottom > AUTOAIM_SLOPE || top < -AUTOAIM_SLOPE {
+                    return None;
+                }
+                let slope = ((bottom + top) * 0.5)
+                    .max(-AUTOAIM_SLOPE)
+                    .min(AUTOAIM_SLOPE);
+                if !self.check_sight(origin, origin_z, target, origin_z + slope * distance) {
+                    return None;
+                }
+                Some((distance, slope))
+            })
+            .min_by(|(left, _), (right, _)| left.partial_cmp(right).expect("NaN aim distance"))
+            .map(|(_, slope)| slope.atan())
+    }
+
+    pub fn damage_monster(&mut self, index: usize, amount: f32) {
+        if let Some(monster) = self.monsters.get_mut(index) {
+            monster.damage(amount);
+        }
+    }
+
+    pub fn pickups(&self) -> &[Pickup] {
+        &self.pickups
+    }
+
+    /// Removes a pickup from the level, once the player has taken it.
+    pub fn take_pickup(&mut self, index: usize) {
+        if let Some(pickup) = self.pickups.get_mut(index) {
+            pickup.deactivate();
+        }
+    }
+
+    fn update_monsters(&mut self, timestep: f32) {
+        self.monster_damage = 0.0;
+        self.monster_knockback = Vec2f::zero();
+        if !self.explosions.is_empty() {
+            let damages = self
+                .monsters
+                .iter()
+                .map(|monster| self.explosion_damage(&monster.body()))
+                .collect::<Vec<_>>();
+            for (monster, damage) in self.monsters.iter_mut().zip(damages) {
+                monster.damage(damage);
+            }
+        }
+
+        let target = match self.target {
+            Some(target) => target.center,
+            None => return,
+        };
+        self.tic_time += timestep;
+        let mut monsters = mem::replace(&mut self.monsters, Vec::new());
+        while self.tic_time >= TIC_SECONDS {
+            self.tic_time -= TIC_SECONDS;
+            self.tic += 1;
+            for monster in &mut monsters {
+                match monster.ai_update(self.tic, self, target) {
+                    Some(MonsterAttack::Damage(damage)) => {
+                        self.monster_damage += damage;
+                        self.monster_knockback += away_from(monster.position(), target) * damage;
+                    }
+                    Some(MonsterAttack::Missile(missile)) => self.spawn_projectile(missile),
+                    None => {}
+                }
+                monster.drift(self);
+            }
+            self.noise = None;
+        }
+        self.monsters = monsters;
+    }
+
+    /// Returns the positions of all the things of a given type, e.g. teleport destinations.
+    pub fn find_things(&self, thing_type: ThingType) -> &[Pnt2f] {
+        self.geometry.find_things(thing_type)
+    }
+
+    /// Iterates over every sector with its id. These are the sectors as loaded: the heights don't
+    /// include the offsets of moving floors and ceilings.
+    pub fn all_sectors(&self) -> impl Iterator<Item = (SectorId, &WadSector)> + '_ {
+        self.geometry.all_sectors()
+    }
+
+    /// Returns the ids of the sectors targeted by linedefs with the given tag.
+    pub fn sectors_with_tag(&self, tag: SectorTag) -> &[SectorId] {
+        self.geometry.sectors_with_tag(tag)
+    }
+
+    /// Marks the sector containing `pos` (if any) as visited.
+    pub fn mark_visited(&mut self, pos: Pnt2f) {
+        if let Some(sector) = self.geometry.sector_at(pos) {
+            let sector_id = self.geometry.sector_id(sector);
+            self.visited_sectors[sector_id as usize] = true;
+        }
+    }
+
+    /// Returns whether the player has ever been inside the given sector in this level.
+    pub fn is_sector_visited(&self, sector_id: SectorId) -> bool {
+        self.visited_sectors
+            .get(sector_id as usize)
+            .cloned()
+            .unwrap_or(false)
+    }
+
+    /// Returns the current height of the floor at `pos`, including any offset from a moving floor,
+    /// or negative infinity outside the map.
+    pub fn floor_height_at(&self, pos: Pnt2f) -> f32 {
+        let sector = match self.geometry.sector_at(pos) {
+            Some(sector) => sector,
+            None => return std::f32::NEG_INFINITY,
+        };
+        let floor_id = self.floor_ids[self.geometry.sector_id(sector) as usize];
+        from_wad_height(sector.floor_height) + self.object_offsets[floor_id.0 as usize]
+    }
+
+    /// Returns how much grip the floor at `pos` gives, scaling both friction and how hard you can
+    /// push off it: one for normal floors, less on ice.
+    pub fn friction_at(&self, pos: Pnt2f) -> f32 {
+        match self.geometry.sector_at(pos) {
+            Some(sector) if sector.sector_type == SECTOR_TYPE_ICE => ICE_FRICTION,
+            _ => 1.0,
+        }
+    }
+
+    /// Returns the wind or current of the sector containing `pos`, if it has one.
+    pub fn sector_special_at(&self, pos: Pnt2f) -> Option<SectorSpecial> {
+        self.geometry
+            .sector_at(pos)
+            .and_then(|sector| SectorSpecial::from_sector_type(sector.sector_type))
+    }
+
+    /// Returns the light level (0 to 255) of the sector containing `pos`, as set in the map:
+    /// without flickering or glowing, which only happen in the shaders. Outside the map, the
+    /// level is `OUTSIDE_LIGHT_LEVEL`.
+    pub fn light_level_at(&self, pos: Pnt2f) -> u8 {
+        self.geometry
+            .sector_at(pos)
+            .map_or(OUTSIDE_LIGHT_LEVEL, |sector| {
+                sector.light.max(0).min(255) as u8
+            })
+    }
+
+    /// Returns the id of the sector containing `pos`, if any.
+    pub fn sector_id_at(&self, pos: Pnt2f) -> Option<SectorId> {
+        self.geometry
+            .sector_at(pos)
+            .map(|sector| self.geometry.sector_id(sector))
+    }
+
+    /// Iterates over the sectors sharing a two-sided linedef with the given one, each only once.
+    pub fn sector_neighbours(&self, sector_id: SectorId) -> impl Iterator<Item = SectorId> + '_ {
+        self.neighbours
+            .get(sector_id as usize)
+            .into_iter()
+            .flatten()
+            .map(|neighbour| neighbour.sector_id)
+    }
+
+    /// Returns true if a sound made in sector `from` carries to sector `to` across at most
+    /// `max_hops` sector boundaries. Sound crosses two-sided linedefs which aren't flagged as
+    /// blocking sound, as long as the opening between the two sectors isn't currently shut (by a
+    /// closed door, say).
+    pub fn sound_reaches(&self, from: SectorId, to: SectorId, max_hops: u8) -> bool {
+        let mut visited = vec![false; self.neighbours.len()];
+        let mut queue = VecDeque::new();
+        queue.push_back((from, 0));
+        while let Some((sector_id, hops)) = queue.pop_front() {
+            if sector_id == to {
+                return true;
+            }
+            if hops == max_hops {
+                continue;
+            }
+            let (floor, ceiling) = match self.sector_heights(sector_id) {
+                Some(heights) => heights,
+                None => continue,
+            };
+            let neighbours = match self.neighbours.get(sector_id as usize) {
+                Some(neighbours) => neighbours,
+                None => continue,
+            };
+            for neighbour in neighbours
+                .iter()
+                .filter(|neighbour| neighbour.carries_sound)
+            {
+                let neighbour_id = neighbour.sector_id;
+                if visited[neighbour_id as usize] {
+                    continue;
+                }
+                let (neighbour_floor, neighbour_ceiling) = match self.sector_heights(neighbour_id) {
+                    Some(heights) => heights,
+                    None => continue,
+                };
+                if ceiling.min(neighbour_ceiling) > floor.max(neighbour_floor) {
+                    visited[neighbour_id as usize] = true;
+                    queue.push_back((neighbour_id, hops + 1));
+                }
+            }
+        }
+        false
+    }
+
+    /// Makes a noise at `pos` (like a gunshot), which wakes the idle monsters it reaches on the
+    /// next tic.
+    pub fn make_noise(&mut self, pos: Pnt2f) {
+        if let Some(sector_id) = self.sector_id_at(pos) {
+            self.noise = Some(sector_id);
+        }
+    }
+
+    /// The sector of the noise monsters can hear this tic, if there was one.
+    pub fn noise(&self) -> Option<SectorId> {
+        self.noise
+    }
+
+    /// Returns true if the REJECT lump rules out anything in sector `to` being seen from sector
+    /// `from`; a cheap test to try before `check_sight`.
+    pub fn reject_quick(&self, from: SectorId, to: SectorId) -> bool {
+        self.geometry.reject_quick(from, to)
+    }
+
+    /// Returns true if the level has no REJECT table to speak of, so `reject_quick` isn't worth
+    /// calling (see `wad::Level::reject_empty`).
+    pub fn reject_empty(&self) -> bool {
+        self.geometry.reject_empty()
+    }
+
+    /// Returns whether the point `to_z` above `to` can be seen from `from_z` above `from`,
+    /// through the level's current openings (see `wad::Level::check_sight`).
+    pub fn check_sight(&self, from: Pnt2f, from_z: f32, to: Pnt2f, to_z: f32) -> bool {
+        self.geometry
+            .check_sight(from, from_z, to, to_z, |sector_id| {
+                // A sector without heights is treated as shut, blocking sight.
+                self.sector_heights(sector_id).unwrap_or((0.0, 0.0))
+            })
+    }
+
+    /// Returns whether a monster `radius` wide and `height` tall can't step from `from` to `to`,
+    /// because it would move into a wall: a one-sided or monster-blocking linedef, or a
+    /// two-sided one whose opening is too short or more than `max_step` above its floor.
+    ///
+    /// Only the linedefs in the blockmap cells around `to` are looked at. Walls the monster
+    /// already overlaps only block it from getting closer to them, so it can't get stuck.
+    pub fn blocks_move(
+        &self,
+        from: Pnt2f,
+        to: Pnt2f,
+        radius: f32,
+        height: f32,
+        max_step: f32,
+    ) -> bool {
+        let floor = self.floor_height_at(from);
+        let geometry = &*self.geometry;
+        geometry
+            .blockmap()
+            .linedefs_near(to, radius)
+            .into_iter()
+            .filter_map(|id| geometry.linedefs.get(id as usize))
+            .any(|linedef| {
+                let (start, end) = match (
+                    geometry.vertex(linedef.start_vertex),
+                    geometry.vertex(linedef.end_vertex),
+                ) {
+                    (Some(start), Some(end)) => (start, end),
+                    _ => return false,
+                };
+                let line = Line2f::from_two_points(start, end);
+                let distance = |pos: Pnt2f| {
+                    let offset = (pos - line.origin).dot(line.displace);
+                    (pos - line.at_offset(offset.max(0.0).min(line.length))).magnitude()
+                };
+                let to_distance = distance(to);
+                if to_distance >= radius || to_distance >= distance(from) {
+                    return false;
+                }
+                let sectors = geometry
+                    .right_sidedef(linedef)
+                    .and_then(|right| Some((right.sector, geometry.left_sidedef(linedef)?.sector)));
+                let (right, left) = match sectors {
+                    Some(sectors) if !linedef.impassable() && !linedef.blocks_monsters() => sectors,
+                    _ => return true,
+                };
+                let ((right_floor, right_ceiling), (left_floor, left_ceiling)) =
+                    match (self.sector_heights(right), self.sector_heights(left)) {
+                        (Some(right), Some(left)) => (right, left),
+                        _ => return true,
+                    };
+                let bottom = right_floor.max(left_floor);
+                let top = right_ceiling.min(left_ceiling);
+                top - bottom < height || bottom - floor > max_step
+            })
+    }
+
+    /// Returns the current floor and ceiling heights of a sector, or `None` (with a warning) if
+    /// the id is out of range.
+    fn sector_heights(&self, sector_id: SectorId) -> Option<(f32, f32)> {
+        let heights = self
+            .geometry
+            .sectors
+            .get(sector_id as usize)
+            .and_then(|sector| {
+                let offset = |ids: &[ObjectId]| {
+                    let id = ids.get(sector_id as usize)?;
+                    self.object_offsets.get(id.0 as usize).cloned()
+                };
+                Some((
+                    from_wad_height(sector.floor_height) + offset(&self.floor_ids)?,
+                    from_wad_height(sector.ceiling_height) + offset(&self.ceiling_ids)?,
+                ))
+            });
+        if heights.is_none() {
+            warn!("No heights for out of range sector {}.", sector_id);
+        }
+        heights
+    }
+
+    /// Returns the damage dealt over `timestep` to a sphere caught under a crushing ceiling.
+    pub fn crush_damage(&self, head: &Sphere, timestep: f32) -> f32 {
+        let sector = match self
+            .geometry
+            .sector_at(Pnt2f::new(head.center.x, head.center.z))
+        {
+            Some(sector) => sector,
+            None => return 0.0,
+        };
+        let sector_id = self.geometry.sector_id(sector);
+        let crushed = self.crushing.iter().any(|&(crushing_id, ceiling)| {
+            crushing_id == sector_id && ceiling < head.center.y + head.radius
+        });
+        if crushed {
+            CRUSH_DAMAGE_PER_SECOND * timestep
+        } else {
+            0.0
+        }
+    }
+
+    /// Activates the triggers walked over, pushed or shot by the player. Locked triggers need the
+    /// right `keys`; if one of those was stopped, its lock is returned.
     pub fn poll_triggers(
         &mut self,
         transform: &Trans3,
         moved: Vec3f,
         action: Option<PlayerAction>,
-    ) {
+        keys: KeyInventory,
+    ) -> Option<Lock> {
+        let mut locked_out = None;
         let position = Pnt2f::new(transform.disp.x, transform.disp.z);
         let walked = Line2f::from_origin_and_displace(position, vec2(-moved.x, -moved.z));
         let action_and_line = action.map(|action| {
@@ -94,48 +760,14 @@ impl Level {
         });
 
         for (i_trigger, trigger) in self.triggers.iter().enumerate() {
-            let mut triggered = false;
-            match trigger.trigger_type {
-                TriggerType::WalkOver => {
-                    if let Some(offset) = walked.segment_intersect_offset(&trigger.line) {
-                        debug!("Trigger {} walk-activated offset={}", i_trigger, offset);
-                        triggered = true;
-                    }
-                }
-                TriggerType::Push | TriggerType::Switch => {
-                    if let Some((PlayerAction::Push, line)) = action_and_line {
-                        if let Some(offset) = line.segment_intersect_offset(&trigger.line) {
-                            debug!("Trigger {} push-activated offset={}", i_trigger, offset);
-                            triggered = true;
-                        }
-                    }
-                }
-                TriggerType::Gun => {
-                    if let Some((PlayerAction::Shoot, line)) = action_and_line {
-                        if let Some(offset) = line.segment_intersect_offset(&trigger.line) {
-                            debug!("Trigger {} shoot-activated offset={}", i_trigger, offset);
-                            triggered = true;
-                        }
-                    }
-                }
-                TriggerType::Any => {
-                    if let Some(offset) = walked.segment_intersect_offset(&trigger.line) {
-                        debug!(
-                            "Trigger {} (any) walk-activated offset={}",
-                            i_trigger, offset
-                        );
-                        triggered = true;
-                    } else if let Some((PlayerAction::Push, line)) = action_and_line {
-                        if let Some(offset) = line.segment_intersect_offset(&trigger.line) {
-                            debug!(
-                                "Trigger {} (any) push-activated offset={}",
-                                i_trigger, offset
-                            );
-                            triggered = true;
-                        }
-                    }
+            let triggered = is_triggered(i_trigger, trigger, &walked, action_and_line);
+            if let (true, Some(lock)) = (triggered, trigger.lock) {
+                if !keys.opens(lock) {
+                    debug!("Trigger {} is locked: {:?}", i_trigger, lock);
+                    locked_out = Some(lock);
+                    continue;
                 }
-            };
+            }
             if triggered {
                 for &effect in &trigger.move_effects {
                     let effect_index = effect.object_id.0 as usize;
@@ -143,7 +775,13 @@ impl Level {
                         "Started effect {} with type {}.",
                         effect_index, trigger.special_type
                     );
-                    self.effects.insert(effect_index, effect);
+                    self.effects.insert(
+                        effect_index,
+                        ActiveEffect {
+                            effect,
+                            original: effect,
+                        },
+                    );
                 }
 
                 if trigger.unimplemented {
@@ -154,8 +792,8 @@ impl Level {
                     self.removed.push(i_trigger);
                 }
 
-                if trigger.exit_effect.is_some() {
-                    self.exit_triggered = true;
+                if let Some(exit) = trigger.exit_effect {
+                    self.exit_triggered = Some(exit);
                 }
             }
         }
@@ -163,8 +801,106 @@ impl Level {
         for &i_removed in self.removed.iter().rev() {
             self.triggers.swap_remove(i_removed);
         }
-        self.removed.clear()
+        self.removed.clear();
+        locked_out
+    }
+}
+
+/// A sector on the other side of a two-sided linedef.
+#[derive(Copy, Clone, Debug)]
+struct Neighbour {
+    sector_id: SectorId,
+    /// Whether any of the linedefs between the two sectors lets sound through.
+    carries_sound: bool,
+}
+
+/// Lists, for each sector, the sectors it shares a two-sided linedef with, sorted by id.
+fn sector_neighbours(level: &WadLevel) -> Vec<Vec<Neighbour>> {
+    let mut neighbours = vec![Vec::new(); level.sectors.len()];
+    for linedef in &level.linedefs {
+        if !linedef.is_two_sided() {
+            continue;
+        }
+        let sector_id = |sidedef| level.sidedef_sector(sidedef).map(|s| level.sector_id(s));
+        let (right, left) = match (
+            level.right_sidedef(linedef).and_then(sector_id),
+            level.left_sidedef(linedef).and_then(sector_id),
+        ) {
+            (Some(right), Some(left)) if right != left => (right, left),
+            _ => continue,
+        };
+        let carries_sound = !linedef.blocks_sound();
+        for &(from, to) in &[(right, left), (left, right)] {
+            neighbours[from as usize].push(Neighbour {
+                sector_id: to,
+                carries_sound,
+            });
+        }
+    }
+    for sector_neighbours in &mut neighbours {
+        sector_neighbours.sort_unstable_by_key(|neighbour| neighbour.sector_id);
+        sector_neighbours.dedup_by(|duplicate, kept| {
+            if duplicate.sector_id != kept.sector_id {
+                return false;
+            }
+            kept.carries_sound |= duplicate.carries_sound;
+            true
+        });
+    }
+    neighbours
+}
+
+/// Whether a trigger was activated by the player walking along `walked` or by an action along
+/// the given line.
+fn is_triggered(
+    i_trigger: usize,
+    trigger: &Trigger,
+    walked: &Line2f,
+    action_and_line: Option<(PlayerAction, Line2f)>,
+) -> bool {
+    let mut triggered = false;
+    match trigger.trigger_type {
+        TriggerType::WalkOver => {
+            if let Some(offset) = walked.segment_intersect_offset(&trigger.line) {
+                debug!("Trigger {} walk-activated offset={}", i_trigger, offset);
+                triggered = true;
+            }
+        }
+        TriggerType::Push | TriggerType::Switch => {
+            if let Some((PlayerAction::Push, line)) = action_and_line {
+                if let Some(offset) = line.segment_intersect_offset(&trigger.line) {
+                    debug!("Trigger {} push-activated offset={}", i_trigger, offset);
+                    triggered = true;
+                }
+            }
+        }
+        TriggerType::Gun => {
+            if let Some((PlayerAction::Shoot, line)) = action_and_line {
+                if let Some(offset) = line.segment_intersect_offset(&trigger.line) {
+                    debug!("Trigger {} shoot-activated offset={}", i_trigger, offset);
+                    triggered = true;
+                }
+            }
+        }
+        TriggerType::Any => {
+            if let Some(offset) = walked.segment_intersect_offset(&trigger.line) {
+                debug!(
+                    "Trigger {} (any) walk-activated offset={}",
+                    i_trigger, offset
+                );
+                triggered = true;
+            } else if let Some((PlayerAction::Push, line)) = action_and_line {
+                if let Some(offset) = line.segment_intersect_offset(&trigger.line) {
+                    debug!(
+                        "Trigger {} (any) push-activated offset={}",
+                        i_trigger, offset
+                    );
+                    triggered = true;
+                }
+            }
+        }
     }
+    triggered
 }
 
 impl<'context> System<'context> for Level {
@@ -186,66 +922,67 @@ impl<'context> System<'context> for Level {
             deps.entities.remove(self.root);
             *self = Builder::build(&mut deps)?;
             self.level_changed = true;
+            self.level_reopened = deps.wad.level_reopened();
         } else if self.level_changed {
             info!("Level changed. {}", deps.entities.debug_tree_dump(4));
             self.level_changed = false;
+            self.level_reopened = false;
         }
 
-        if self.exit_triggered {
-            self.exit_triggered = false;
-            deps.entities.remove(self.root);
-            let current_index = deps.wad.level_index();
-            deps.wad.change_level(current_index + 1);
+        // The level (root included) stays in place until the next one has loaded in the
+        // background and `level_changed` rebuilds it above.
+        if let Some(exit) = self.exit_triggered.take() {
+            deps.wad.exit_level(exit);
         }
 
         self.volume.update(deps.transforms);
         let timestep = deps.tick.timestep();
-        for (i_effect, effect) in &mut self.effects {
+        self.update_projectiles(timestep);
+        self.update_monsters(timestep);
+        for (monster, entity_id) in self.monsters.iter().zip(&mut self.monster_entities) {
+            let entity = match *entity_id {
+                Some(entity) => entity,
+                None => continue,
+            };
+            if monster.is_dead() {
+                deps.entities.remove(entity);
+                *entity_id = None;
+            } else if let Some(transform) = deps.transforms.get_local_mut(entity) {
+                transform.disp = monster.position().to_vec();
+            }
+        }
+        for (pickup, entity_id) in self.pickups.iter().zip(&mut self.pickup_entities) {
+            if !pickup.is_active() {
+                if let Some(entity) = entity_id.take() {
+                    deps.entities.remove(entity);
+                }
+            }
+        }
+        self.crushing.clear();
+        for (i_effect, active) in &mut self.effects {
             let entity_id = self.objects[i_effect];
             let transform = deps
                 .transforms
                 .get_local_mut(entity_id)
                 .expect("no transform on object");
             let current_offset = &mut transform.disp[1];
-            let mut timestep = timestep;
-
-            loop {
-                if effect.first_height_offset != *current_offset {
-                    let offset_difference = effect.first_height_offset - *current_offset;
-                    let sign = offset_difference.signum();
-                    let time_left = offset_difference.abs() / effect.speed;
-                    if time_left > timestep {
-                        *current_offset += sign * effect.speed * timestep;
-                        break;
-                    } else {
-                        *current_offset = effect.first_height_offset;
-                        timestep -= time_left;
-                        effect.first_height_offset = *current_offset;
-                        debug!("Effect {}: finished first offset.", i_effect);
-                    }
-                }
-
-                if effect.wait > timestep {
-                    effect.wait -= timestep;
-                    break;
-                } else {
-                    debug!("Effect {}: finished waiting.", i_effect);
-                    timestep -= effect.wait;
-                    effect.wait = 0.0;
-                }
-
-                if let Some(offset) = effect.second_height_offset.take() {
-                    effect.first_height_offset = offset;
-                    debug!(
-                        "Effect {}: moved second offset {} into first.",
-                        i_effect, offset
-                    );
-                    continue;
-                }
-
-                debug!("Effect {}: done, removing.", i_effect);
+            if advance_effect(i_effect, active, current_offset, timestep) {
                 self.removed.push(i_effect);
-                break;
+            }
+            let effect = &active.effect;
+            self.object_offsets[i_effect] = *current_offset;
+
+            if effect.crush {
+                match self.geometry.sectors.get(effect.sector_id as usize) {
+                    Some(sector) => self.crushing.push((
+                        effect.sector_id,
+                        from_wad_height(sector.ceiling_height) + *current_offset,
+                    )),
+                    None => warn!(
+                        "Skipping crusher in out of range sector {}.",
+                        effect.sector_id
+                    ),
+                }
             }
         }
 
@@ -274,6 +1011,7 @@ impl<'context> System<'context> for Level {
 
 struct Indices {
     wall: Vec<u32>,
+    masked_wall: Vec<u32>,
     flat: Vec<u32>,
     sky: Vec<u32>,
     decor: Vec<u32>,
@@ -291,6 +1029,7 @@ impl Indices {
     fn with_capacity(capacity: usize) -> Self {
         Indices {
             wall: Vec::with_capacity(capacity),
+            masked_wall: Vec::with_capacity(capacity),
             flat: Vec::with_capacity(capacity),
             sky: Vec::with_capacity(capacity),
             decor: Vec::with_capacity(capacity),
@@ -308,14 +1047,17 @@ struct Builder<'a> {
     materials: &'a LevelMaterials,
 
     lights: Lights,
-    start_pos: Pnt3f,
-    start_yaw: Rad<f32>,
+    player_starts: [Option<(Pnt3f, Rad<f32>)>; 4],
+    deathmatch_starts: Vec<(Pnt3f, Rad<f32>)>,
 
     static_vertices: Vec<StaticVertex>,
     sky_vertices: Vec<SkyVertex>,
     decor_vertices: Vec<SpriteVertex>,
+    monsters: Vec<(Monster, [SpriteVertex; 4])>,
+    pickups: Vec<(Pickup, [SpriteVertex; 4])>,
 
     object_indices: VecMap<Indices>,
+    dynamic_lights: Vec<DynamicLight>,
 
     num_wall_quads: usize,
     num_floor_polys: usize,
@@ -356,14 +1098,17 @@ impl<'a> Builder<'a> {
             materials: deps.game_shaders.level_materials(),
 
             lights: Lights::new(),
-            start_pos: Pnt3f::origin(),
-            start_yaw: Rad(0.0f32),
+            player_starts: [None; 4],
+            deathmatch_starts: Vec::new(),
 
             static_vertices: Vec::with_capacity(16_384),
             sky_vertices: Vec::with_capacity(16_384),
             decor_vertices: Vec::with_capacity(16_384),
+            monsters: Vec::new(),
+            pickups: Vec::new(),
 
             object_indices: VecMap::new(),
+            dynamic_lights: Vec::new(),
 
             num_wall_quads: 0,
             num_floor_polys: 0,
@@ -380,6 +1125,11 @@ impl<'a> Builder<'a> {
             deps.wad.walk(&mut builder.chain(&mut world_builder));
             world_builder.build()
         };
+        deps.game_shaders.set_dynamic_lights(
+            deps.window,
+            deps.uniforms,
+            &builder.dynamic_lights,
+        )?;
 
         info!(
             "Level built in {:.2}ms:\n\
@@ -404,7 +1154,9 @@ impl<'a> Builder<'a> {
             builder
                 .object_indices
                 .values()
-                .map(|indices| indices.wall.len() + indices.flat.len())
+                .map(|indices| {
+                    indices.wall.len() + indices.masked_wall.len() + indices.flat.len()
+                })
                 .sum::<usize>()
                 / 3,
             builder
@@ -468,6 +1220,24 @@ impl<'a> Builder<'a> {
                     .attach_model(entity, mesh, builder.materials.walls.material);
             }
 
+            if !indices.masked_wall.is_empty() {
+                let entity = deps.entities.add(object, "masked_walls")?;
+                let mesh = deps
+                    .meshes
+                    .add(
+                        deps.window,
+                        deps.entities,
+                        entity,
+                        "object_masked_walls_mesh",
+                    )
+                    .shared(global_static_mesh)
+                    .immutable_indices(&indices.masked_wall)?
+                    .build()?;
+                deps.transforms.attach_identity(entity);
+                deps.render
+                    .attach_masked_model(entity, mesh, builder.materials.walls.material);
+            }
+
             if !indices.decor.is_empty() {
                 let entity = deps.entities.add(object, "decor")?;
                 let mesh = deps
@@ -495,6 +1265,45 @@ impl<'a> Builder<'a> {
             }
         }
 
+        let mut monsters = Vec::with_capacity(builder.monsters.len());
+        let mut monster_entities = Vec::with_capacity(builder.monsters.len());
+        for (monster, vertices) in builder.monsters {
+            let entity = Self::add_sprite(
+                deps,
+                root,
+                "monster",
+                &vertices,
+                monster.position(),
+                builder.materials,
+            )?;
+            monsters.push(monster);
+            monster_entities.push(Some(entity));
+        }
+        let mut pickups = Vec::with_capacity(builder.pickups.len());
+        let mut pickup_entities = Vec::with_capacity(builder.pickups.len());
+        for (pickup, vertices) in builder.pickups {
+            let entity = Self::add_sprite(
+                deps,
+                root,
+                "pickup",
+                &vertices,
+                pickup.position(),
+                builder.materials,
+            )?;
+            pickups.push(pickup);
+            pickup_entities.push(Some(entity));
+        }
+        info!(
+            "Spawned {} monsters and {} pickups.",
+            monsters.len(),
+            pickups.len()
+        );
+
+        let start_pos = builder.player_starts[0].unwrap_or_else(|| {
+            warn!("No player 1 start in level.");
+            (Pnt3f::origin(), Rad(0.0))
+        });
+        let object_offsets = vec![0.0; objects.len()];
         Ok(Level {
             root,
             volume,
@@ -502,11 +1311,40 @@ impl<'a> Builder<'a> {
             triggers: deps.wad.analysis.take_triggers(),
             removed: Vec::with_capacity(128),
             effects: VecMap::new(),
-            start_pos: builder.start_pos,
-            start_yaw: builder.start_yaw,
+            crushing: Vec::new(),
+            start_pos,
+            player_starts: builder
+                .player_starts
+                .iter()
+                .filter_map(|&start| start)
+                .collect(),
+            deathmatch_starts: builder.deathmatch_starts,
             lights: builder.lights,
-            exit_triggered: false,
+            exit_triggered: None,
             level_changed: true,
+            level_reopened: false,
+            projectiles: Vec::new(),
+            explosions: Vec::new(),
+            monsters,
+            monster_entities,
+            target: None,
+            monster_damage: 0.0,
+            monster_knockback: Vec2f::zero(),
+            pickups,
+            pickup_entities,
+            tic: 0,
+            tic_time: 0.0,
+            visited_sectors: vec![false; deps.wad.level.sectors.len()],
+            neighbours: sector_neighbours(&deps.wad.level),
+            noise: None,
+            floor_ids: (0..deps.wad.level.sectors.len())
+                .map(|i_sector| deps.wad.analysis.floor_id(i_sector as SectorId))
+                .collect(),
+            ceiling_ids: (0..deps.wad.level.sectors.len())
+                .map(|i_sector| deps.wad.analysis.ceiling_id(i_sector as SectorId))
+                .collect(),
+            object_offsets,
+            geometry: deps.wad.level.clone(),
         })
     }
 
@@ -515,34 +1353,53 @@ impl<'a> Builder<'a> {
         &mut self,
         xz: Pnt2f,
         y: f32,
+        normal: Vec3f,
         tile_u: f32,
         tile_v: f32,
         light_info: u8,
         scroll_rate: f32,
+        (floor, ceiling): (f32, f32),
         bounds: &WadBounds,
     ) -> &mut Self {
+        let height_fraction = if ceiling > floor {
+            ((y - floor) / (ceiling - floor)).max(0.0).min(1.0)
+        } else {
+            0.0
+        };
         self.static_vertices.push(StaticVertex {
             a_pos: [xz[0], y, xz[1]],
+            a_normal: [normal[0], normal[1], normal[2]],
             a_atlas_uv: [bounds.pos[0], bounds.pos[1]],
             a_tile_uv: [tile_u, tile_v],
             a_tile_size: [bounds.size[0], bounds.size[1]],
             a_scroll_rate: scroll_rate,
             a_num_frames: bounds.num_frames as u8,
             a_row_height: bounds.row_height as f32,
+            a_height_fraction: height_fraction,
             a_light: light_info,
         });
         self
     }
 
-    fn flat_vertex(&mut self, xz: Pnt2f, y: f32, light_info: u8, bounds: &WadBounds) -> &mut Self {
+    /// Adds a vertex of a floor (`normal_y` of 1) or ceiling (-1) polygon.
+    fn flat_vertex(
+        &mut self,
+        xz: Pnt2f,
+        y: f32,
+        normal_y: f32,
+        light_info: u8,
+        bounds: &WadBounds,
+    ) -> &mut Self {
         self.static_vertices.push(StaticVertex {
             a_pos: [xz[0], y, xz[1]],
+            a_normal: [0.0, normal_y, 0.0],
             a_atlas_uv: [bounds.pos[0], bounds.pos[1]],
             a_tile_uv: [-xz[0] * 100.0, -xz[1] * 100.0],
             a_tile_size: [bounds.size[0], bounds.size[1]],
             a_scroll_rate: 0.0,
             a_num_frames: bounds.num_frames as u8,
             a_row_height: bounds.row_height as f32,
+            a_height_fraction: if normal_y > 0.0 { 0.0 } else { 1.0 },
             a_light: light_info,
         });
         self
@@ -555,16 +1412,52 @@ impl<'a> Builder<'a> {
         self
     }
 
-    fn decor_vertex(
-        &mut self,
-        pos: Pnt3f,
-        local_x: f32,
-        tile_u: f32,
-        tile_v: f32,
-        bounds: &WadBounds,
-        light_info: u8,
-    ) -> &mut Self {
-        self.decor_vertices.push(SpriteVertex {
+    /// Adds an entity drawing a single sprite, which can be moved or removed on its own.
+    fn add_sprite(
+        deps: &mut Dependencies,
+        root: EntityId,
+        name: &'static str,
+        vertices: &[SpriteVertex; 4],
+        position: Pnt3f,
+        materials: &LevelMaterials,
+    ) -> Result<EntityId> {
+        let entity = deps.entities.add(root, name)?;
+        let mesh = deps
+            .meshes
+            .add(deps.window, deps.entities, entity, "sprite_mesh")
+            .immutable(vertices)?
+            .immutable_indices(&SPRITE_QUAD_INDICES)?
+            .build()?;
+        deps.transforms.attach_identity(entity);
+        deps.transforms
+            .get_local_mut(entity)
+            .expect("sprite has no transform")
+            .disp = position.to_vec();
+        deps.render
+            .attach_model(entity, mesh, materials.decor.material);
+        Ok(entity)
+    }
+
+    /// Returns the four corners of a decor sprite, in the order `decor_quad` expects, relative
+    /// to `origin`.
+    fn sprite_quad(&mut self, decor: &Decor, origin: Pnt3f) -> Option<[SpriteVertex; 4]> {
+        let &Decor {
+            low,
+            high,
+            half_width,
+            light_info,
+            tex_name,
+            ..
+        } = decor;
+        let light_info = self.add_light_info(light_info);
+        let bounds = if let Some(bounds) = self.materials.decor.bounds.get(&tex_name) {
+            *bounds
+        } else {
+            warn!("No such decor texture {}.", tex_name);
+            return None;
+        };
+        let (low, high) = (low - origin.to_vec(), high - origin.to_vec());
+        let vertex = |pos: Pnt3f, local_x, tile_u, tile_v| SpriteVertex {
             a_pos: [pos[0], pos[1], pos[2]],
             a_local_x: local_x,
             a_atlas_uv: [bounds.pos[0], bounds.pos[1]],
@@ -572,8 +1465,13 @@ impl<'a> Builder<'a> {
             a_tile_size: [bounds.size[0], bounds.size[1]],
             a_num_frames: 1,
             a_light: light_info,
-        });
-        self
+        };
+        Some([
+            vertex(low, -half_width, 0.0, bounds.size[1]),
+            vertex(low, half_width, bounds.size[0], bounds.size[1]),
+            vertex(high, half_width, bounds.size[0], 0.0),
+            vertex(high, -half_width, 0.0, 0.0),
+        ])
     }
 
     fn flat_poly(&mut self, object_id: ObjectId, poly_length: usize) {
@@ -584,10 +1482,15 @@ impl<'a> Builder<'a> {
         );
     }
 
-    fn wall_quad(&mut self, object_id: ObjectId) {
+    fn wall_quad(&mut self, object_id: ObjectId, masked: bool) {
+        let indices = Indices::in_map(&mut self.object_indices, object_id);
         Self::any_quad(
             self.static_vertices.len(),
-            &mut Indices::in_map(&mut self.object_indices, object_id).wall,
+            if masked {
+                &mut indices.masked_wall
+            } else {
+                &mut indices.wall
+            },
         );
     }
 
@@ -656,8 +1559,10 @@ impl<'a> LevelVisitor for Builder<'a> {
             scroll,
             vertices: (v1, v2),
             height_range: (low, high),
+            sector_heights: heights,
             tex_start: (s1, t1),
             tex_end: (s2, t2),
+            masked,
             ..
         } = quad;
 
@@ -672,12 +1577,16 @@ impl<'a> LevelVisitor for Builder<'a> {
             warn!("No such wall texture {}.", tex_name);
             return;
         };
-        let light_info = self.add_light_info(light_info);
-        self.wall_vertex(v1, low, s1, t1, light_info, scroll, &bounds)
-            .wall_vertex(v2, low, s2, t1, light_info, scroll, &bounds)
-            .wall_vertex(v2, high, s2, t2, light_info, scroll, &bounds)
-            .wall_vertex(v1, high, s1, t2, light_info, scroll, &bounds)
-            .wall_quad(object_id);
+        let light = self.add_light_info(light_info);
+        // WAD coordinates are mirrored in world space, so the front of a wall (to the right of its
+        // direction in the WAD) faces to the left of its direction here.
+        let direction = v2 - v1;
+        let normal = Vec3f::new(-direction[1], 0.0, direction[0]).normalize_or_zero();
+        self.wall_vertex(v1, low, normal, s1, t1, light, scroll, heights, &bounds)
+            .wall_vertex(v2, low, normal, s2, t1, light, scroll, heights, &bounds)
+            .wall_vertex(v2, high, normal, s2, t2, light, scroll, heights, &bounds)
+            .wall_vertex(v1, high, normal, s1, t2, light, scroll, heights, &bounds)
+            .wall_quad(object_id, masked);
     }
 
     fn visit_floor_poly(&mut self, poly: &StaticPoly) {
@@ -697,7 +1606,7 @@ impl<'a> LevelVisitor for Builder<'a> {
         };
         let light_info = self.add_light_info(light_info);
         for &vertex in vertices {
-            self.flat_vertex(vertex, height, light_info, &bounds);
+            self.flat_vertex(vertex, height, 1.0, light_info, &bounds);
         }
         self.flat_poly(object_id, vertices.len());
     }
@@ -719,7 +1628,7 @@ impl<'a> LevelVisitor for Builder<'a> {
         };
         let light_info = self.add_light_info(light_info);
         for &vertex in vertices.iter().rev() {
-            self.flat_vertex(vertex, height, light_info, &bounds);
+            self.flat_vertex(vertex, height, -1.0, light_info, &bounds);
         }
         self.flat_poly(object_id, vertices.len());
     }
@@ -755,40 +1664,105 @@ impl<'a> LevelVisitor for Builder<'a> {
     }
 
     fn visit_marker(&mut self, pos: Pnt3f, yaw: Rad<f32>, marker: Marker) {
-        if let Marker::StartPos { player: 0 } = marker {
-            self.start_pos = pos + Vec3f::new(0.0, 0.5, 32.0 / 100.0);
-            self.start_yaw = yaw;
+        let start = (pos + Vec3f::new(0.0, 0.5, 32.0 / 100.0), yaw);
+        match marker {
+            Marker::StartPos { player } => {
+                if let Some(player_start) = self.player_starts.get_mut(player) {
+                    *player_start = Some(start);
+                }
+            }
+            Marker::DeathmatchStart => self.deathmatch_starts.push(start),
+            Marker::TeleportEnd => {}
         }
     }
 
+    fn visit_dynamic_light(&mut self, light: &DynamicLight) {
+        self.dynamic_lights.push(*light);
+    }
+
     fn visit_decor(&mut self, decor: &Decor) {
         self.num_decors += 1;
-        let &Decor {
-            object_id,
-            low,
-            high,
-            half_width,
-            light_info,
-            tex_name,
-        } = decor;
-        let light_info = self.add_light_info(light_info);
-        let bounds = if let Some(bounds) = self.materials.decor.bounds.get(&tex_name) {
-            *bounds
-        } else {
-            warn!("No such decor texture {}.", tex_name);
-            return;
+        if let Some(vertices) = self.sprite_quad(decor, Pnt3f::origin()) {
+            self.decor_vertices.extend_from_slice(&vertices);
+            self.decor_quad(decor.object_id);
+        }
+    }
+
+    fn visit_pickup(&mut self, pickup: &ThingSpawn) {
+        let position = pickup.decor.low;
+        if let Some(vertices) = self.sprite_quad(&pickup.decor, position) {
+            self.pickups.push((
+                Pickup::new(pickup.thing_type, position, pickup.radius),
+                vertices,
+            ));
+        }
+    }
+
+    fn visit_monster(&mut self, monster: &ThingSpawn) {
+        let position = monster.decor.low;
+        if let Some(vertices) = self.sprite_quad(&monster.decor, position) {
+            self.monsters.push((
+                Monster::new(
+                    monster.thing_type,
+                    position,
+                    monster.yaw,
+                    monster.radius,
+                    monster.ambush,
+                ),
+                vertices,
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{advance_effect, ActiveEffect};
+    use engine::{Entities, InfallibleSystem, Transforms};
+    use math::prelude::*;
+    use math::Trans3;
+    use wad::{MoveEffect, ObjectId};
+
+    #[test]
+    fn test_effect_keeps_moving_after_exit() {
+        let mut entities = Entities::create(());
+        let mut transforms = Transforms::create(&entities);
+        let root = entities.add_root("level");
+        let door = entities.add(root, "door").unwrap();
+        transforms.attach(door, Trans3::one());
+
+        // A door which opens by 1.0 at 2.0 units per second, waits, then closes again.
+        let effect = MoveEffect {
+            object_id: ObjectId(0),
+            sector_id: 0,
+            first_height_offset: 1.0,
+            second_height_offset: Some(0.0),
+            speed: 2.0,
+            wait: 1.0,
+            repeat: false,
+            crush: false,
         };
-        self.decor_vertex(low, -half_width, 0.0, bounds.size[1], &bounds, light_info)
-            .decor_vertex(
-                low,
-                half_width,
-                bounds.size[0],
-                bounds.size[1],
-                &bounds,
-                light_info,
-            )
-            .decor_vertex(high, half_width, bounds.size[0], 0.0, &bounds, light_info)
-            .decor_vertex(high, -half_width, 0.0, 0.0, &bounds, light_info)
-            .decor_quad(object_id);
+        let mut active = ActiveEffect {
+            effect,
+            original: effect,
+        };
+
+        // The exit fires as the door starts opening. The next level loads in the background for
+        // the following updates, during which the level's entities must stay alive.
+        let mut finished = false;
+        for _ in 0..40 {
+            entities.update(());
+            transforms.update(&entities);
+            let transform = transforms
+                .get_local_mut(door)
+                .expect("no transform on object");
+            finished = advance_effect(0, &mut active, &mut transform.disp[1], 0.1);
+            if finished {
+                break;
+            }
+        }
+        assert!(finished);
+        assert!(entities.contains(door));
+        assert!(transforms.get_local_mut(door).unwrap().disp[1].abs() < 1e-6);
     }
 }
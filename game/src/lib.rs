@@ -1,18 +1,34 @@
 #![cfg_attr(feature = "cargo-clippy", allow(clippy::too_many_arguments))]
 
+mod console;
+mod debug_overlay;
 mod errors;
 mod game;
 mod game_shaders;
 mod hud;
 mod level;
 mod lights;
+mod monster;
+mod pathfinder;
+mod pickup;
 mod player;
+mod sector_special;
 mod vertex;
 mod wad_system;
+mod weapon;
 mod world;
 
-pub use self::errors::{Error, Result};
-pub use self::game::{create, Game, GameConfig};
-pub use self::level::Level;
+pub use self::console::{Command, CommandRegistry, Console};
+pub use self::errors::{Error, ErrorKind, Result};
+pub use self::game::{create, EngineState, Game, GameConfig};
+pub use self::game_shaders::{ClearColor, RenderQuality};
+pub use self::hud::{Crosshair, CrosshairStyle};
+pub use self::level::{Level, RayHit};
+pub use self::monster::{Monster, MonsterAttack, MonsterState};
+pub use self::pathfinder::Pathfinder;
+pub use self::pickup::{Pickup, PickupEffect};
+pub use self::player::{GameFlags, WidescreenMode};
+pub use self::sector_special::SectorSpecial;
+pub use self::weapon::{Ammo, Explosion, Projectile, Weapon, WeaponEffect, WeaponType};
 
 pub const SHADER_ROOT: &str = "assets/shaders";
@@ -0,0 +1,426 @@
+use super::level::Level;
+use super::pathfinder::{Pathfinder, MAX_STEP_HEIGHT};
+use super::sector_special::SectorSpecial;
+use super::weapon::Projectile;
+use math::prelude::*;
+use math::{vec2, Pnt2f, Pnt3f, Rad, Sphere, Vec3f};
+use wad::types::ThingType;
+use wad::util::from_wad_height;
+
+/// Where a monster is in DOOM's state machine: it waits until it sees or hears the player, then
+/// chases them, stopping to attack whenever it can, flinching when hurt.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MonsterState {
+    Idle,
+    Chase,
+    Attack,
+    Pain,
+    Dead,
+}
+
+/// What a monster did to the player when attacking.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MonsterAttack {
+    /// A hitscan or melee attack which landed, dealing this much damage.
+    Damage(f32),
+    /// A missile was launched, to be simulated by the level.
+    Missile(Projectile),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum AttackKind {
+    Hitscan { bullets: u8 },
+    Melee { damage: u8 },
+    Missile { speed: i16, damage: u8 },
+}
+
+/// The properties of a monster type, from DOOM's `mobjinfo` table.
+#[derive(Copy, Clone, Debug)]
+struct MonsterInfo {
+    health: u16,
+    /// Distance moved per tic, in map units.
+    speed: i16,
+    height: i16,
+    /// Out of 256, the chance that taking damage makes the monster flinch.
+    pain_chance: u16,
+    /// Tics to wait after one attack before the next.
+    refire_tics: u32,
+    attack: AttackKind,
+}
+
+impl MonsterInfo {
+    fn for_thing(thing_type: ThingType) -> Self {
+        let (health, speed, height, pain_chance, refire_tics, attack) = match thing_type {
+            // Former human.
+            3004 => (20, 8, 56, 200, 35, AttackKind::Hitscan { bullets: 1 }),
+            // Former human sergeant.
+            9 => (30, 8, 56, 170, 35, AttackKind::Hitscan { bullets: 3 }),
+            // Heavy weapon dude.
+            65 => (70, 8, 56, 170, 4, AttackKind::Hitscan { bullets: 1 }),
+            // Imp.
+            3001 => (
+                60,
+                8,
+                56,
+                200,
+                35,
+                AttackKind::Missile {
+                    speed: 10,
+                    damage: 24,
+                },
+            ),
+            // Demon and spectre.
+            3002 | 58 => (150, 10, 56, 180, 17, AttackKind::Melee { damage: 22 }),
+            // Lost soul.
+            3006 => (100, 8, 56, 256, 17, AttackKind::Melee { damage: 10 }),
+            // Cacodemon.
+            3005 => (
+                400,
+                8,
+                56,
+                128,
+                35,
+                AttackKind::Missile {
+                    speed: 10,
+                    damage: 40,
+                },
+            ),
+            // Baron of hell and hell knight.
+            3003 => (
+                1000,
+                8,
+                64,
+                50,
+                35,
+                AttackKind::Missile {
+                    speed: 15,
+                    damage: 64,
+                },
+            ),
+            69 => (
+                500,
+                8,
+                64,
+                50,
+                35,
+                AttackKind::Missile {
+                    speed: 15,
+                    damage: 64,
+                },
+            ),
+            // Cyberdemon.
+            16 => (
+                4000,
+                16,
+                110,
+                20,
+                17,
+                AttackKind::Missile {
+                    speed: 20,
+                    damage: 128,
+                },
+            ),
+            // Spider mastermind.
+            7 => (3000, 12, 100, 40, 4, AttackKind::Hitscan { bullets: 3 }),
+            _ => (100, 8, 56, 100, 35, AttackKind::Hitscan { bullets: 1 }),
+        };
+        MonsterInfo {
+            health,
+            speed,
+            height,
+            pain_chance,
+            refire_tics,
+            attack,
+        }
+    }
+}
+
+/// A monster walking around the level; `position` is the center of its feet.
+pub struct Monster {
+    info: MonsterInfo,
+    pathfinder: Pathfinder,
+    position: Pnt3f,
+    yaw: Rad<f32>,
+    radius: f32,
+    /// Ambush monsters aren't woken by noise, only by seeing the player.
+    ambush: bool,
+    health: f32,
+    state: MonsterState,
+    /// Tics left in the `Attack` or `Pain` state.
+    state_tics: u32,
+    /// Tics left before the monster may attack.
+    cooldown_tics: u32,
+    random: u32,
+}
+
+impl Monster {
+    pub fn new(
+        thing_type: ThingType,
+        position: Pnt3f,
+        yaw: Rad<f32>,
+        radius: f32,
+        ambush: bool,
+    ) -> Self {
+        let info = MonsterInfo::for_thing(thing_type);
+        Monster {
+            info,
+            pathfinder: Pathfinder::new(from_wad_height(info.speed), from_wad_height(info.height)),
+            position,
+            yaw,
+            radius,
+            ambush,
+            health: f32::from(info.health),
+            state: MonsterState::Idle,
+            state_tics: 0,
+            cooldown_tics: REACTION_TICS,
+            // Any non-zero seed will do; vary it so monsters don't all flinch in lockstep.
+            random: (position.x.to_bits() ^ position.z.to_bits()) | 1,
+        }
+    }
+
+    pub fn state(&self) -> MonsterState {
+        self.state
+    }
+
+    pub fn position(&self) -> Pnt3f {
+        self.position
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    pub fn height(&self) -> f32 {
+        from_wad_height(self.info.height)
+    }
+
+    /// A sphere around the monster, for things hitting it.
+    pub fn body(&self) -> Sphere {
+        let half_height = self.height() * 0.5;
+        Sphere {
+            center: self.position + Vec3f::new(0.0, half_height, 0.0),
+            radius: self.radius.max(half_height),
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.state == MonsterState::Dead
+    }
+
+    /// Takes damage, waking the monster up and possibly making it flinch.
+    pub fn damage(&mut self, amount: f32) {
+        if self.is_dead() || amount <= 0.0 {
+            return;
+        }
+        self.health -= amount;
+        if self.health <= 0.0 {
+            self.state = MonsterState::Dead;
+        } else if self.random_byte() < self.info.pain_chance {
+            self.state = MonsterState::Pain;
+            self.state_tics = PAIN_TICS;
+        } else if self.state == MonsterState::Idle {
+            self.state = MonsterState::Chase;
+        }
+    }
+
+    /// Runs one 35 Hz tic of the monster's AI against the player standing at `player_pos`.
+    ///
+    /// Chasing monsters walk towards the player, steering around walls with a `Pathfinder`.
+    pub fn ai_update(
+        &mut self,
+        tic: u32,
+        level: &Level,
+        player_pos: Pnt3f,
+    ) -> Option<MonsterAttack> {
+        self.cooldown_tics = self.cooldown_tics.saturating_sub(1);
+        match self.state {
+            MonsterState::Dead => None,
+            MonsterState::Idle => {
+                // Sight checks are the expensive part, so idle monsters only look now and then.
+                if (!self.ambush && self.hears(level))
+                    || (tic % LOOK_INTERVAL_TICS == 0 && self.notices(level, player_pos))
+                {
+                    self.state = MonsterState::Chase;
+                    self.cooldown_tics = REACTION_TICS;
+                }
+                None
+            }
+            MonsterState::Attack | MonsterState::Pain => {
+                self.state_tics = self.state_tics.saturating_sub(1);
+                if self.state_tics == 0 {
+                    self.state = MonsterState::Chase;
+                }
+                None
+            }
+            MonsterState::Chase => {
+                let to_player = vec2(
+                    player_pos.x - self.position.x,
+                    player_pos.z - self.position.z,
+                );
+                let distance = to_player.magnitude();
+                if distance > 0.0 {
+                    self.yaw = Rad((-to_player.x).atan2(-to_player.y));
+                }
+                if self.cooldown_tics == 0 && self.can_see(level, player_pos) {
+                    if let Some(attack) = self.attack(player_pos, distance) {
+                        self.state = MonsterState::Attack;
+                        self.state_tics = ATTACK_TICS;
+                        self.cooldown_tics = self.info.refire_tics;
+                        return Some(attack);
+                    }
+                }
+                if distance > self.radius + from_wad_height(CLOSEST_APPROACH) {
+                    self.walk(level, Pnt2f::new(player_pos.x, player_pos.z));
+                }
+                None
+            }
+        }
+    }
+
+    fn eye(&self) -> Pnt3f {
+        self.position + Vec3f::new(0.0, self.height() * EYE_HEIGHT_FRACTION, 0.0)
+    }
+
+    fn can_see(&self, level: &Level, target: Pnt3f) -> bool {
+        let eye = self.eye();
+        let (from, to) = (Pnt2f::new(eye.x, eye.z), Pnt2f::new(target.x, target.z));
+        if !level.reject_empty() {
+            if let (Some(from_sector), Some(to_sector)) =
+                (level.sector_id_at(from), level.sector_id_at(to))
+            {
+                if level.reject_quick(from_sector, to_sector) {
+                    return false;
+                }
+            }
+        }
+        level.check_sight(from, eye.y, to, target.y)
+    }
+
+    /// Whether an idle monster spots the player: they must be in front of it (or right next to
+    /// it) and in sight.
+    fn notices(&self, level: &Level, target: Pnt3f) -> bool {
+        let to_target = vec2(target.x - self.position.x, target.z - self.position.z);
+        let facing = vec2(-self.yaw.0.sin(), -self.yaw.0.cos());
+        let in_front =
+            to_target.dot(facing) >= 0.0 || to_target.magnitude() <= from_wad_height(MELEE_RANGE);
+        in_front && self.can_see(level, target)
+    }
+
+    /// Whether a noise made this tic (see `Level::make_noise`) carries to the monster's sector.
+    fn hears(&self, level: &Level) -> bool {
+        match (
+            level.noise(),
+            level.sector_id_at(Pnt2f::new(self.position.x, self.position.z)),
+        ) {
+            (Some(noise), Some(sector_id)) => level.sound_reaches(noise, sector_id, HEARING_HOPS),
+            _ => false,
+        }
+    }
+
+    fn attack(&mut self, target: Pnt3f, distance: f32) -> Option<MonsterAttack> {
+        match self.info.attack {
+            AttackKind::Hitscan { bullets } => {
+                Some(MonsterAttack::Damage(f32::from(bullets) * BULLET_DAMAGE))
+            }
+            AttackKind::Melee { damage } => {
+                if distance <= self.radius + from_wad_height(MELEE_RANGE) {
+                    Some(MonsterAttack::Damage(f32::from(damage)))
+                } else {
+                    None
+                }
+            }
+            AttackKind::Missile { speed, damage } => {
+                let eye = self.eye();
+                let direction = (target - eye).normalize_or_zero();
+                Some(MonsterAttack::Missile(Projectile {
+                    position: eye,
+                    velocity: direction * from_wad_height(speed * TIC_RATE),
+                    gravity: 0.0,
+                    blast_radius: from_wad_height(MISSILE_BLAST_RADIUS),
+                    blast_damage: f32::from(damage),
+                }))
+            }
+        }
+    }
+
+    /// Takes a step towards `target`, steering around any walls in the way.
+    fn walk(&mut self, level: &Level, target: Pnt2f) {
+        let from = Pnt2f::new(self.position.x, self.position.z);
+        let step = self
+            .pathfinder
+            .move_toward(from, target, self.radius, level);
+        if step.magnitude2() == 0.0 {
+            return;
+        }
+        let to = from + step;
+        self.position = Pnt3f::new(to.x, level.floor_height_at(to), to.y);
+    }
+
+    /// Lets a current the monster stands in carry it, unless a wall or a step is in the way.
+    /// Monsters have no momentum, so they move at a steady speed in proportion to the push.
+    pub(crate) fn drift(&mut self, level: &Level) {
+        let from = Pnt2f::new(self.position.x, self.position.z);
+        let push = match level.sector_special_at(from) {
+            Some(current @ SectorSpecial::Current { .. }) => current.push(true),
+            _ => return,
+        };
+        let to = from + push * (CURRENT_CARRY_SECONDS / f32::from(TIC_RATE));
+        let max_step = from_wad_height(MAX_STEP_HEIGHT);
+        if (level.floor_height_at(to) - self.position.y).abs() > max_step
+            || level.blocks_move(from, to, self.radius, self.height(), max_step)
+        {
+            return;
+        }
+        self.position = Pnt3f::new(to.x, level.floor_height_at(to), to.y);
+    }
+
+    /// Xorshift, standing in for DOOM's `P_Random` table.
+    fn random_byte(&mut self) -> u16 {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        (self.random & 0xff) as u16
+    }
+}
+
+const TIC_RATE: i16 = 35;
+
+/// Monsters in a current drift at its push times this, in world units per second.
+const CURRENT_CARRY_SECONDS: f32 = 0.05;
+const LOOK_INTERVAL_TICS: u32 = 4;
+const REACTION_TICS: u32 = 8;
+const ATTACK_TICS: u32 = 10;
+const PAIN_TICS: u32 = 6;
+
+/// Each of a former human's bullets deals 3-15 damage; this is the average.
+const BULLET_DAMAGE: f32 = 9.0;
+
+/// Imp fireballs and the like hurt what they hit directly; a small blast stands in for that.
+const MISSILE_BLAST_RADIUS: i16 = 32;
+
+/// In map units. Monsters stop walking this far in front of the player.
+const MELEE_RANGE: i16 = 64;
+const CLOSEST_APPROACH: i16 = 32;
+
+const EYE_HEIGHT_FRACTION: f32 = 0.75;
+
+/// How many sector boundaries a noise carries across to wake idle monsters.
+const HEARING_HOPS: u8 = 2;
+
+#[cfg(test)]
+mod test {
+    use super::{Monster, MonsterState};
+    use math::{Pnt3f, Rad};
+
+    #[test]
+    fn test_damage_kills_and_wakes() {
+        let mut monster = Monster::new(3004, Pnt3f::new(0.0, 0.0, 0.0), Rad(0.0), 0.2, false);
+        assert_eq!(monster.state(), MonsterState::Idle);
+        monster.damage(5.0);
+        assert!(monster.state() != MonsterState::Idle);
+        monster.damage(100.0);
+        assert!(monster.is_dead());
+        monster.damage(100.0);
+        assert_eq!(monster.state(), MonsterState::Dead);
+    }
+}
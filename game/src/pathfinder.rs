@@ -0,0 +1,76 @@
+use super::level::Level;
+use math::prelude::*;
+use math::{vec2, Pnt2f, Vec2f};
+use std::cmp::Ordering;
+use wad::util::from_wad_height;
+
+/// Steers a monster around walls on its way to a target, like DOOM's `P_NewChaseDir`: it heads
+/// straight for the target when it can, and otherwise tries the eight compass directions, the
+/// ones closest to the target's direction first.
+///
+/// Like DOOM this is greedy rather than a search over the whole map, so a monster can still be
+/// stuck behind a wall between it and the target; it does slide along walls and round corners.
+#[derive(Copy, Clone, Debug)]
+pub struct Pathfinder {
+    step: f32,
+    height: f32,
+}
+
+impl Pathfinder {
+    /// A pathfinder for a monster moving `step` world units per move which is `height` tall.
+    pub fn new(step: f32, height: f32) -> Self {
+        Pathfinder { step, height }
+    }
+
+    /// Returns the displacement of the monster's next step from `from` towards `target`, or zero
+    /// if every direction is blocked by walls (see `Level::blocks_move`) or ledges it can't step
+    /// down from.
+    pub fn move_toward(self, from: Pnt2f, target: Pnt2f, radius: f32, level: &Level) -> Vec2f {
+        let to_target = target - from;
+        let distance = to_target.magnitude();
+        if distance == 0.0 {
+            return Vec2f::zero();
+        }
+        let direction = to_target / distance;
+        let step = self.step.min(distance);
+
+        let mut directions = COMPASS_DIRECTIONS
+            .iter()
+            .map(|&(x, y)| vec2(x, y).normalize())
+            .collect::<Vec<_>>();
+        directions.sort_by(|a, b| {
+            direction
+                .dot(*b)
+                .partial_cmp(&direction.dot(*a))
+                .unwrap_or(Ordering::Equal)
+        });
+
+        let max_step = from_wad_height(MAX_STEP_HEIGHT);
+        let floor = level.floor_height_at(from);
+        Some(direction)
+            .into_iter()
+            .chain(directions)
+            .map(|direction| direction * step)
+            .find(|&displacement| {
+                let to = from + displacement;
+                (level.floor_height_at(to) - floor).abs() <= max_step
+                    && !level.blocks_move(from, to, radius, self.height, max_step)
+            })
+            .unwrap_or_else(Vec2f::zero)
+    }
+}
+
+/// The directions DOOM's monsters walk in: east, north-east, north, and so on.
+const COMPASS_DIRECTIONS: [(f32, f32); 8] = [
+    (1.0, 0.0),
+    (1.0, 1.0),
+    (0.0, 1.0),
+    (-1.0, 1.0),
+    (-1.0, 0.0),
+    (-1.0, -1.0),
+    (0.0, -1.0),
+    (1.0, -1.0),
+];
+
+/// In map units: the highest step a monster can climb or drop down.
+pub(crate) const MAX_STEP_HEIGHT: i16 = 24;
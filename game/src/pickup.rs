@@ -0,0 +1,132 @@
+use super::weapon::{Ammo, WeaponType};
+use math::Pnt3f;
+use wad::types::ThingType;
+use wad::{KeyType, Lock};
+
+/// What picking up an item gives the player.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PickupEffect {
+    /// Heals by `amount`, up to `max` (bonuses and spheres go over the usual 100).
+    Health {
+        amount: f32,
+        max: f32,
+    },
+    Ammo {
+        ammo: Ammo,
+        amount: u32,
+    },
+    /// A weapon, which comes loaded with some ammo.
+    Weapon {
+        weapon: WeaponType,
+        ammo: Ammo,
+        amount: u32,
+    },
+    Key(KeyType),
+}
+
+impl PickupEffect {
+    /// The effect of a pickup thing, or `None` for the ones which aren't implemented yet (armor,
+    /// power-ups and the like).
+    pub fn for_thing(thing_type: ThingType) -> Option<Self> {
+        if let Some(key) = KeyType::from_thing(thing_type) {
+            return Some(PickupEffect::Key(key));
+        }
+        let health = |amount, max| PickupEffect::Health { amount, max };
+        let ammo = |ammo, amount| PickupEffect::Ammo { ammo, amount };
+        let weapon = |weapon, ammo, amount| PickupEffect::Weapon {
+            weapon,
+            ammo,
+            amount,
+        };
+        Some(match thing_type {
+            2014 => health(1.0, 200.0),
+            2011 => health(10.0, 100.0),
+            2012 => health(25.0, 100.0),
+            2013 => health(100.0, 200.0),
+            2007 => ammo(Ammo::Bullet, 10),
+            2048 => ammo(Ammo::Bullet, 50),
+            2008 => ammo(Ammo::Shell, 4),
+            2049 => ammo(Ammo::Shell, 20),
+            2010 => ammo(Ammo::Rocket, 1),
+            2046 => ammo(Ammo::Rocket, 5),
+            2047 => ammo(Ammo::Cell, 20),
+            17 => ammo(Ammo::Cell, 100),
+            2001 => weapon(WeaponType::Shotgun, Ammo::Shell, 8),
+            2002 => weapon(WeaponType::Chaingun, Ammo::Bullet, 20),
+            2003 => weapon(WeaponType::RocketLauncher, Ammo::Rocket, 2),
+            2004 => weapon(WeaponType::PlasmaRifle, Ammo::Cell, 40),
+            2006 => weapon(WeaponType::Bfg9000, Ammo::Cell, 40),
+            _ => return None,
+        })
+    }
+}
+
+/// The keys the player is carrying.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyInventory(u8);
+
+impl KeyInventory {
+    pub fn contains(self, key: KeyType) -> bool {
+        self.0 & Self::bit(key) != 0
+    }
+
+    /// Adds a key, returning whether it's new.
+    pub fn insert(&mut self, key: KeyType) -> bool {
+        let had = self.contains(key);
+        self.0 |= Self::bit(key);
+        !had
+    }
+
+    pub fn opens(self, lock: Lock) -> bool {
+        lock.is_opened_by(|key| self.contains(key))
+    }
+
+    fn bit(key: KeyType) -> u8 {
+        1 << key as u8
+    }
+}
+
+/// An item lying in the level, until the player walks over it.
+pub struct Pickup {
+    thing_type: ThingType,
+    position: Pnt3f,
+    radius: f32,
+    active: bool,
+}
+
+impl Pickup {
+    pub fn new(thing_type: ThingType, position: Pnt3f, radius: f32) -> Self {
+        Pickup {
+            thing_type,
+            position,
+            radius,
+            active: true,
+        }
+    }
+
+    pub fn thing_type(&self) -> ThingType {
+        self.thing_type
+    }
+
+    pub fn effect(&self) -> Option<PickupEffect> {
+        PickupEffect::for_thing(self.thing_type)
+    }
+
+    /// The center of the item's base.
+    pub fn position(&self) -> Pnt3f {
+        self.position
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// Whether the item is still there (and drawn), i.e. hasn't been picked up.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub(crate) fn deactivate(&mut self) {
+        self.active = false;
+    }
+}
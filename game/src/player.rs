@@ -1,12 +1,16 @@
 use super::level::{Level, PlayerAction};
+use super::pickup::{KeyInventory, PickupEffect};
+use super::weapon::{Ammo, Weapon, WeaponEffect, WeaponType};
 use engine::{
     Analog2d, DependenciesFrom, Entities, EntityId, Gesture, InfallibleSystem, Input, MouseButton,
-    Projection, Projections, RenderPipeline, Scancode, Tick, Transforms, Window,
+    Projection, Projections, RenderPipeline, Scancode, TextId, TextRenderer, Tick, Transforms,
+    Window,
 };
-use log::error;
+use log::{debug, error, info, warn};
 use math::prelude::*;
-use math::{vec3, Deg, Euler, Pnt3f, Quat, Rad, Sphere, Trans3, Vec3f};
+use math::{vec3, Deg, Pnt2f, Pnt3f, Quat, Rad, Sphere, Trans3, Vec2f, Vec3f};
 use std::f32::consts::FRAC_PI_2;
+use wad::KeyType;
 
 pub struct Bindings {
     pub movement: Analog2d,
@@ -16,14 +20,24 @@ pub struct Bindings {
     pub clip: Gesture,
     pub push: Gesture,
     pub shoot: Gesture,
+    /// Gestures selecting each weapon, in `WeaponType::ALL` order.
+    pub weapons: Vec<Gesture>,
+    pub next_weapon: Gesture,
+    pub previous_weapon: Gesture,
 }
 
 impl Default for Bindings {
     fn default() -> Bindings {
         Bindings {
             movement: Analog2d::Gestures {
-                x_positive: Gesture::KeyHold(Scancode::D),
-                x_negative: Gesture::KeyHold(Scancode::A),
+                x_positive: Gesture::AnyOf(vec![
+                    Gesture::KeyHold(Scancode::D),
+                    Gesture::KeyHold(Scancode::Period),
+                ]),
+                x_negative: Gesture::AnyOf(vec![
+                    Gesture::KeyHold(Scancode::A),
+                    Gesture::KeyHold(Scancode::Comma),
+                ]),
                 y_positive: Gesture::KeyHold(Scancode::S),
                 y_negative: Gesture::KeyHold(Scancode::W),
                 step: 1.0,
@@ -43,15 +57,66 @@ impl Default for Bindings {
                 ],
             },
             jump: Gesture::KeyHold(Scancode::Space),
-            push: Gesture::KeyTrigger(Scancode::E),
+            push: Gesture::AnyOf(vec![
+                Gesture::KeyTrigger(Scancode::E),
+                Gesture::ButtonTrigger(MouseButton::Middle),
+            ]),
             shoot: Gesture::ButtonTrigger(MouseButton::Left),
             fly: Gesture::KeyTrigger(Scancode::F),
             clip: Gesture::KeyTrigger(Scancode::C),
+            weapons: [
+                Scancode::Key1,
+                Scancode::Key2,
+                Scancode::Key3,
+                Scancode::Key4,
+                Scancode::Key5,
+                Scancode::Key6,
+                Scancode::Key7,
+            ]
+            .iter()
+            .map(|&key| Gesture::KeyTrigger(key))
+            .collect(),
+            next_weapon: Gesture::WheelUp,
+            previous_weapon: Gesture::WheelDown,
         }
     }
 }
 
+/// How the view adapts to windows which are wider than DOOM's 4:3.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WidescreenMode {
+    /// Keep the 4:3 field of view and stretch it to fill the window.
+    Stretch,
+    /// Keep the 4:3 field of view and aspect ratio, with black bars at the sides.
+    Pillarbox,
+    /// Widen the horizontal field of view to fill the window.
+    Expand,
+}
+
+impl Default for WidescreenMode {
+    fn default() -> Self {
+        WidescreenMode::Expand
+    }
+}
+
+/// Which cheats have been used in the current game, so that a run can be marked as assisted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameFlags {
+    /// Set by `give` and by flying.
+    pub cheats_used: bool,
+    /// Set by turning off wall collisions.
+    pub noclip_used: bool,
+}
+
+pub const CLASSIC_ASPECT_RATIO: f32 = 4.0 / 3.0;
+
 pub struct Config {
+    pub widescreen_mode: WidescreenMode,
+    /// Whether looking up and down (with the mouse or arrow keys) tilts the view. Off, as in
+    /// vanilla DOOM, the view stays level and shots are aimed automatically.
+    pub freelook: bool,
+    pub fov: Deg<f32>,
+
     move_force: f32,
     spring_const_p: f32,
     spring_const_d: f32,
@@ -61,17 +126,32 @@ pub struct Config {
     ground_drag: f32,
     friction: f32,
 
-    fov: Deg<f32>,
     near: f32,
     far: f32,
     aspect_ratio_correction: f32,
 
     camera_height: f32,
+    max_health: f32,
+    /// How close (horizontally, beyond the item's own radius) the player must get to pick up items.
+    pickup_radius: f32,
+
+    /// How fast each point of damage taken knocks the player away from its source. DOOM
+    /// thrusts its 100 mass player by an eighth of a map unit per tic per point of damage.
+    knockback_per_damage: f32,
+
+    /// How far the view dips per point of damage taken, and the lowest it can dip.
+    damage_view_bob: f32,
+    max_view_bob: f32,
+    /// The fraction of the view bob which is recovered per second.
+    view_bob_recovery: f32,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
+            widescreen_mode: WidescreenMode::default(),
+            freelook: false,
+
             move_force: 60.0,
             spring_const_p: 200.0,
             spring_const_d: 22.4,
@@ -87,6 +167,14 @@ impl Default for Config {
             aspect_ratio_correction: 1.2,
 
             camera_height: 0.12,
+            max_health: 100.0,
+            pickup_radius: 0.16,
+
+            knockback_per_damage: 0.044,
+
+            damage_view_bob: 0.004,
+            max_view_bob: 0.06,
+            view_bob_recovery: 6.0,
         }
     }
 }
@@ -103,33 +191,313 @@ pub struct Dependencies<'context> {
     transforms: &'context mut Transforms,
     projections: &'context mut Projections,
     render: &'context mut RenderPipeline,
+    text: &'context mut TextRenderer,
 
     level: &'context mut Level,
 }
 
 pub struct Player {
     id: EntityId,
+    camera_id: EntityId,
     velocity: Vec3f,
     fly: bool,
     clip: bool,
     last_height_diff: f32,
+    health: f32,
+    view_height_delta: f32,
+    /// How far the view is tilted up (positive) or down, always zero without freelook.
+    look_pitch: f32,
+    current_weapon: WeaponType,
+    /// Which of `WeaponType::ALL` the player has.
+    weapons: [bool; 7],
+    ammo: [u32; 4],
+    keys: KeyInventory,
+    flags: GameFlags,
+    /// The level start the player last spawned at, to tell whether a reopened level still starts
+    /// in the same place.
+    start_pos: (Pnt3f, Rad<f32>),
+    /// The message flashed on screen, with how many seconds it has left.
+    message: Option<(TextId, f32)>,
 }
 
+/// DOOM starts (and restarts after dying) with a fist, a pistol and 50 bullets.
+const STARTING_WEAPONS: [bool; 7] = [true, true, false, false, false, false, false];
+const STARTING_AMMO: [u32; 4] = [50, 0, 0, 0];
+
+const MESSAGE_SECONDS: f32 = 4.0;
+const MESSAGE_PADDING: u32 = 6;
+/// Just below the help prompt.
+const MESSAGE_POSITION: [f32; 2] = [0.0, 0.06];
+
+/// Items can be picked up from this far below the player's feet, so they don't need to be
+/// level with the floor.
+const PICKUP_DROP: f32 = 0.24;
+
 impl Player {
-    fn reset(&mut self, transforms: &mut Transforms, level: &Level) {
+    pub fn ammo(&self, ammo: Ammo) -> u32 {
+        self.ammo[ammo as usize]
+    }
+
+    pub fn current_weapon(&self) -> WeaponType {
+        self.current_weapon
+    }
+
+    pub fn has_key(&self, key: KeyType) -> bool {
+        self.keys.contains(key)
+    }
+
+    pub fn has_weapon(&self, weapon: WeaponType) -> bool {
+        self.weapons[weapon as usize]
+    }
+
+    /// Changes weapon immediately, if the player has it; there's no raising or lowering animation
+    /// yet.
+    pub fn switch_weapon(&mut self, weapon: WeaponType) {
+        if !self.has_weapon(weapon) {
+            debug!("Don't have {:?} to switch to.", weapon);
+        } else if weapon != self.current_weapon {
+            info!("Switching weapon to {:?}.", weapon);
+            self.current_weapon = weapon;
+        }
+    }
+
+    /// The cheats used so far. They stay set for the rest of the game, even across levels.
+    pub fn flags(&self) -> GameFlags {
+        self.flags
+    }
+
+    /// Toggles wall collisions, returning whether they're now enabled.
+    pub fn toggle_clip(&mut self) -> bool {
+        self.clip = !self.clip;
+        if !self.clip {
+            self.mark_cheat(GameFlags {
+                noclip_used: true,
+                ..self.flags
+            });
+        }
+        self.clip
+    }
+
+    /// Restores the player's health and gives all weapons with full ammo.
+    pub fn give_all(&mut self, config: &Config) {
+        self.mark_cheat(GameFlags {
+            cheats_used: true,
+            ..self.flags
+        });
+        self.health = config.max_health;
+        self.weapons = [true; 7];
+        for &ammo in &Ammo::ALL {
+            self.ammo[ammo as usize] = ammo.max();
+        }
+    }
+
+    /// Picks up every active item within reach of `head`, removing from the level those which had
+    /// any effect; items which couldn't be used (at full health, say) are left lying around.
+    pub fn check_pickups(&mut self, level: &mut Level, head: &Sphere, config: &Config) {
+        let feet = head.center.y - head.radius;
+        let in_reach = level
+            .pickups()
+            .iter()
+            .enumerate()
+            .filter(|(_, pickup)| {
+                let position = pickup.position();
+                let distance =
+                    Vec2f::new(position.x - head.center.x, position.z - head.center.z).magnitude();
+                pickup.is_active()
+                    && distance <= pickup.radius() + config.pickup_radius
+                    && position.y >= feet - PICKUP_DROP
+                    && position.y <= head.center.y + head.radius
+            })
+            .filter_map(|(i_pickup, pickup)| Some((i_pickup, pickup.effect()?)))
+            .collect::<Vec<_>>();
+        for (i_pickup, effect) in in_reach {
+            if self.apply_pickup(effect) {
+                info!("Picked up {:?}.", effect);
+                level.take_pickup(i_pickup);
+            }
+        }
+    }
+
+    /// Applies the effect of an item, returning whether it did anything.
+    fn apply_pickup(&mut self, effect: PickupEffect) -> bool {
+        match effect {
+            PickupEffect::Health { amount, max } => {
+                if self.health >= max {
+                    return false;
+                }
+                self.health = (self.health + amount).min(max);
+                true
+            }
+            PickupEffect::Ammo { ammo, amount } => self.give_ammo(ammo, amount),
+            PickupEffect::Key(key) => self.keys.insert(key),
+            PickupEffect::Weapon {
+                weapon,
+                ammo,
+                amount,
+            } => {
+                let new_weapon = !self.has_weapon(weapon);
+                self.weapons[weapon as usize] = true;
+                if new_weapon {
+                    self.switch_weapon(weapon);
+                }
+                self.give_ammo(ammo, amount) || new_weapon
+            }
+        }
+    }
+
+    /// Adds ammo up to the maximum, returning whether there was room for any.
+    fn give_ammo(&mut self, ammo: Ammo, amount: u32) -> bool {
+        let count = &mut self.ammo[ammo as usize];
+        if *count >= ammo.max() {
+            return false;
+        }
+        *count = (*count + amount).min(ammo.max());
+        true
+    }
+
+    /// Fires the current weapon from `transform` if there's enough ammo for it, returning whether
+    /// it fired.
+    fn fire(&mut self, transform: &Trans3, level: &mut Level, config: &Config) -> bool {
+        let weapon = Weapon::new(self.current_weapon);
+        if let Some((ammo, per_shot)) = weapon.ammo() {
+            let count = &mut self.ammo[ammo as usize];
+            if *count < per_shot {
+                debug!("Out of {:?} for {:?}.", ammo, weapon.weapon_type());
+                return false;
+            }
+            *count -= per_shot;
+        }
+        level.make_noise(Pnt2f::new(transform.disp.x, transform.disp.z));
+        let aim = Trans3 {
+            disp: transform.disp + Vec3f::new(0.0, config.camera_height, 0.0),
+            rot: transform.rot * self.pitch_rotation(),
+            ..*transform
+        };
+        for effect in weapon.fire(&aim, level, !config.freelook) {
+            match effect {
+                WeaponEffect::Puff { point, .. } => debug!("Bullet puff at {:?}.", point),
+                WeaponEffect::Projectile(projectile) => level.spawn_projectile(projectile),
+                WeaponEffect::Hit { monster, damage } => level.damage_monster(monster, damage),
+            }
+        }
+        true
+    }
+
+    fn mark_cheat(&mut self, flags: GameFlags) {
+        if flags != self.flags {
+            warn!(
+                "Cheat used, this game no longer counts as unassisted: {:?}",
+                flags
+            );
+            self.flags = flags;
+        }
+    }
+
+    fn reset(&mut self, transforms: &mut Transforms, level: &Level, config: &Config) {
+        self.start_pos = level.start_pos();
+        let (position, yaw) = self.start_pos;
+        self.set_position(transforms, position, yaw);
+        self.health = config.max_health;
+        self.keys = KeyInventory::default();
+    }
+
+    /// Moves the player to `position`, facing `yaw`, looking straight ahead and standing still,
+    /// as when spawning or teleporting.
+    pub fn set_position(&mut self, transforms: &mut Transforms, position: Pnt3f, yaw: Rad<f32>) {
         let transform = transforms
             .get_local_mut(self.id)
-            .expect("player has no transform component: reset");
+            .expect("player has no transform component: set_position");
 
-        transform.rot = Quat::from(Euler {
-            x: Rad(1e-8),
-            y: level.start_yaw(),
-            z: Rad(0.0),
-        });
-        transform.disp = level.start_pos().to_vec();
+        transform.rot = Quat::from_angle_y(yaw);
+        transform.disp = position.to_vec();
 
         self.velocity = Vec3f::zero();
         self.last_height_diff = 0.0;
+        self.view_height_delta = 0.0;
+        self.look_pitch = 0.0;
+    }
+
+    /// The camera's tilt relative to the player's body, which only turns around the vertical.
+    fn pitch_rotation(&self) -> Quat {
+        Quat::from_angle_x(Rad(self.look_pitch))
+    }
+
+    /// Flashes a message on screen for a few seconds, replacing any current one.
+    fn show_message(&mut self, text: &mut TextRenderer, window: &Window, message: &str) {
+        if let Some((old_text, _)) = self.message.take() {
+            text.remove(old_text);
+        }
+        let position = Pnt2f::new(MESSAGE_POSITION[0], MESSAGE_POSITION[1]);
+        let text_id = text.insert(window, message, position, MESSAGE_PADDING);
+        self.message = Some((text_id, MESSAGE_SECONDS));
+    }
+
+    fn update_message(&mut self, text: &mut TextRenderer, delta_time: f32) {
+        if let Some((text_id, ref mut seconds_left)) = self.message {
+            *seconds_left -= delta_time;
+            if *seconds_left <= 0.0 {
+                text.remove(text_id);
+                self.message = None;
+            }
+        }
+    }
+
+    /// Pushes the player along `direction` (a unit vector in world X and Z), adding `force` (in
+    /// world units per second) to the velocity, which friction then slows as usual.
+    pub fn apply_knockback(&mut self, direction: Vec2f, force: f32) {
+        self.velocity += Vec3f::new(direction.x, 0.0, direction.y) * force;
+    }
+
+    /// Takes damage, dipping the view in proportion.
+    fn damage(&mut self, amount: f32, config: &Config) {
+        if amount <= 0.0 {
+            return;
+        }
+        self.health -= amount;
+        self.view_height_delta =
+            (self.view_height_delta - amount * config.damage_view_bob).max(-config.max_view_bob);
+    }
+
+    /// Moves the camera to `camera_height` above the head, offset by the current view bob, which
+    /// decays back to zero, and tilts it by the look pitch.
+    fn update_view_height(
+        &mut self,
+        delta_time: f32,
+        transforms: &mut Transforms,
+        config: &Config,
+    ) {
+        self.view_height_delta -=
+            self.view_height_delta * (config.view_bob_recovery * delta_time).min(1.0);
+        let pitch_rotation = self.pitch_rotation();
+        let camera = transforms
+            .get_local_mut(self.camera_id)
+            .expect("player camera has no transform component");
+        camera.disp.y = config.camera_height + self.view_height_delta;
+        camera.rot = pitch_rotation;
+    }
+
+    fn poll_weapon_switch(&mut self, input: &Input, bindings: &Bindings) {
+        let selected = WeaponType::ALL
+            .iter()
+            .zip(&bindings.weapons)
+            .find(|&(_, gesture)| input.poll_gesture(gesture))
+            .map(|(&weapon, _)| weapon);
+        if let Some(weapon) = selected {
+            self.switch_weapon(weapon);
+        } else if input.poll_gesture(&bindings.next_weapon) {
+            self.cycle_weapon(WeaponType::next);
+        } else if input.poll_gesture(&bindings.previous_weapon) {
+            self.cycle_weapon(WeaponType::previous);
+        }
+    }
+
+    /// Switches to the next weapon the player has in the order given by `step`.
+    fn cycle_weapon(&mut self, step: fn(WeaponType) -> WeaponType) {
+        let mut weapon = step(self.current_weapon);
+        while !self.has_weapon(weapon) {
+            weapon = step(weapon);
+        }
+        self.switch_weapon(weapon);
     }
 
     fn head(&self, config: &Config, transform: &Trans3) -> Sphere {
@@ -170,14 +538,11 @@ impl Player {
         head.center += self.velocity * delta_time;
 
         if !self.fly {
-            let height = 2000.0;
-            let probe = Sphere {
-                center: head.center + Vec3f::new(0.0, height / 2.0, 0.0),
-                ..*head
-            };
-            let height = match level.volume().sweep_sphere(probe, vec3(0.0, -height, 0.0)) {
-                Some(contact) => head.center[1] + height * (0.5 - contact.time),
-                None => old_height,
+            let floor = level.floor_height_at(Pnt2f::new(head.center.x, head.center.z));
+            let height = if floor.is_finite() {
+                floor + head.radius
+            } else {
+                old_height
             };
 
             if head.center[1] <= height {
@@ -202,23 +567,21 @@ impl Player {
         let look = input.poll_analog2d(&bindings.look);
         let jump = input.poll_gesture(&bindings.jump);
 
-        // Compute the maximum pitch rotation we're allowed (since we don't want to look
-        // upside-down!).
-        let current_pitch = 2.0 * f32::atan(transform.rot.v.x / transform.rot.s);
-        let clamped_pitch_by = clamp(
-            -look[1],
-            (
-                1e-2 - FRAC_PI_2 - current_pitch,
-                FRAC_PI_2 - 1e-2 - current_pitch,
-            ),
-        );
-        transform.rot = Quat::from_angle_y(Rad(-look.x))
-            * transform.rot
-            * Quat::from_angle_x(Rad(clamped_pitch_by));
+        // Without freelook, vertical look input is discarded. Either way, the pitch is kept
+        // short of straight up or down, so the view never turns upside-down.
+        if config.freelook {
+            self.look_pitch = clamp(
+                self.look_pitch - look[1],
+                (1e-2 - FRAC_PI_2, FRAC_PI_2 - 1e-2),
+            );
+        } else {
+            self.look_pitch = 0.0;
+        }
+        transform.rot = Quat::from_angle_y(Rad(-look.x)) * transform.rot;
 
         if self.fly {
             let up = if jump { 0.5 } else { 0.0 };
-            transform.rot.rotate_vector(
+            (transform.rot * self.pitch_rotation()).rotate_vector(
                 vec3(movement[0], up, movement[1]).normalize_or_zero() * config.move_force,
             )
         } else {
@@ -273,6 +636,23 @@ impl Player {
             config,
             bindings,
         );
+        // Less grip means less push as well as less friction, so the top speed stays the same
+        // on ice but takes longer to reach or lose.
+        let grip = if self.fly || normal.is_none() {
+            1.0
+        } else {
+            level.friction_at(Pnt2f::new(head.center.x, head.center.z))
+        };
+        force[0] *= grip;
+        force[2] *= grip;
+        if !self.fly {
+            let pos = Pnt2f::new(head.center.x, head.center.z);
+            if let Some(special) = level.sector_special_at(pos) {
+                let push = special.push(normal.is_some());
+                force[0] += push.x;
+                force[2] += push.y;
+            }
+        }
         let speed = self.velocity.magnitude();
         if speed > 0.0 {
             let mut slowdown = if self.fly {
@@ -281,7 +661,7 @@ impl Player {
                 let tangential = self.velocity - normal * self.velocity.dot(normal);
                 let speed = tangential.magnitude();
                 if speed > 0.0 {
-                    -tangential * (config.friction / speed + config.ground_drag * speed)
+                    -tangential * grip * (config.friction / speed + config.ground_drag * speed)
                 } else {
                     Vec3f::zero()
                 }
@@ -337,7 +717,12 @@ impl<'context> InfallibleSystem<'context> for Player {
             camera_entity,
             Projection {
                 fov: deps.config.fov.into(),
-                aspect_ratio: deps.window.aspect_ratio() * deps.config.aspect_ratio_correction,
+                aspect_ratio: match deps.config.widescreen_mode {
+                    WidescreenMode::Stretch => CLASSIC_ASPECT_RATIO,
+                    WidescreenMode::Pillarbox | WidescreenMode::Expand => {
+                        deps.window.aspect_ratio()
+                    }
+                } * deps.config.aspect_ratio_correction,
                 near: deps.config.near,
                 far: deps.config.far,
             },
@@ -346,19 +731,34 @@ impl<'context> InfallibleSystem<'context> for Player {
 
         let mut player = Player {
             id: player_entity,
+            camera_id: camera_entity,
             velocity: Vec3f::zero(),
             fly: false,
             clip: true,
             last_height_diff: 0.0,
+            health: deps.config.max_health,
+            view_height_delta: 0.0,
+            look_pitch: 0.0,
+            current_weapon: WeaponType::default(),
+            weapons: STARTING_WEAPONS,
+            ammo: STARTING_AMMO,
+            keys: KeyInventory::default(),
+            flags: GameFlags::default(),
+            start_pos: deps.level.start_pos(),
+            message: None,
         };
 
-        player.reset(deps.transforms, deps.level);
+        player.reset(deps.transforms, deps.level, deps.config);
         player
     }
 
     fn update(&mut self, deps: Dependencies) {
         if deps.level.level_changed() {
-            self.reset(deps.transforms, deps.level);
+            if deps.level.level_reopened() && deps.level.start_pos() == self.start_pos {
+                info!("Level reopened with the same start, keeping the player where they are.");
+            } else {
+                self.reset(deps.transforms, deps.level, deps.config);
+            }
         }
 
         let delta_time = deps.tick.timestep();
@@ -369,12 +769,20 @@ impl<'context> InfallibleSystem<'context> for Player {
 
         if deps.input.poll_gesture(&deps.bindings.fly) {
             self.fly = !self.fly;
+            if self.fly {
+                self.mark_cheat(GameFlags {
+                    cheats_used: true,
+                    ..self.flags
+                });
+            }
         }
 
         if deps.input.poll_gesture(&deps.bindings.clip) {
-            self.clip = !self.clip;
+            self.toggle_clip();
         }
 
+        self.poll_weapon_switch(deps.input, deps.bindings);
+
         let mut head = self.head(deps.config, transform);
         let force = self.force(
             &head,
@@ -393,21 +801,53 @@ impl<'context> InfallibleSystem<'context> for Player {
 
         transform.disp = head.center.to_vec();
         self.velocity += force * delta_time;
-
-        deps.level.poll_triggers(
-            transform,
-            self.velocity * delta_time,
-            if deps.input.poll_gesture(&deps.bindings.push) {
-                Some(PlayerAction::Push)
-            } else if deps.input.poll_gesture(&deps.bindings.shoot) {
-                Some(PlayerAction::Shoot)
-            } else {
-                None
-            },
-        );
+        self.damage(deps.level.crush_damage(&head, delta_time), deps.config);
+        self.damage(deps.level.explosion_damage(&head), deps.config);
+        self.damage(deps.level.monster_damage(), deps.config);
+        let knockback = deps.level.damage_knockback(&head);
+        let amount = knockback.magnitude();
+        if amount > 0.0 {
+            self.apply_knockback(
+                knockback / amount,
+                amount * deps.config.knockback_per_damage,
+            );
+        }
+        deps.level.set_target(head);
+        self.check_pickups(deps.level, &head, deps.config);
+        deps.level
+            .mark_visited(Pnt2f::new(transform.disp.x, transform.disp.z));
+
+        let action = if deps.input.poll_gesture(&deps.bindings.push) {
+            Some(PlayerAction::Push)
+        } else if deps.input.poll_gesture(&deps.bindings.shoot)
+            && self.fire(transform, deps.level, deps.config)
+        {
+            Some(PlayerAction::Shoot)
+        } else {
+            None
+        };
+        let locked_out =
+            deps.level
+                .poll_triggers(transform, self.velocity * delta_time, action, self.keys);
+        if let Some(lock) = locked_out {
+            self.show_message(deps.text, deps.window, &lock.message());
+        }
+        self.update_message(deps.text, delta_time);
+
+        if self.health <= 0.0 {
+            info!("Player died, respawning.");
+            self.weapons = STARTING_WEAPONS;
+            self.ammo = STARTING_AMMO;
+            self.current_weapon = WeaponType::default();
+            self.reset(deps.transforms, deps.level, deps.config);
+        }
+        self.update_view_height(delta_time, deps.transforms, deps.config);
     }
 
     fn teardown(&mut self, deps: Dependencies) {
+        if let Some((text_id, _)) = self.message.take() {
+            deps.text.remove(text_id);
+        }
         deps.entities.remove(self.id);
     }
 }
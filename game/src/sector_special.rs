@@ -0,0 +1,81 @@
+use math::{vec2, Vec2f};
+use wad::types::SectorType;
+
+/// A sector type which pushes things standing (or, for wind, flying) in the sector.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SectorSpecial {
+    /// Pushes the player whether on the ground or in the air. `speed` is an acceleration, in
+    /// world units per second squared, and `direction` is a unit vector in world (X, Z).
+    Wind { direction: Vec2f, speed: f32 },
+    /// Like `Wind`, but only pushes what's on the floor, monsters included.
+    Current { direction: Vec2f, speed: f32 },
+}
+
+impl SectorSpecial {
+    /// Decodes sector types 40 to 51: four each of light wind, strong wind and current, blowing
+    /// north, east, south and west in turn.
+    pub fn from_sector_type(sector_type: SectorType) -> Option<Self> {
+        if sector_type < FIRST_PUSH_TYPE || sector_type >= FIRST_PUSH_TYPE + 12 {
+            return None;
+        }
+        let index = sector_type - FIRST_PUSH_TYPE;
+        // Map north (+y) is world -X and map east (+x) is world -Z.
+        let direction = match index % 4 {
+            0 => vec2(-1.0, 0.0),
+            1 => vec2(0.0, -1.0),
+            2 => vec2(1.0, 0.0),
+            _ => vec2(0.0, 1.0),
+        };
+        Some(match index / 4 {
+            0 => SectorSpecial::Wind {
+                direction,
+                speed: LIGHT_WIND_SPEED,
+            },
+            1 => SectorSpecial::Wind {
+                direction,
+                speed: STRONG_WIND_SPEED,
+            },
+            _ => SectorSpecial::Current {
+                direction,
+                speed: CURRENT_SPEED,
+            },
+        })
+    }
+
+    /// The push's acceleration on something in the sector, which is zero for currents unless
+    /// it's on the floor.
+    pub fn push(self, on_floor: bool) -> Vec2f {
+        match self {
+            SectorSpecial::Wind { direction, speed } => direction * speed,
+            SectorSpecial::Current { direction, speed } if on_floor => direction * speed,
+            SectorSpecial::Current { .. } => vec2(0.0, 0.0),
+        }
+    }
+}
+
+const FIRST_PUSH_TYPE: SectorType = 40;
+
+/// Light wind is weaker than the player's ground friction, so it only moves the player in the
+/// air or while already moving; strong winds and currents push the player along the ground.
+const LIGHT_WIND_SPEED: f32 = 20.0;
+const STRONG_WIND_SPEED: f32 = 45.0;
+const CURRENT_SPEED: f32 = 40.0;
+
+#[cfg(test)]
+mod test {
+    use super::SectorSpecial;
+    use math::vec2;
+
+    #[test]
+    fn test_from_sector_type() {
+        assert_eq!(SectorSpecial::from_sector_type(39), None);
+        assert_eq!(SectorSpecial::from_sector_type(52), None);
+        match SectorSpecial::from_sector_type(45) {
+            Some(SectorSpecial::Wind { direction, .. }) => assert_eq!(direction, vec2(0.0, -1.0)),
+            other => panic!("expected strong wind east, got {:?}", other),
+        }
+        let current = SectorSpecial::from_sector_type(51).expect("no current");
+        assert_eq!(current.push(false), vec2(0.0, 0.0));
+        assert!(current.push(true).y > 0.0);
+    }
+}
@@ -6,11 +6,13 @@ use glium::implement_vertex;
 #[derive(Copy, Clone)]
 pub struct StaticVertex {
     pub a_pos: [f32; 3],
+    pub a_normal: [f32; 3],
     pub a_atlas_uv: [f32; 2],
     pub a_tile_uv: [f32; 2],
     pub a_tile_size: [f32; 2],
     pub a_scroll_rate: f32,
     pub a_row_height: f32,
+    pub a_height_fraction: f32,
     pub a_num_frames: u8,
     pub a_light: u8,
 }
@@ -18,11 +20,13 @@ pub struct StaticVertex {
 implement_vertex! {
     StaticVertex,
     a_pos,
+    a_normal,
     a_atlas_uv,
     a_tile_uv,
     a_tile_size,
     a_scroll_rate,
     a_row_height,
+    a_height_fraction,
     a_num_frames,
     a_light,
 }
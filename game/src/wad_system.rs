@@ -1,30 +1,40 @@
 use super::errors::{Error, ErrorKind, Result};
 use engine::{DependenciesFrom, System};
-use failchain::{bail, ResultExt};
-use log::info;
+use failchain::{bail, ensure, ResultExt};
+use log::{error, info};
 use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use wad::types::ThingFlags;
 use wad::{
-    Archive, Level as WadLevel, LevelAnalysis, LevelVisitor, LevelWalker, Result as WadResult,
-    TextureDirectory, WadName,
+    Archive, ExitEffectDef, Level as WadLevel, LevelAnalysis, LevelLumps, LevelVisitor,
+    LevelWalker, Result as WadResult, TextureDirectory, WadName,
 };
 
 #[derive(Debug)]
 pub struct Config {
     pub wad_path: PathBuf,
+    pub patch_paths: Vec<PathBuf>,
     pub metadata_path: PathBuf,
     pub initial_level_index: usize,
+    pub thing_flags_mask: ThingFlags,
 }
 
 pub struct WadSystem {
     pub archive: Archive,
     pub textures: TextureDirectory,
-    pub level: WadLevel,
+    pub level: Rc<WadLevel>,
     pub analysis: LevelAnalysis,
 
     level_name: WadName,
+    thing_flags_mask: ThingFlags,
     current_level_index: usize,
     next_level_index: usize,
     level_changed: bool,
+    reopen_requested: bool,
+    level_reopened: bool,
+    loading: Option<LevelHandle>,
 }
 
 impl WadSystem {
@@ -36,14 +46,102 @@ impl WadSystem {
         self.current_level_index
     }
 
+    /// Finds the index of the level with a given (case-insensitive) name, like "E2M1" or "MAP07".
+    pub fn level_index_by_name(&self, name: &str) -> Option<usize> {
+        let name: WadName = name.parse().ok()?;
+        (0..self.archive.num_levels()).find(|&index| {
+            self.archive
+                .level_lump(index)
+                .ok()
+                .map_or(false, |lump| lump.name() == name)
+        })
+    }
+
     pub fn change_level(&mut self, new_level_index: usize) {
         self.next_level_index = new_level_index;
     }
 
+    /// Changes to the level an exit leads to: the episode's secret level for a secret exit, the
+    /// level after the one which leads to the secret level when leaving it, or otherwise the
+    /// next level in the archive. Finishing an episode (or DOOM II) starts it over, since
+    /// there's no finale screen.
+    pub fn exit_level(&mut self, exit: ExitEffectDef) {
+        let destination = exit_destination(self.level_name.as_ref().trim_end_matches('\0'), exit);
+        if let Some(ref name) = destination {
+            if name.ends_with("M1") || name == "MAP01" {
+                info!(
+                    "Finished the episode at {}, starting it over.",
+                    self.level_name
+                );
+            }
+        }
+        let destination = destination.and_then(|name| self.level_index_by_name(&name));
+        self.change_level(destination.unwrap_or(self.current_level_index + 1));
+    }
+
+    /// The name of the level being loaded in the background, if any. The current level stays
+    /// in place until it's ready.
+    pub fn loading_level_name(&self) -> Option<WadName> {
+        self.loading.as_ref().map(|handle| handle.name)
+    }
+
     pub fn level_changed(&self) -> bool {
         self.level_changed
     }
 
+    /// Requests that the WAD files be re-read from disk and the current level reloaded from
+    /// them on the next update, to pick up edits without restarting.
+    pub fn reopen_level(&mut self) {
+        self.reopen_requested = true;
+    }
+
+    /// Whether the level changed because it was reopened (see `reopen_level`), rather than
+    /// replaced by a different one. Implies `level_changed`.
+    pub fn level_reopened(&self) -> bool {
+        self.level_reopened
+    }
+
+    /// Re-reads the archive and textures from disk and reloads the current level, or the last
+    /// one if the edited WAD has fewer levels.
+    fn reopen(&mut self, config: &Config) -> Result<()> {
+        info!("Reopening WAD {:?}...", config.wad_path);
+        let archive = Archive::open_with_patches(
+            &config.wad_path,
+            &config.patch_paths,
+            &config.metadata_path,
+        )
+        .chain_err(|| {
+            ErrorKind::Context(format!("while reopening WAD with config {:#?}", config))
+        })?;
+        if archive.num_levels() == 0 {
+            bail!(ErrorKind::Context, "Reopened WAD has no levels.");
+        }
+        let textures = TextureDirectory::from_archive(&archive).chain_err(|| {
+            ErrorKind::Context("while reading textures of reopened WAD".to_owned())
+        })?;
+        let level_index = self.current_level_index.min(archive.num_levels() - 1);
+        let level_name = archive
+            .level_lump(level_index)
+            .chain_err(|| ErrorKind::Context(format!("while reopening level {}", level_index)))?
+            .name();
+        let level =
+            Rc::new(WadLevel::from_archive(&archive, level_index).chain_err(|| {
+                ErrorKind::Context(format!("while reopening level {}", level_name))
+            })?);
+        let analysis = LevelAnalysis::new(&level, archive.metadata());
+
+        self.archive = archive;
+        self.textures = textures;
+        self.level = level;
+        self.analysis = analysis;
+        self.level_name = level_name;
+        self.current_level_index = level_index;
+        self.next_level_index = level_index;
+        self.loading = None;
+        info!("Level {:?} ({}) reopened.", level_name, level_index);
+        Ok(())
+    }
+
     pub fn walk<V: LevelVisitor>(&self, visitor: &mut V) {
         LevelWalker::new(
             &self.level,
@@ -52,6 +150,7 @@ impl WadSystem {
             self.archive.metadata(),
             visitor,
         )
+        .thing_flags_mask(self.thing_flags_mask)
         .walk();
     }
 }
@@ -70,34 +169,34 @@ impl<'context> System<'context> for WadSystem {
     }
 
     fn create(deps: Dependencies) -> Result<Self> {
-        let (archive, textures, level_index, level_name) = (|| -> WadResult<_> {
-            let archive = Archive::open(&deps.config.wad_path, &deps.config.metadata_path)?;
+        let (archive, textures) = (|| -> WadResult<_> {
+            let archive = Archive::open_with_patches(
+                &deps.config.wad_path,
+                &deps.config.patch_paths,
+                &deps.config.metadata_path,
+            )?;
             let textures = TextureDirectory::from_archive(&archive)?;
-            let level_index = deps.config.initial_level_index;
-            let level_name = archive.level_lump(level_index)?.name();
-            Ok((archive, textures, level_index, level_name))
+            Ok((archive, textures))
         })()
-        .chain_err(|| ErrorKind(format!("WAD setup failed with: {:#?}", deps.config)))?;
-
-        if level_index >= archive.num_levels() {
-            bail!(
-                ErrorKind,
-                "Level index {} is not in valid range 0..{}, see --list-levels for level names.",
-                level_index,
-                archive.num_levels()
-            );
-        }
+        .chain_err(|| ErrorKind::Context(format!("WAD setup failed with: {:#?}", deps.config)))?;
+
+        let level_index = deps.config.initial_level_index;
+        check_level_index(level_index, archive.num_levels())?;
+        let level_name = archive
+            .level_lump(level_index)
+            .chain_err(|| ErrorKind::Context(format!("while opening level {}", level_index)))?
+            .name();
 
         info!(
             "Loading initial level {:?} ({})...",
             level_name, level_index
         );
-        let level = WadLevel::from_archive(&archive, level_index).chain_err(|| {
-            ErrorKind(format!(
+        let level = Rc::new(WadLevel::from_archive(&archive, level_index).chain_err(|| {
+            ErrorKind::Context(format!(
                 "when loading WAD level with config {:#?}",
                 deps.config
             ))
-        })?;
+        })?);
         info!("Analysing level...");
         let analysis = LevelAnalysis::new(&level, archive.metadata());
 
@@ -109,12 +208,29 @@ impl<'context> System<'context> for WadSystem {
             current_level_index: level_index,
             next_level_index: level_index,
             level_changed: false,
+            reopen_requested: false,
+            level_reopened: false,
+            loading: None,
             level_name,
+            thing_flags_mask: deps.config.thing_flags_mask,
         })
     }
 
-    fn update(&mut self, _deps: Dependencies) -> Result<()> {
+    fn update(&mut self, deps: Dependencies) -> Result<()> {
         self.level_changed = false;
+        self.level_reopened = false;
+
+        if self.reopen_requested {
+            self.reopen_requested = false;
+            match self.reopen(deps.config) {
+                Ok(()) => {
+                    self.level_changed = true;
+                    self.level_reopened = true;
+                    return Ok(());
+                }
+                Err(error) => error!("Failed to reopen level, keeping current: {}", error),
+            }
+        }
 
         if self.next_level_index != self.current_level_index {
             if self.next_level_index >= self.archive.num_levels() {
@@ -123,13 +239,17 @@ impl<'context> System<'context> for WadSystem {
                     self.next_level_index
                 );
                 self.next_level_index = self.current_level_index;
-            } else {
-                self.current_level_index = self.next_level_index;
-                self.level_name = self
+                self.loading = None;
+            } else if self
+                .loading
+                .as_ref()
+                .map_or(true, |handle| handle.index != self.next_level_index)
+            {
+                let level_name = self
                     .archive
                     .level_lump(self.next_level_index)
                     .chain_err(|| {
-                        ErrorKind(format!(
+                        ErrorKind::Context(format!(
                             "while accessing level name for next level request {}",
                             self.next_level_index
                         ))
@@ -137,21 +257,170 @@ impl<'context> System<'context> for WadSystem {
                     .name();
                 info!(
                     "Loading new level {:?} ({})...",
-                    self.level_name, self.next_level_index
+                    level_name, self.next_level_index
                 );
-                self.level = WadLevel::from_archive(&self.archive, self.current_level_index)
-                    .chain_err(|| {
-                        ErrorKind(format!(
-                            "while loading next level {} ({}) for next level request",
-                            self.level_name, self.next_level_index
-                        ))
-                    })?;
-                info!("Analysing new level...");
-                self.analysis = LevelAnalysis::new(&self.level, self.archive.metadata());
-                info!("Level replaced.");
-                self.level_changed = true;
+                self.loading = Some(LevelHandle::spawn(
+                    &self.archive,
+                    self.next_level_index,
+                    level_name,
+                )?);
+            }
+        }
+
+        if let Some(result) = self.loading.as_ref().and_then(LevelHandle::try_recv) {
+            let handle = self
+                .loading
+                .take()
+                .expect("finished loading without a handle");
+            match result {
+                Ok((level, analysis)) => {
+                    self.level = Rc::new(level);
+                    self.analysis = analysis;
+                    self.level_name = handle.name;
+                    self.current_level_index = handle.index;
+                    info!("Level replaced.");
+                    self.level_changed = true;
+                }
+                Err(error) => {
+                    error!("Failed to load level, keeping current: {}", error);
+                    self.next_level_index = self.current_level_index;
+                }
             }
         }
         Ok(())
     }
 }
+
+/// A level being read and analysed on a background thread, so that the window keeps drawing
+/// the current level meanwhile. Building its meshes needs the GL context, so `Level` does that
+/// on the main thread once `WadSystem` reports the change.
+struct LevelHandle {
+    index: usize,
+    name: WadName,
+    receiver: Receiver<WadResult<(WadLevel, LevelAnalysis)>>,
+}
+
+impl LevelHandle {
+    /// Starts loading level `index`. An `Archive` can't be shared between threads, so its
+    /// lumps are read here and only parsed and analysed on the loading thread.
+    fn spawn(archive: &Archive, index: usize, name: WadName) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let lumps = LevelLumps::read(archive, index)
+            .chain_err(|| ErrorKind::Context(format!("while reading level {}", name)))?;
+        let metadata = archive.shared_metadata();
+        thread::Builder::new()
+            .name("level_loader".to_owned())
+            .spawn(move || {
+                let result = WadLevel::from_lumps(&lumps).map(|level| {
+                    info!("Analysing new level...");
+                    let analysis = LevelAnalysis::new(&level, &metadata);
+                    (level, analysis)
+                });
+                // Nobody is waiting any more if another level was requested meanwhile.
+                let _ = sender.send(result);
+            })
+            .chain_err(|| ErrorKind::Context(format!("while starting to load level {}", name)))?;
+        Ok(LevelHandle {
+            index,
+            name,
+            receiver,
+        })
+    }
+
+    /// Returns the loaded level once the thread is done, without waiting for it.
+    fn try_recv(&self) -> Option<Result<(WadLevel, LevelAnalysis)>> {
+        let name = self.name;
+        match self.receiver.try_recv() {
+            Ok(result) => Some(
+                result.chain_err(|| ErrorKind::Context(format!("while loading level {}", name))),
+            ),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(ErrorKind::Context(format!(
+                "the thread loading level {} panicked",
+                name
+            ))
+            .into())),
+        }
+    }
+}
+
+/// Fails with `InvalidConfig` if the initial level set on the command line isn't in the WAD.
+fn check_level_index(level_index: usize, num_levels: usize) -> Result<()> {
+    ensure!(
+        level_index < num_levels,
+        ErrorKind::InvalidConfig,
+        "Level index {} is not in valid range 0..{}, see --list-levels for level names.",
+        level_index,
+        num_levels
+    );
+    Ok(())
+}
+
+/// The name of the level an exit from `level_name` leads to, following DOOM and DOOM II, or
+/// `None` if it's just the next level. The last level of an episode (ExM8, or MAP30) leads
+/// back to the episode's first, rather than on to the secret levels after it.
+fn exit_destination(level_name: &str, exit: ExitEffectDef) -> Option<String> {
+    if level_name.starts_with("MAP") {
+        let map: u32 = level_name[3..].parse().ok()?;
+        let destination = match (exit, map) {
+            (ExitEffectDef::Secret, 15) => 31,
+            (ExitEffectDef::Secret, 31) => 32,
+            (ExitEffectDef::Normal, 31) | (ExitEffectDef::Normal, 32) => 16,
+            (ExitEffectDef::Normal, 30) => 1,
+            _ => return None,
+        };
+        return Some(format!("MAP{:02}", destination));
+    }
+
+    let name = level_name.as_bytes();
+    if name.len() != 4 || name[0] != b'E' || name[2] != b'M' {
+        return None;
+    }
+    let (episode, map) = (name[1], name[3]);
+    let destination = match (exit, episode, map) {
+        (ExitEffectDef::Secret, _, _) => b'9',
+        (ExitEffectDef::Normal, b'1', b'9') => b'4',
+        (ExitEffectDef::Normal, b'2', b'9') => b'6',
+        (ExitEffectDef::Normal, b'3', b'9') => b'7',
+        (ExitEffectDef::Normal, b'4', b'9') => b'3',
+        (ExitEffectDef::Normal, _, b'8') => b'1',
+        _ => return None,
+    };
+    Some(format!("E{}M{}", episode as char, destination as char))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_level_index, exit_destination};
+    use crate::errors::ErrorKind;
+    use wad::ExitEffectDef::{Normal, Secret};
+
+    #[test]
+    fn test_check_level_index() {
+        assert!(check_level_index(0, 9).is_ok());
+        assert!(check_level_index(8, 9).is_ok());
+        for &(index, num_levels) in &[(9, 9), (40, 9), (0, 0)] {
+            match check_level_index(index, num_levels).map_err(|error| error.kind().clone()) {
+                Err(ErrorKind::InvalidConfig(_)) => {}
+                other => panic!("expected an invalid config error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_exit_destination() {
+        assert_eq!(exit_destination("E1M3", Secret), Some("E1M9".to_owned()));
+        assert_eq!(exit_destination("E1M9", Normal), Some("E1M4".to_owned()));
+        assert_eq!(exit_destination("E4M9", Normal), Some("E4M3".to_owned()));
+        assert_eq!(exit_destination("E2M2", Normal), None);
+        assert_eq!(exit_destination("E1M8", Normal), Some("E1M1".to_owned()));
+        assert_eq!(exit_destination("E3M8", Normal), Some("E3M1".to_owned()));
+        assert_eq!(exit_destination("MAP15", Secret), Some("MAP31".to_owned()));
+        assert_eq!(exit_destination("MAP31", Secret), Some("MAP32".to_owned()));
+        assert_eq!(exit_destination("MAP32", Normal), Some("MAP16".to_owned()));
+        assert_eq!(exit_destination("MAP07", Secret), None);
+        assert_eq!(exit_destination("MAP29", Normal), None);
+        assert_eq!(exit_destination("MAP30", Normal), Some("MAP01".to_owned()));
+        assert_eq!(exit_destination("START", Secret), None);
+    }
+}
@@ -0,0 +1,270 @@
+use super::level::Level;
+use math::prelude::*;
+use math::{Pnt2f, Pnt3f, Rad, Trans3, Vec3f};
+use wad::util::from_wad_height;
+
+/// The weapons the player can hold, in the order of their number keys.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WeaponType {
+    Fist,
+    Pistol,
+    Shotgun,
+    Chaingun,
+    RocketLauncher,
+    PlasmaRifle,
+    Bfg9000,
+}
+
+impl WeaponType {
+    pub const ALL: [WeaponType; 7] = [
+        WeaponType::Fist,
+        WeaponType::Pistol,
+        WeaponType::Shotgun,
+        WeaponType::Chaingun,
+        WeaponType::RocketLauncher,
+        WeaponType::PlasmaRifle,
+        WeaponType::Bfg9000,
+    ];
+
+    /// The weapon after this one, wrapping around to the fist.
+    pub fn next(self) -> WeaponType {
+        WeaponType::ALL[(self.index() + 1) % WeaponType::ALL.len()]
+    }
+
+    /// The weapon before this one, wrapping around to the BFG.
+    pub fn previous(self) -> WeaponType {
+        WeaponType::ALL[(self.index() + WeaponType::ALL.len() - 1) % WeaponType::ALL.len()]
+    }
+
+    fn index(self) -> usize {
+        WeaponType::ALL
+            .iter()
+            .position(|&weapon| weapon == self)
+            .expect("weapon missing from WeaponType::ALL")
+    }
+}
+
+impl Default for WeaponType {
+    fn default() -> Self {
+        WeaponType::Pistol
+    }
+}
+
+/// The kinds of ammo, each with its own count in the player's inventory.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Ammo {
+    Bullet,
+    Shell,
+    Rocket,
+    Cell,
+}
+
+impl Ammo {
+    pub const ALL: [Ammo; 4] = [Ammo::Bullet, Ammo::Shell, Ammo::Rocket, Ammo::Cell];
+
+    /// The most of this ammo the player can carry (without a backpack).
+    pub fn max(self) -> u32 {
+        match self {
+            Ammo::Bullet => 200,
+            Ammo::Shell => 50,
+            Ammo::Rocket => 50,
+            Ammo::Cell => 300,
+        }
+    }
+}
+
+/// Something which happened as a result of firing a weapon.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WeaponEffect {
+    /// A bullet hit a wall, floor or ceiling at `point`, facing `normal`.
+    Puff { point: Pnt3f, normal: Vec3f },
+    /// A projectile was launched, to be simulated by the level.
+    Projectile(Projectile),
+    /// A bullet or punch hit the monster with this index in `Level::monsters`.
+    Hit { monster: usize, damage: f32 },
+}
+
+/// A missile which flies until it hits the level geometry, then explodes.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Projectile {
+    pub position: Pnt3f,
+    pub velocity: Vec3f,
+    /// Downward acceleration; zero for DOOM's missiles, which fly in straight lines.
+    pub gravity: f32,
+    /// The explosion's radius and its damage at the center, falling off linearly to the edge.
+    pub blast_radius: f32,
+    pub blast_damage: f32,
+}
+
+/// An explosion which happened during the current tick.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Explosion {
+    pub origin: Pnt3f,
+    pub radius: f32,
+    pub damage: f32,
+}
+
+impl Explosion {
+    /// The damage taken by a sphere at `center` with `radius`, ignoring anything in between.
+    pub fn damage_at(&self, center: Pnt3f, radius: f32) -> f32 {
+        let distance = ((center - self.origin).magnitude() - radius).max(0.0);
+        if distance >= self.radius {
+            0.0
+        } else {
+            self.damage * (1.0 - distance / self.radius)
+        }
+    }
+}
+
+/// How a `WeaponType` fires.
+pub struct Weapon {
+    weapon_type: WeaponType,
+    range: f32,
+}
+
+impl Weapon {
+    pub fn new(weapon_type: WeaponType) -> Self {
+        let range = match weapon_type {
+            WeaponType::Fist => MELEE_RANGE,
+            _ => MISSILE_RANGE,
+        };
+        Weapon {
+            weapon_type,
+            range: from_wad_height(range),
+        }
+    }
+
+    pub fn weapon_type(&self) -> WeaponType {
+        self.weapon_type
+    }
+
+    /// The ammo used by the weapon and how much of it each shot takes, if any.
+    pub fn ammo(&self) -> Option<(Ammo, u32)> {
+        match self.weapon_type {
+            WeaponType::Fist => None,
+            WeaponType::Pistol | WeaponType::Chaingun => Some((Ammo::Bullet, 1)),
+            WeaponType::Shotgun => Some((Ammo::Shell, 1)),
+            WeaponType::RocketLauncher => Some((Ammo::Rocket, 1)),
+            WeaponType::PlasmaRifle => Some((Ammo::Cell, 1)),
+            WeaponType::Bfg9000 => Some((Ammo::Cell, 40)),
+        }
+    }
+
+    /// The damage dealt by one hitscan shot, all pellets included: DOOM's random damage rolls,
+    /// averaged.
+    pub fn damage(&self) -> f32 {
+        match self.weapon_type {
+            WeaponType::Shotgun => 7.0 * BULLET_DAMAGE,
+            _ => BULLET_DAMAGE,
+        }
+    }
+
+    /// Fires a perfectly accurate shot from `aim` (the shooter's eye, looking down negative Z),
+    /// returning what it hit.
+    ///
+    /// With `autoaim`, the shot's pitch is ignored and it is instead aimed up or down at any
+    /// monster in line, or slightly to either side, as in DOOM (see `Level::autoaim`).
+    ///
+    /// Plasma and BFG shots aren't simulated yet; they don't hit anything.
+    pub fn fire(&self, aim: &Trans3, level: &Level, autoaim: bool) -> Vec<WeaponEffect> {
+        let mut direction = aim.rot.rotate_vector(-Vec3f::unit_z());
+        if autoaim {
+            direction = self.autoaim(aim, direction, level);
+        }
+        match self.weapon_type {
+            WeaponType::Fist | WeaponType::Pistol | WeaponType::Shotgun | WeaponType::Chaingun => {}
+            WeaponType::RocketLauncher => {
+                return vec![WeaponEffect::Projectile(Projectile {
+                    position: Pnt3f::from_vec(aim.disp),
+                    velocity: direction * from_wad_height(ROCKET_SPEED),
+                    gravity: 0.0,
+                    blast_radius: from_wad_height(ROCKET_BLAST_RADIUS),
+                    blast_damage: f32::from(ROCKET_BLAST_RADIUS),
+                })];
+            }
+            WeaponType::PlasmaRifle | WeaponType::Bfg9000 => return Vec::new(),
+        }
+        let origin = Pnt3f::from_vec(aim.disp);
+        let wall_hit = level.raycast(origin, direction, self.range);
+        let monster_hit = level
+            .hit_monster(origin, direction, self.range)
+            .filter(|&(_, distance)| wall_hit.map_or(true, |hit| distance < hit.distance));
+        if let Some((monster, _)) = monster_hit {
+            return vec![WeaponEffect::Hit {
+                monster,
+                damage: self.damage(),
+            }];
+        }
+        wall_hit
+            .map(|hit| WeaponEffect::Puff {
+                point: hit.point,
+                normal: hit.normal,
+            })
+            .into_iter()
+            .collect()
+    }
+
+    /// The direction of an autoaimed shot from `aim`, initially along `direction`.
+    fn autoaim(&self, aim: &Trans3, direction: Vec3f, level: &Level) -> Vec3f {
+        let origin = Pnt2f::new(aim.disp.x, aim.disp.z);
+        let yaw = Rad((-direction.x).atan2(-direction.z));
+        let pitch = [Rad(0.0), AUTOAIM_SPREAD, -AUTOAIM_SPREAD]
+            .iter()
+            .filter_map(|&offset| {
+                level
+                    .autoaim(origin, aim.disp.y, yaw + offset, self.range)
+                    .map(|pitch| (offset, pitch))
+            })
+            .next();
+        let (offset, pitch) = pitch.unwrap_or((Rad(0.0), 0.0));
+        let yaw = yaw + offset;
+        Vec3f::new(
+            -yaw.0.sin() * pitch.cos(),
+            pitch.sin(),
+            -yaw.0.cos() * pitch.cos(),
+        )
+    }
+}
+
+/// When nothing is straight ahead, autoaim also looks this far to either side (DOOM's `1 << 26`
+/// angle units).
+const AUTOAIM_SPREAD: Rad<f32> = Rad(std::f32::consts::PI / 32.0);
+
+/// Range of hitscan weapons and punches, in map units.
+const MISSILE_RANGE: i16 = 2048;
+const MELEE_RANGE: i16 = 64;
+
+/// Bullets deal 5-15 damage and punches 2-20; both average about 10.
+const BULLET_DAMAGE: f32 = 10.0;
+
+/// Rockets fly at 20 map units per tic. Their blast deals 128 damage at the center, losing a
+/// point per map unit away (the same as DOOM's `P_RadiusAttack`).
+const ROCKET_SPEED: i16 = 20 * 35;
+const ROCKET_BLAST_RADIUS: i16 = 128;
+
+#[cfg(test)]
+mod test {
+    use super::{Explosion, WeaponType};
+    use math::Pnt3f;
+
+    #[test]
+    fn test_weapon_cycling_wraps_around() {
+        assert_eq!(WeaponType::Fist.next(), WeaponType::Pistol);
+        assert_eq!(WeaponType::Bfg9000.next(), WeaponType::Fist);
+        assert_eq!(WeaponType::Fist.previous(), WeaponType::Bfg9000);
+        assert_eq!(WeaponType::Shotgun.previous(), WeaponType::Pistol);
+    }
+
+    #[test]
+    fn test_explosion_damage_falls_off() {
+        let explosion = Explosion {
+            origin: Pnt3f::new(0.0, 0.0, 0.0),
+            radius: 1.28,
+            damage: 128.0,
+        };
+        let damage_at = |x, y| explosion.damage_at(Pnt3f::new(x, y, 0.0), 0.2);
+        assert!((damage_at(0.1, 0.0) - 128.0).abs() < 1e-3);
+        assert!((damage_at(0.84, 0.0) - 64.0).abs() < 1e-3);
+        assert!(damage_at(0.0, 2.0).abs() < 1e-3);
+    }
+}
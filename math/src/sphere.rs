@@ -13,6 +13,16 @@ impl Sphere {
         Self { center, radius }
     }
 
+    /// Returns the fraction of `displacement` at which a point moving from `start` enters the
+    /// sphere, or `None` if it misses it or starts inside it.
+    pub fn intersect_segment(&self, start: Pnt3f, displacement: Vec3f) -> Option<f32> {
+        if (start - self.center).magnitude2() <= self.radius * self.radius {
+            return None;
+        }
+        intersect_sphere_line(self.center, self.radius, start, start + displacement)
+            .filter(|&time| time >= 0.0 && time <= 1.0)
+    }
+
     pub fn sweep_triangle(
         &self,
         triangle: &[Pnt3f; 3],
@@ -1,5 +1,6 @@
+use engine::VSync;
 use failure::{bail, Error};
-use game::{self, Game, GameConfig};
+use game::{self, ClearColor, Game, GameConfig, RenderQuality, WidescreenMode};
 use log::{error, info};
 use math::DurationExt;
 use std::env;
@@ -7,7 +8,7 @@ use std::path::PathBuf;
 use std::process;
 use std::time::Instant;
 use structopt::StructOpt;
-use wad::Archive;
+use wad::{Archive, Difficulty};
 
 #[derive(StructOpt)]
 #[structopt(
@@ -25,6 +26,10 @@ struct App {
     /// Initial WAD file to use.
     iwad: PathBuf,
 
+    #[structopt(short = "p", long = "pwad", value_name = "FILE", parse(from_os_str))]
+    /// PWAD files to load on top of the IWAD; lumps in later ones take priority.
+    pwads: Vec<PathBuf>,
+
     #[structopt(
         short = "m",
         long = "metadata",
@@ -64,6 +69,93 @@ struct App {
     /// Horizontal field of view.
     fov: f32,
 
+    #[structopt(
+        short = "s",
+        long = "skill",
+        default_value = "4",
+        value_name = "1-5",
+        parse(try_from_str = "parse_skill")
+    )]
+    /// Skill level, which decides which things are spawned.
+    skill: Difficulty,
+
+    #[structopt(long = "thing-flags-mask", value_name = "MASK")]
+    /// Spawn the things whose flags intersect MASK instead of those for the skill level (e.g. 7
+    /// to spawn things from every skill level).
+    thing_flags_mask: Option<u16>,
+
+    #[structopt(long = "fps-limit", value_name = "FPS")]
+    /// Render at most this many frames per second (the game never renders more than 60). Rounded
+    /// down to 60 divided by a whole number, e.g. 50 renders 30 frames per second.
+    fps_limit: Option<u32>,
+
+    #[structopt(
+        long = "vsync",
+        default_value = "on",
+        value_name = "off|on|adaptive",
+        parse(try_from_str = "parse_vsync")
+    )]
+    /// Whether to wait for the display's vertical sync before showing each frame.
+    vsync: VSync,
+
+    #[structopt(
+        long = "widescreen",
+        default_value = "expand",
+        value_name = "stretch|pillarbox|expand",
+        parse(try_from_str = "parse_widescreen_mode")
+    )]
+    /// How to fill windows wider than 4:3: stretch the view, add black bars or widen the view.
+    widescreen_mode: WidescreenMode,
+
+    #[structopt(long = "freelook")]
+    /// Look up and down with the mouse, instead of DOOM's level view and automatic aiming.
+    freelook: bool,
+
+    #[structopt(
+        long = "msaa",
+        default_value = "1",
+        value_name = "1|2|4|8",
+        parse(try_from_str = "parse_msaa_samples")
+    )]
+    /// Samples per pixel for multisample anti-aliasing (1 disables it).
+    msaa_samples: u8,
+
+    #[structopt(long = "hot-shaders")]
+    /// Watch the shader directory and recompile shaders when they change.
+    hot_shaders: bool,
+
+    #[structopt(long = "light-gradient", default_value = "0", value_name = "0..1")]
+    /// How much darker than floors to light ceilings, fading along the walls between them.
+    light_gradient: f32,
+
+    #[structopt(long = "record-input", value_name = "FILE", parse(from_os_str))]
+    /// Record the keyboard and mouse state of every tic to a file, for replaying later.
+    record_input: Option<PathBuf>,
+
+    #[structopt(long = "replay-input", value_name = "FILE", parse(from_os_str))]
+    /// Replay input recorded with --record-input instead of using the keyboard and mouse; the
+    /// game quits when the recording ends.
+    replay_input: Option<PathBuf>,
+
+    #[structopt(
+        long = "clear-color",
+        default_value = "sky",
+        value_name = "sky|R,G,B",
+        parse(try_from_str = "parse_clear_color")
+    )]
+    /// Background colour behind the level: `sky` for the average colour of its sky texture, or
+    /// components from 0 to 1.
+    clear_color: ClearColor,
+
+    #[structopt(
+        long = "quality",
+        default_value = "high",
+        value_name = "low|medium|high",
+        parse(try_from_str = "parse_render_quality")
+    )]
+    /// Rendering quality: how many dynamic lights are shaded (none, up to 8 or up to 32).
+    rendering_quality: RenderQuality,
+
     #[structopt(subcommand)]
     command: Option<Command>,
 }
@@ -116,7 +208,7 @@ impl App {
                 );
             }
             Some(Command::ListLevelNames) => {
-                let wad = Archive::open(&self.iwad, &self.metadata)?;
+                let wad = Archive::open_with_patches(&self.iwad, &self.pwads, &self.metadata)?;
                 for i_level in 0..wad.num_levels() {
                     println!("{:3} {:8}", i_level, wad.level_lump(i_level)?.name());
                 }
@@ -128,14 +220,31 @@ impl App {
 
     /// Populate `GameConfig` fields from the parsed command-line arguments.
     fn into_config(self) -> GameConfig {
+        let skill = self.skill;
+        let thing_flags_mask = self
+            .thing_flags_mask
+            .unwrap_or_else(|| skill.thing_flags_mask());
         GameConfig {
             wad_file: self.iwad,
+            pwad_files: self.pwads,
             metadata_file: self.metadata,
             fov: self.fov,
             width: self.resolution.0,
             height: self.resolution.1,
             version: env!("CARGO_PKG_VERSION"),
             initial_level_index: self.level_index,
+            thing_flags_mask,
+            fps_limit: self.fps_limit,
+            vsync: self.vsync,
+            widescreen_mode: self.widescreen_mode,
+            freelook: self.freelook,
+            msaa_samples: self.msaa_samples,
+            hot_shaders: self.hot_shaders,
+            light_gradient: self.light_gradient,
+            record_input: self.record_input,
+            replay_input: self.replay_input,
+            clear_color: self.clear_color,
+            rendering_quality: self.rendering_quality,
         }
     }
 }
@@ -164,6 +273,80 @@ fn parse_resolution(size_str: &str) -> Result<(u32, u32), Error> {
     }
 }
 
+/// Parse a 1-based skill number into a `Difficulty`.
+fn parse_skill(skill_str: &str) -> Result<Difficulty, Error> {
+    if let Some(difficulty) = skill_str.parse().ok().and_then(Difficulty::from_skill) {
+        Ok(difficulty)
+    } else {
+        bail!("Skill must be a number from 1 to 5");
+    }
+}
+
+/// Parse a vsync mode: `off`, `on` or `adaptive`.
+fn parse_vsync(vsync_str: &str) -> Result<VSync, Error> {
+    Ok(match vsync_str {
+        "off" => VSync::Off,
+        "on" => VSync::On,
+        "adaptive" => VSync::Adaptive,
+        _ => bail!("VSync must be one of off, on or adaptive"),
+    })
+}
+
+/// Parse a widescreen mode: `stretch`, `pillarbox` or `expand`.
+fn parse_widescreen_mode(mode_str: &str) -> Result<WidescreenMode, Error> {
+    Ok(match mode_str {
+        "stretch" => WidescreenMode::Stretch,
+        "pillarbox" => WidescreenMode::Pillarbox,
+        "expand" => WidescreenMode::Expand,
+        _ => bail!("Widescreen mode must be one of stretch, pillarbox or expand"),
+    })
+}
+
+/// Parse an MSAA sample count: 1, 2, 4 or 8.
+fn parse_msaa_samples(samples_str: &str) -> Result<u8, Error> {
+    match samples_str.parse() {
+        Ok(samples @ 1) | Ok(samples @ 2) | Ok(samples @ 4) | Ok(samples @ 8) => Ok(samples),
+        _ => bail!("MSAA samples must be one of 1, 2, 4 or 8"),
+    }
+}
+
+/// Parse a rendering quality: `low`, `medium` or `high`.
+fn parse_render_quality(quality_str: &str) -> Result<RenderQuality, Error> {
+    Ok(match quality_str {
+        "low" => RenderQuality::Low,
+        "medium" => RenderQuality::Medium,
+        "high" => RenderQuality::High,
+        _ => bail!("Quality must be one of low, medium or high"),
+    })
+}
+
+/// Parse a clear colour: `sky` or three comma-separated components like `1,0,1`.
+fn parse_clear_color(color_str: &str) -> Result<ClearColor, Error> {
+    if color_str == "sky" {
+        return Ok(ClearColor::Sky);
+    }
+    let components = color_str
+        .split(',')
+        .map(|component| component.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>();
+    match components.as_ref().map(|components| &components[..]) {
+        Ok(&[r, g, b]) => Ok(ClearColor::Fixed([r, g, b])),
+        _ => bail!("Clear colour must be sky or R,G,B"),
+    }
+}
+
+/// Whether `error` was caused by an out of range setting, rather than by I/O, the WAD or the GPU.
+fn is_invalid_config(error: &Error) -> bool {
+    error.iter_chain().any(|cause| {
+        cause
+            .downcast_ref::<game::Error>()
+            .map_or(false, |error| match error.kind() {
+                game::ErrorKind::InvalidConfig(_) => true,
+                _ => false,
+            })
+    })
+}
+
 fn main() {
     if let Err(error) = App::run_from_args() {
         error!("Fatal error: {}", error);
@@ -180,6 +363,7 @@ fn main() {
         } else {
             error!("Run with RUST_BACKTRACE=1 to capture backtrace.");
         }
-        process::exit(1);
+        // Bad settings exit like bad command line arguments, rather than like a failure to run.
+        process::exit(if is_invalid_config(&error) { 2 } else { 1 });
     }
 }
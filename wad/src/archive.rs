@@ -1,6 +1,7 @@
 use super::errors::{ErrorKind, Result};
 use super::meta::WadMetadata;
 use super::name::IntoWadName;
+use super::sprite::sprite_rotation;
 use super::types::{WadInfo, WadLump, WadName};
 use bincode;
 use failchain::{ensure, ResultExt};
@@ -15,19 +16,23 @@ use std::hash::Hash;
 use std::io::{BufReader, Read, Seek, SeekFrom, Take};
 use std::mem;
 use std::path::Path;
+use std::sync::Arc;
 use std::vec::Vec;
 
 #[derive(Debug)]
 pub struct Archive {
-    file: RefCell<BufReader<File>>,
+    files: Vec<RefCell<BufReader<File>>>,
+    file_sizes: Vec<u64>,
     index_map: IndexMap<WadName, usize>,
     lumps: Vec<LumpInfo>,
     levels: Vec<usize>,
-    meta: WadMetadata,
+    meta: Arc<WadMetadata>,
 }
 
-struct OpenWad {
-    file: RefCell<BufReader<File>>,
+#[derive(Default)]
+struct OpenWads {
+    files: Vec<RefCell<BufReader<File>>>,
+    file_sizes: Vec<u64>,
     index_map: IndexMap<WadName, usize>,
     lumps: Vec<LumpInfo>,
     levels: Vec<usize>,
@@ -39,75 +44,146 @@ impl Archive {
         W: AsRef<Path> + Debug,
         M: AsRef<Path> + Debug,
     {
+        Archive::open_with_patches::<_, &Path, _>(wad_path, &[], meta_path)
+    }
+
+    /// Opens an IWAD together with a list of PWADs which patch it.
+    ///
+    /// Lumps in later PWADs override same-named lumps in earlier ones and in the IWAD, and a PWAD
+    /// level replaces the level with the same name (or is added after the others if it's new).
+    pub fn open_with_patches<W, P, M>(
+        wad_path: &W,
+        patch_paths: &[P],
+        meta_path: &M,
+    ) -> Result<Archive>
+    where
+        W: AsRef<Path> + Debug,
+        P: AsRef<Path> + Debug,
+        M: AsRef<Path> + Debug,
+    {
+        let mut wads = OpenWads::default();
         let wad_path = wad_path.as_ref().to_owned();
-        let meta_path = meta_path.as_ref().to_owned();
         info!("Loading wad file '{:?}'...", wad_path);
-        let OpenWad {
-            file,
-            index_map,
-            lumps,
-            levels,
-        } = Archive::open_wad(&wad_path)?;
+        wads.add(&wad_path, &[IWAD_HEADER])?;
+        for patch_path in patch_paths {
+            let patch_path = patch_path.as_ref().to_owned();
+            info!("Loading patch wad file '{:?}'...", patch_path);
+            wads.add(&patch_path, &[PWAD_HEADER, IWAD_HEADER])?;
+        }
+
+        let meta_path = meta_path.as_ref().to_owned();
         info!("Loading metadata file '{:?}'...", meta_path);
         let meta = WadMetadata::from_file(&meta_path)?;
 
-        Ok(Archive {
-            file,
-            meta,
+        let OpenWads {
+            files,
+            file_sizes,
+            index_map,
+            lumps,
+            levels,
+        } = wads;
+        let archive = Archive {
+            files,
+            file_sizes,
+            meta: Arc::new(meta),
             lumps,
             index_map,
             levels,
-        })
+        };
+        archive.verify_integrity()?;
+        Ok(archive)
     }
 
-    fn open_wad(wad_path: &Path) -> Result<OpenWad> {
-        // Open file, read and check header.
-        let mut file = BufReader::new(File::open(&wad_path).chain_err(ErrorKind::on_file_open)?);
-
-        let header: WadInfo =
-            bincode::deserialize_from(&mut file).chain_err(ErrorKind::bad_wad_header)?;
-
-        ensure!(
-            header.identifier == IWAD_HEADER,
-            ErrorKind::bad_wad_header_identifier(&header.identifier)
-        );
+    /// Checks that every lump lies within its file, so that truncated WADs fail with a
+    /// `CorruptWad` error when opened rather than when some lump is first read. The headers and
+    /// lump tables are already checked while opening.
+    pub fn verify_integrity(&self) -> Result<()> {
+        for (index, lump) in self.lumps.iter().enumerate() {
+            let file_size = self.file_sizes[lump.file_index];
+            let fits = (lump.size as u64)
+                .checked_add(lump.offset)
+                .map_or(false, |end| end <= file_size);
+            ensure!(
+                fits,
+                ErrorKind::lump_out_of_bounds(
+                    index,
+                    lump.name.as_ref(),
+                    lump.offset,
+                    lump.size,
+                    file_size
+                )
+            );
+        }
+        Ok(())
+    }
 
-        // Read lump info.
-        let mut lumps = Vec::with_capacity(header.num_lumps as usize);
-        let mut levels = Vec::with_capacity(64);
-        let mut index_map = IndexMap::new();
+    pub fn metadata(&self) -> &WadMetadata {
+        &self.meta
+    }
 
-        file.seek(SeekFrom::Start(header.info_table_offset as u64))
-            .chain_err(|| ErrorKind::seeking_to_info_table_offset(header.info_table_offset))?;
-        for i_lump in 0..header.num_lumps {
-            let fileinfo: WadLump = bincode::deserialize_from(&mut file)
-                .chain_err(|| ErrorKind::bad_lump_info(i_lump))?;
+    /// Like `metadata`, but shared so that it can be sent to other threads, unlike the archive
+    /// itself.
+    pub fn shared_metadata(&self) -> Arc<WadMetadata> {
+        Arc::clone(&self.meta)
+    }
 
-            index_map.insert(fileinfo.name, lumps.len());
-            lumps.push(LumpInfo {
-                name: fileinfo.name,
-                offset: fileinfo.file_pos as u64,
-                size: fileinfo.size as usize,
-            });
+    pub fn num_lumps(&self) -> usize {
+        self.lumps.len()
+    }
 
-            // Our heuristic for level lumps is that they are preceeded by the "THINGS"
-            // lump.
-            if &fileinfo.name == b"THINGS\0\0" {
-                assert!(i_lump > 0);
-                levels.push((i_lump - 1) as usize);
+    /// Returns the indices of the lumps between any of the `start` and `end` markers, in order,
+    /// from the IWAD and every PWAD (which may use different markers, like `FF_START`).
+    pub fn lumps_between(&self, start: &[&[u8; 8]], end: &[&[u8; 8]]) -> Vec<usize> {
+        let mut inside = false;
+        let mut indices = Vec::new();
+        for (index, lump) in self.lumps.iter().enumerate() {
+            if start.iter().any(|&marker| lump.name == *marker) {
+                inside = true;
+            } else if end.iter().any(|&marker| lump.name == *marker) {
+                inside = false;
+            } else if inside {
+                indices.push(index);
             }
         }
+        indices
+    }
 
-        Ok(OpenWad {
-            file: RefCell::new(file),
-            index_map,
-            lumps,
-            levels,
-        })
+    /// Returns the lumps in the sprite namespace, between `S_START` and `S_END` (or the doubled
+    /// `SS_` markers). Of the sprites with the same name in several WADs, only the last is
+    /// returned. Empty lumps, like the `S1_START` sub-markers, are skipped.
+    pub fn sprite_lumps(&self) -> impl Iterator<Item = LumpReader<'_>> + '_ {
+        self.namespace_lumps(SPRITES_START, SPRITES_END)
     }
 
-    pub fn metadata(&self) -> &WadMetadata {
-        &self.meta
+    /// Returns the lumps in the flat namespace, between `F_START` and `F_END` (or the doubled
+    /// `FF_` markers), in the same way as `sprite_lumps`.
+    pub fn flat_lumps(&self) -> impl Iterator<Item = LumpReader<'_>> + '_ {
+        self.namespace_lumps(FLATS_START, FLATS_END)
+    }
+
+    /// Returns the lumps in ZDoom's texture namespace, between `TX_START` and `TX_END`: single
+    /// patch textures which need no `TEXTURE1` entry. They are returned like `sprite_lumps`.
+    pub fn custom_texture_lumps(&self) -> impl Iterator<Item = LumpReader<'_>> + '_ {
+        self.namespace_lumps(TEXTURES_START, TEXTURES_END)
+    }
+
+    fn namespace_lumps(
+        &self,
+        start: &[&[u8; 8]],
+        end: &[&[u8; 8]],
+    ) -> impl Iterator<Item = LumpReader<'_>> + '_ {
+        let mut latest = IndexMap::new();
+        for index in self.lumps_between(start, end) {
+            let info = &self.lumps[index];
+            if info.size > 0 {
+                latest.insert(info.name, index);
+            }
+        }
+        latest.into_iter().map(move |(_, index)| LumpReader {
+            archive: self,
+            info: &self.lumps[index],
+            index,
+        })
     }
 
     pub fn num_levels(&self) -> usize {
@@ -115,7 +191,11 @@ impl Archive {
     }
 
     pub fn level_lump(&self, level_index: usize) -> Result<LumpReader> {
-        self.lump_by_index(self.levels[level_index])
+        let &lump_index = self
+            .levels
+            .get(level_index)
+            .ok_or_else(|| ErrorKind::no_such_level(level_index, self.levels.len()))?;
+        self.lump_by_index(lump_index)
     }
 
     pub fn required_named_lump<'a, Q>(&self, name: &'a Q) -> Result<LumpReader>
@@ -138,6 +218,48 @@ impl Archive {
         }
     }
 
+    /// Reads the contents of the lump called `name`, ignoring case.
+    ///
+    /// Returns `ErrorKind::MissingLump` if there is no such lump.
+    pub fn read_lump_by_name(&self, name: &str) -> Result<Vec<u8>> {
+        self.try_read_lump_by_name(name)?
+            .ok_or_else(|| ErrorKind::missing_required_lump(&name).into())
+    }
+
+    /// Like `read_lump_by_name`, but returns `None` if there is no such lump.
+    pub fn try_read_lump_by_name(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let name: WadName = name.into_wad_name()?;
+        match self.named_lump(&name)? {
+            Some(lump) => lump.read_bytes().map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether there is a lump called `name`, ignoring case. Names which aren't valid lump names
+    /// (too long, say) don't exist.
+    pub fn lump_exists(&self, name: &str) -> bool {
+        name.into_wad_name()
+            .ok()
+            .map_or(false, |name| self.index_map.contains_key(&name))
+    }
+
+    /// Finds the sprite lump for `frame` of the sprite `base` (like "TROO") seen from `angle`
+    /// (1-8, or 0 for any angle), following DOOM's rotation naming scheme.
+    ///
+    /// Returns the lump and whether it should be flipped horizontally, since rotations 6-8 are
+    /// often stored as mirror images of 2-4.
+    pub fn sprite_lump(&self, base: &str, frame: char, angle: u8) -> Option<(LumpReader, bool)> {
+        let base: WadName = base.parse().ok()?;
+        if !frame.is_ascii() {
+            return None;
+        }
+        let (name, flip) = sprite_rotation(base, frame as u8, angle, |name| {
+            self.index_map.contains_key(name)
+        })?;
+        let lump = self.named_lump(&name).ok()??;
+        Some((lump, flip))
+    }
+
     pub fn lump_by_index(&self, index: usize) -> Result<LumpReader> {
         Ok(LumpReader {
             archive: self,
@@ -150,6 +272,73 @@ impl Archive {
     }
 }
 
+impl OpenWads {
+    fn add(&mut self, wad_path: &Path, identifiers: &[&[u8]]) -> Result<()> {
+        // Open file, read and check header.
+        let file = File::open(&wad_path).chain_err(ErrorKind::on_file_open)?;
+        let file_size = file.metadata().chain_err(ErrorKind::on_file_open)?.len();
+        let mut file = BufReader::new(file);
+
+        let header: WadInfo =
+            bincode::deserialize_from(&mut file).chain_err(ErrorKind::bad_wad_header)?;
+
+        ensure!(
+            identifiers.contains(&&header.identifier[..]),
+            ErrorKind::bad_wad_header_identifier(&header.identifier)
+        );
+        let table_size = i64::from(header.num_lumps) * mem::size_of::<WadLump>() as i64;
+        ensure!(
+            header.num_lumps >= 0
+                && header.info_table_offset >= 0
+                && i64::from(header.info_table_offset) + table_size <= file_size as i64,
+            ErrorKind::bad_info_table(header.num_lumps, header.info_table_offset, file_size)
+        );
+
+        // Read lump info.
+        let file_index = self.files.len();
+        let first_lump = self.lumps.len();
+        self.lumps.reserve(header.num_lumps as usize);
+
+        file.seek(SeekFrom::Start(header.info_table_offset as u64))
+            .chain_err(|| ErrorKind::seeking_to_info_table_offset(header.info_table_offset))?;
+        for i_lump in 0..header.num_lumps {
+            let fileinfo: WadLump = bincode::deserialize_from(&mut file)
+                .chain_err(|| ErrorKind::bad_lump_info(i_lump))?;
+
+            // Later lumps with the same name shadow earlier ones, including those in earlier
+            // WADs.
+            self.index_map.insert(fileinfo.name, self.lumps.len());
+            self.lumps.push(LumpInfo {
+                name: fileinfo.name,
+                file_index,
+                offset: fileinfo.file_pos as u64,
+                size: fileinfo.size as usize,
+            });
+
+            // Our heuristic for level lumps is that they are preceeded by the "THINGS"
+            // lump.
+            if &fileinfo.name == b"THINGS\0\0" {
+                ensure!(i_lump > 0, ErrorKind::level_without_marker());
+                let level = first_lump + i_lump as usize - 1;
+                let level_name = self.lumps[level].name;
+                let lumps = &self.lumps;
+                match self
+                    .levels
+                    .iter_mut()
+                    .find(|existing| lumps[**existing].name == level_name)
+                {
+                    Some(existing) => *existing = level,
+                    None => self.levels.push(level),
+                }
+            }
+        }
+
+        self.files.push(RefCell::new(file));
+        self.file_sizes.push(file_size);
+        Ok(())
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct LumpReader<'a> {
     archive: &'a Archive,
@@ -172,21 +361,15 @@ impl<'a> LumpReader<'a> {
 
     pub fn decode_vec<T: DeserializeOwned>(&self) -> Result<Vec<T>> {
         let LumpReader { info, index, .. } = *self;
-        self.read(|mut file| {
-            let element_size = mem::size_of::<T>();
-            let num_elements = info.size / element_size;
+        self.read(|file| decode_elements(file, index, info.name, info.size))
+    }
 
-            ensure!(
-                info.size > 0 && (info.size % element_size == 0),
-                ErrorKind::bad_lump_size(index, info.name.as_ref(), info.size, element_size),
-            );
-            (0..num_elements)
-                .map(move |i_element| {
-                    bincode::deserialize_from(&mut file).chain_err(|| {
-                        ErrorKind::bad_lump_element(index, info.name.as_ref(), i_element)
-                    })
-                })
-                .collect()
+    /// Reads the lump into memory, to be decoded later or on another thread.
+    pub fn read_lump_bytes(&self) -> Result<LumpBytes> {
+        Ok(LumpBytes {
+            index: self.index,
+            name: self.name(),
+            bytes: self.read_bytes()?,
         })
     }
 
@@ -251,18 +434,270 @@ impl<'a> LumpReader<'a> {
             index,
             archive,
         } = *self;
-        let mut file = archive.file.borrow_mut();
+        let mut file = archive.files[info.file_index].borrow_mut();
         file.seek(SeekFrom::Start(info.offset))
             .chain_err(|| ErrorKind::seeking_to_lump(index, info.name.as_ref()))?;
         with(&mut Read::take(&mut *file, info.size as u64))
     }
 }
 
+/// A lump's contents read into memory by `LumpReader::read_lump_bytes`, which unlike a
+/// `LumpReader` can be sent to other threads.
+#[derive(Clone, Debug)]
+pub struct LumpBytes {
+    index: usize,
+    name: WadName,
+    bytes: Vec<u8>,
+}
+
+impl LumpBytes {
+    pub fn name(&self) -> WadName {
+        self.name
+    }
+
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Like `LumpReader::decode_vec`.
+    pub fn decode_vec<T: DeserializeOwned>(&self) -> Result<Vec<T>> {
+        decode_elements(&self.bytes[..], self.index, self.name, self.bytes.len())
+    }
+}
+
+/// Decodes a lump of `size` bytes made of consecutive `T`s.
+fn decode_elements<T: DeserializeOwned, R: Read>(
+    mut reader: R,
+    index: usize,
+    name: WadName,
+    size: usize,
+) -> Result<Vec<T>> {
+    let element_size = mem::size_of::<T>();
+    let num_elements = size / element_size;
+
+    ensure!(
+        size > 0 && (size % element_size == 0),
+        ErrorKind::bad_lump_size(index, name.as_ref(), size, element_size),
+    );
+    (0..num_elements)
+        .map(move |i_element| {
+            bincode::deserialize_from(&mut reader)
+                .chain_err(|| ErrorKind::bad_lump_element(index, name.as_ref(), i_element))
+        })
+        .collect()
+}
+
 #[derive(Copy, Clone, Debug)]
 struct LumpInfo {
     name: WadName,
+    file_index: usize,
     offset: u64,
     size: usize,
 }
 
+// PWADs often use the doubled markers, so that they can be merged with the IWAD's lists.
+const SPRITES_START: &[&[u8; 8]] = &[b"S_START\0", b"SS_START"];
+const SPRITES_END: &[&[u8; 8]] = &[b"S_END\0\0\0", b"SS_END\0\0"];
+const FLATS_START: &[&[u8; 8]] = &[b"F_START\0", b"FF_START"];
+const FLATS_END: &[&[u8; 8]] = &[b"F_END\0\0\0", b"FF_END\0\0"];
+const TEXTURES_START: &[&[u8; 8]] = &[b"TX_START"];
+const TEXTURES_END: &[&[u8; 8]] = &[b"TX_END\0\0"];
+
 const IWAD_HEADER: &[u8] = b"IWAD";
+const PWAD_HEADER: &[u8] = b"PWAD";
+
+#[cfg(test)]
+mod test {
+    use super::super::errors::ErrorKind;
+    use super::super::level::Level;
+    use super::Archive;
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const META_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../assets/meta/doom.toml");
+
+    /// Writes a WAD file with the given header identifier and (name, contents) lumps.
+    fn write_wad(file_name: &str, identifier: [u8; 4], lumps: &[(&[u8; 8], &[u8])]) -> PathBuf {
+        let mut data = Vec::new();
+        let mut directory = Vec::new();
+        let data_start = 12;
+        for &(name, contents) in lumps {
+            directory.extend_from_slice(&((data_start + data.len()) as i32).to_le_bytes());
+            directory.extend_from_slice(&(contents.len() as i32).to_le_bytes());
+            directory.extend_from_slice(&name[..]);
+            data.extend_from_slice(contents);
+        }
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(&identifier);
+        bytes.extend_from_slice(&(lumps.len() as i32).to_le_bytes());
+        bytes.extend_from_slice(&((data_start + data.len()) as i32).to_le_bytes());
+        bytes.extend_from_slice(&data);
+        bytes.extend_from_slice(&directory);
+
+        let path = env::temp_dir().join(format!("{}-{}", std::process::id(), file_name));
+        fs::write(&path, bytes).expect("failed to write test wad");
+        path
+    }
+
+    #[test]
+    fn test_later_pwads_override_lumps() {
+        let iwad = write_wad(
+            "iwad.wad",
+            *b"IWAD",
+            &[
+                (b"F_START\0", b""),
+                (b"FLOOR0_1", b"iwad"),
+                (b"F_END\0\0\0", b""),
+                (b"PLAYPAL\0", b"palette"),
+            ],
+        );
+        let first = write_wad(
+            "first.wad",
+            *b"PWAD",
+            &[
+                (b"FF_START", b""),
+                (b"FLOOR0_1", b"first"),
+                (b"FF_END\0\0", b""),
+                (b"TX_START", b""),
+                (b"MYTEX\0\0\0", b"texture"),
+                (b"TX_END\0\0", b""),
+            ],
+        );
+        let second = write_wad("second.wad", *b"PWAD", &[(b"FLOOR0_1", b"second")]);
+
+        let archive = Archive::open_with_patches(&iwad, &[&first], &META_PATH).unwrap();
+        assert_eq!(archive.read_lump_by_name("FLOOR0_1").unwrap(), b"first");
+        assert_eq!(archive.read_lump_by_name("PLAYPAL").unwrap(), b"palette");
+        assert!(archive.lump_exists("playpal"));
+        assert!(!archive.lump_exists("COLORMAP"));
+        assert!(!archive.lump_exists("TOOLONGNAME"));
+        assert_eq!(archive.num_levels(), 0);
+        assert!(archive.level_lump(0).is_err());
+        let flats = archive.lumps_between(
+            &[b"F_START\0", b"FF_START"],
+            &[b"F_END\0\0\0", b"FF_END\0\0"],
+        );
+        assert_eq!(flats.len(), 2);
+
+        let flats = archive.flat_lumps().collect::<Vec<_>>();
+        assert_eq!(flats.len(), 1);
+        assert_eq!(flats[0].read_bytes().unwrap(), b"first");
+        assert_eq!(archive.sprite_lumps().count(), 0);
+        let textures = archive.custom_texture_lumps().collect::<Vec<_>>();
+        assert_eq!(textures.len(), 1);
+        assert_eq!(textures[0].read_bytes().unwrap(), b"texture");
+
+        let archive = Archive::open_with_patches(&iwad, &[&first, &second], &META_PATH).unwrap();
+        assert_eq!(archive.read_lump_by_name("floor0_1").unwrap(), b"second");
+
+        let archive = Archive::open(&iwad, &META_PATH).unwrap();
+        assert_eq!(archive.read_lump_by_name("FLOOR0_1").unwrap(), b"iwad");
+
+        for path in &[iwad, first, second] {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_truncated_wad_is_corrupt() {
+        let path = write_wad(
+            "truncated.wad",
+            *b"IWAD",
+            &[(b"PLAYPAL\0", b"palette"), (b"COLORMAP", b"colormap")],
+        );
+        assert!(Archive::open(&path, &META_PATH).is_ok());
+
+        // Point the second lump past the end of the file.
+        let mut bytes = fs::read(&path).unwrap();
+        let offset = bytes.len() - 16;
+        bytes[offset..offset + 4].copy_from_slice(&1000i32.to_le_bytes());
+        fs::write(&path, &bytes).unwrap();
+        let error = Archive::open(&path, &META_PATH).unwrap_err();
+        assert!(match error.kind() {
+            ErrorKind::CorruptWad(_) => true,
+            _ => false,
+        });
+
+        // Drop the end of the lump table.
+        fs::write(&path, &bytes[..bytes.len() - 4]).unwrap();
+        assert!(Archive::open(&path, &META_PATH).is_err());
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_mutated_wads_dont_panic() {
+        // A deterministic xorshift, so failures are reproducible.
+        let mut state = 0x2545_f491u32;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+
+        // Long enough to hold a whole number of every kind of level record.
+        let lump: Vec<u8> = (0..5460).map(|_| random() as u8).collect();
+        let lumps = [
+            &b"E1M1\0\0\0\0"[..],
+            b"THINGS\0\0",
+            b"LINEDEFS",
+            b"SIDEDEFS",
+            b"VERTEXES",
+            b"SEGS\0\0\0\0",
+            b"SSECTORS",
+            b"NODES\0\0\0",
+            b"SECTORS\0",
+            b"REJECT\0\0",
+            b"BLOCKMAP",
+        ]
+        .iter()
+        .map(|name| {
+            let mut fixed = [0u8; 8];
+            fixed.copy_from_slice(name);
+            fixed
+        })
+        .collect::<Vec<_>>();
+        let lumps = lumps
+            .iter()
+            .map(|name| (name, &lump[..]))
+            .collect::<Vec<_>>();
+        let path = write_wad("mutated.wad", *b"PWAD", &lumps);
+        let original = fs::read(&path).unwrap();
+
+        for _ in 0..200 {
+            let mut bytes = original.clone();
+            match random() % 3 {
+                // Flip a few bytes anywhere, including in the header and lump table.
+                0 | 1 => {
+                    for _ in 0..=random() % 8 {
+                        let index = random() as usize % bytes.len();
+                        bytes[index] = random() as u8;
+                    }
+                }
+                // Or replace everything after the identifier with noise.
+                _ => {
+                    let len = 4 + random() as usize % 4096;
+                    bytes.resize(len, 0);
+                    for byte in &mut bytes[4..] {
+                        *byte = random() as u8;
+                    }
+                }
+            }
+            fs::write(&path, &bytes).unwrap();
+            if let Ok(archive) = Archive::open(&path, &META_PATH) {
+                for index in 0..archive.num_lumps() {
+                    let _ = archive
+                        .lump_by_index(index)
+                        .and_then(|lump| lump.read_bytes());
+                }
+                for index in 0..archive.num_levels() {
+                    let _ = Level::from_archive(&archive, index);
+                }
+            }
+        }
+
+        let _ = fs::remove_file(&path);
+    }
+}
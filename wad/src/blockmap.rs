@@ -0,0 +1,225 @@
+//! The BLOCKMAP lump: a grid over the level listing which linedefs cross each cell, for finding
+//! the walls near a point without looking at every linedef.
+
+use super::errors::{ErrorKind, Result};
+use super::types::{LinedefId, WadCoord, WadLinedef, WadVertex};
+use super::util::to_wad_height;
+use failchain::ensure;
+use math::Pnt2f;
+use std::cmp;
+use std::ops::RangeInclusive;
+
+/// The side of a blockmap cell, in map units.
+const BLOCK_SIZE: i32 = 128;
+
+/// Marks the end of a cell's list of linedefs.
+const BLOCKLIST_END: u16 = 0xffff;
+
+pub struct Blockmap {
+    origin_x: i32,
+    origin_y: i32,
+    columns: usize,
+    rows: usize,
+    blocklists: Vec<Vec<LinedefId>>,
+}
+
+impl Blockmap {
+    /// Parses a BLOCKMAP lump, returning an `InvalidWad` error if it is truncated or otherwise
+    /// corrupt.
+    ///
+    /// The lump is a header of four words (the grid's origin and size), then one word per cell
+    /// giving the offset of its blocklist (in words from the start of the lump). Each blocklist
+    /// starts with a zero, which is skipped, and is terminated by `0xffff`.
+    pub(crate) fn parse(bytes: &[u8]) -> Result<Blockmap> {
+        let words: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|word| u16::from_le_bytes([word[0], word[1]]))
+            .collect();
+        ensure!(
+            words.len() >= 4,
+            ErrorKind::InvalidWad,
+            "BLOCKMAP lump too small for its header: {} bytes.",
+            bytes.len()
+        );
+        let (columns, rows) = (words[2] as usize, words[3] as usize);
+        let offsets = words
+            .get(4..4 + columns * rows)
+            .filter(|_| columns > 0 && rows > 0)
+            .ok_or_else(|| ErrorKind::bad_blockmap_dimensions(columns, rows, words.len()))?;
+        let blocklists = offsets
+            .iter()
+            .enumerate()
+            .map(|(cell, &offset)| {
+                let list = words
+                    .get(offset as usize..)
+                    .ok_or_else(|| ErrorKind::bad_blocklist(cell, offset))?;
+                let list = if list.first() == Some(&0) {
+                    &list[1..]
+                } else {
+                    list
+                };
+                let end = list
+                    .iter()
+                    .position(|&word| word == BLOCKLIST_END)
+                    .ok_or_else(|| ErrorKind::bad_blocklist(cell, offset))?;
+                Ok(list[..end].to_vec())
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Blockmap {
+            origin_x: i32::from(words[0] as WadCoord),
+            origin_y: i32::from(words[1] as WadCoord),
+            columns,
+            rows,
+            blocklists,
+        })
+    }
+
+    /// Builds a blockmap for levels whose lump is unusable. Each linedef is listed in every cell
+    /// its bounding box touches, which is a superset of the cells it actually crosses.
+    pub(crate) fn build(linedefs: &[WadLinedef], vertices: &[WadVertex]) -> Blockmap {
+        let min_x = vertices.iter().map(|v| i32::from(v.x)).min().unwrap_or(0);
+        let min_y = vertices.iter().map(|v| i32::from(v.y)).min().unwrap_or(0);
+        let max_x = vertices.iter().map(|v| i32::from(v.x)).max().unwrap_or(0);
+        let max_y = vertices.iter().map(|v| i32::from(v.y)).max().unwrap_or(0);
+        let mut blockmap = Blockmap {
+            origin_x: min_x,
+            origin_y: min_y,
+            columns: ((max_x - min_x) / BLOCK_SIZE + 1) as usize,
+            rows: ((max_y - min_y) / BLOCK_SIZE + 1) as usize,
+            blocklists: Vec::new(),
+        };
+        blockmap.blocklists = vec![Vec::new(); blockmap.columns * blockmap.rows];
+        for (id, linedef) in linedefs.iter().enumerate() {
+            let (v1, v2) = match (
+                vertices.get(linedef.start_vertex as usize),
+                vertices.get(linedef.end_vertex as usize),
+            ) {
+                (Some(v1), Some(v2)) => (v1, v2),
+                _ => continue,
+            };
+            let (columns, rows) = blockmap.cells_between(
+                (i32::from(v1.x.min(v2.x)), i32::from(v1.y.min(v2.y))),
+                (i32::from(v1.x.max(v2.x)), i32::from(v1.y.max(v2.y))),
+            );
+            for row in rows {
+                for column in columns.clone() {
+                    blockmap.blocklists[row * blockmap.columns + column].push(id as LinedefId);
+                }
+            }
+        }
+        blockmap
+    }
+
+    /// Returns the linedefs crossing the cell in column `block_x` and row `block_y`, counted from
+    /// the grid's origin at its lowest map coordinates; cells outside the grid are empty.
+    pub fn cell(&self, block_x: i32, block_y: i32) -> &[LinedefId] {
+        if block_x < 0
+            || block_y < 0
+            || block_x as usize >= self.columns
+            || block_y as usize >= self.rows
+        {
+            return &[];
+        }
+        &self.blocklists[block_y as usize * self.columns + block_x as usize]
+    }
+
+    /// Returns the linedefs which may come within `radius` of `center` (both in world units),
+    /// sorted and without duplicates. Most of them will usually be further away.
+    pub fn linedefs_near(&self, center: Pnt2f, radius: f32) -> Vec<LinedefId> {
+        let (x, y) = (to_wad_height(-center.y), to_wad_height(-center.x));
+        let radius = to_wad_height(radius);
+        let (columns, rows) = self.cells_between(
+            ((x - radius).floor() as i32, (y - radius).floor() as i32),
+            ((x + radius).ceil() as i32, (y + radius).ceil() as i32),
+        );
+        let mut linedefs = Vec::new();
+        for row in rows {
+            for column in columns.clone() {
+                linedefs.extend_from_slice(self.cell(column as i32, row as i32));
+            }
+        }
+        linedefs.sort_unstable();
+        linedefs.dedup();
+        linedefs
+    }
+
+    /// The ranges of columns and rows of the cells overlapping a box in map coordinates. Boxes
+    /// beyond the edge of the grid get the cells along that edge.
+    fn cells_between(
+        &self,
+        (min_x, min_y): (i32, i32),
+        (max_x, max_y): (i32, i32),
+    ) -> (RangeInclusive<usize>, RangeInclusive<usize>) {
+        let cell = |coord: i32, origin: i32, count: usize| {
+            cmp::min(
+                cmp::max((coord - origin) / BLOCK_SIZE, 0) as usize,
+                count - 1,
+            )
+        };
+        let columns =
+            cell(min_x, self.origin_x, self.columns)..=cell(max_x, self.origin_x, self.columns);
+        let rows = cell(min_y, self.origin_y, self.rows)..=cell(max_y, self.origin_y, self.rows);
+        (columns, rows)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Blockmap;
+    use math::Pnt2f;
+
+    fn lump(words: &[u16]) -> Vec<u8> {
+        words
+            .iter()
+            .flat_map(|word| word.to_le_bytes().to_vec())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_blockmap() {
+        // A 2x1 grid at (-128, 0): the left cell holds linedefs 0 and 1, the right one 1 and 2.
+        let words = [
+            (-128i16) as u16,
+            0,
+            2,
+            1,
+            6,
+            10,
+            0,
+            0,
+            1,
+            0xffff,
+            0,
+            1,
+            2,
+            0xffff,
+        ];
+        let blockmap = Blockmap::parse(&lump(&words)).unwrap();
+        // World (X, Z) = (-y, -x) / 100, so map point (-64, 64) is at (-0.64, 0.64).
+        assert_eq!(
+            blockmap.linedefs_near(Pnt2f::new(-0.64, 0.64), 0.1),
+            vec![0, 1]
+        );
+        assert_eq!(
+            blockmap.linedefs_near(Pnt2f::new(-0.64, 0.0), 0.1),
+            vec![0, 1, 2]
+        );
+        assert_eq!(
+            blockmap.linedefs_near(Pnt2f::new(-0.64, -0.64), 0.1),
+            vec![1, 2]
+        );
+        // Far outside the grid, the cells on the nearest edge are returned.
+        assert_eq!(
+            blockmap.linedefs_near(Pnt2f::new(0.0, -10.0), 0.1),
+            vec![1, 2]
+        );
+
+        assert_eq!(blockmap.cell(1, 0), &[1, 2]);
+        assert!(blockmap.cell(2, 0).is_empty());
+        assert!(blockmap.cell(0, -1).is_empty());
+
+        assert!(Blockmap::parse(&[]).is_err());
+        assert!(Blockmap::parse(&lump(&words[..12])).is_err());
+        assert!(Blockmap::parse(&lump(&[0, 0, 0, 1, 4])).is_err());
+    }
+}
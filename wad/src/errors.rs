@@ -14,8 +14,14 @@ pub enum ErrorKind {
     #[fail(display = "Corrupt WAD file: {}", 0)]
     CorruptWad(String),
 
+    #[fail(display = "Invalid WAD: {}", 0)]
+    InvalidWad(String),
+
     #[fail(display = "I/O WAD error: {}", 0)]
     Io(String),
+
+    #[fail(display = "Missing WAD lump: {}", 0)]
+    MissingLump(String),
 }
 
 impl ChainErrorKind for ErrorKind {
@@ -65,6 +71,48 @@ impl ErrorKind {
         ))
     }
 
+    pub(crate) fn bad_info_table(num_lumps: i32, offset: i32, file_size: u64) -> Self {
+        ErrorKind::CorruptWad(format!(
+            "Lump table of {} lumps at offset {} doesn't fit in file of size {}.",
+            num_lumps, offset, file_size
+        ))
+    }
+
+    pub(crate) fn lump_out_of_bounds(
+        index: usize,
+        name: &str,
+        offset: u64,
+        size: usize,
+        file_size: u64,
+    ) -> Self {
+        ErrorKind::CorruptWad(format!(
+            "Lump {}, `{}` at offset {} of size {} runs past the end of its file (size={}).",
+            index, name, offset, size, file_size
+        ))
+    }
+
+    pub(crate) fn missing_level_lump(level: &str, lump: &str) -> Self {
+        ErrorKind::InvalidWad(format!("Level {} missing {} lump.", level, lump))
+    }
+
+    pub(crate) fn level_without_marker() -> Self {
+        ErrorKind::InvalidWad("THINGS lump at the start of a WAD has no level marker.".to_owned())
+    }
+
+    pub(crate) fn bad_blockmap_dimensions(columns: usize, rows: usize, words: usize) -> Self {
+        ErrorKind::InvalidWad(format!(
+            "BLOCKMAP lump has invalid dimensions {}x{} for a lump of {} words.",
+            columns, rows, words
+        ))
+    }
+
+    pub(crate) fn bad_blocklist(cell: usize, offset: u16) -> Self {
+        ErrorKind::InvalidWad(format!(
+            "BLOCKMAP cell {} has an unterminated blocklist at offset {}.",
+            cell, offset
+        ))
+    }
+
     pub(crate) fn on_metadata_read() -> Self {
         ErrorKind::Io("Failed to load metadata to memory.".to_owned())
     }
@@ -124,7 +172,14 @@ impl ErrorKind {
         ))
     }
 
+    pub(crate) fn no_such_level(index: usize, num_levels: usize) -> Self {
+        ErrorKind::MissingLump(format!(
+            "No level with index {}, there are {} levels.",
+            index, num_levels
+        ))
+    }
+
     pub(crate) fn missing_required_lump<NameT: fmt::Debug>(name: &NameT) -> Self {
-        ErrorKind::CorruptWad(format!("Missing required lump {:?}", name))
+        ErrorKind::MissingLump(format!("Missing required lump {:?}", name))
     }
 }
@@ -0,0 +1,268 @@
+//! Decoding of Boom's generalized linedef specials, which pack the trigger, speed and target of
+//! an action into the bit fields of the special type instead of looking it up in a table.
+
+use super::lock::{KeyType, Lock};
+use super::meta::{HeightDef, HeightEffectDef, HeightRef, MoveEffectDef, TriggerType};
+use super::types::{SpecialType, WadCoord};
+
+const FLOOR_BASE: SpecialType = 0x6000;
+const CEILING_BASE: SpecialType = 0x4000;
+const DOOR_BASE: SpecialType = 0x3c00;
+const LOCKED_DOOR_BASE: SpecialType = 0x3800;
+const LAST_GENERALIZED: SpecialType = 0x7fff;
+
+const TRIGGER_MASK: SpecialType = 0x0007;
+const SPEED_MASK: SpecialType = 0x0018;
+const SPEED_SHIFT: SpecialType = 3;
+
+const MODEL_MASK: SpecialType = 0x0020;
+const DIRECTION_MASK: SpecialType = 0x0040;
+const TARGET_MASK: SpecialType = 0x0380;
+const TARGET_SHIFT: SpecialType = 7;
+const CHANGE_MASK: SpecialType = 0x0c00;
+const CRUSH_MASK: SpecialType = 0x1000;
+
+const DOOR_KIND_MASK: SpecialType = 0x0060;
+const DOOR_KIND_SHIFT: SpecialType = 5;
+const DOOR_MONSTER_MASK: SpecialType = 0x0080;
+const DOOR_DELAY_MASK: SpecialType = 0x0300;
+const DOOR_DELAY_SHIFT: SpecialType = 8;
+
+const LOCKED_KIND_MASK: SpecialType = 0x0020;
+const LOCK_MASK: SpecialType = 0x01c0;
+const LOCK_SHIFT: SpecialType = 6;
+const LOCK_EITHER_KIND_MASK: SpecialType = 0x0200;
+
+/// Floor and ceiling speeds in WAD units per tic, indexed by the speed bits. Doors move twice as
+/// fast.
+const SPEEDS: [f32; 4] = [1.0, 2.0, 4.0, 8.0];
+const DOOR_DELAYS: [f32; 4] = [1.0, 4.0, 9.0, 30.0];
+const LOCKED_DOOR_DELAY: f32 = 4.0;
+const TICS_PER_SECOND: f32 = 35.0;
+
+/// The effect of a generalized linedef special, in the same terms as a `LinedefMetadata` entry.
+#[derive(Debug, Copy, Clone)]
+pub struct GeneralizedAction {
+    pub trigger: TriggerType,
+    pub only_once: bool,
+    pub monsters: bool,
+    pub move_effect: MoveEffectDef,
+    pub lock: Option<Lock>,
+}
+
+impl GeneralizedAction {
+    /// Decodes a generalized floor, ceiling or door special. Returns `None` for anything else,
+    /// including generalized actions whose targets aren't supported yet (lifts, stairs, crushers
+    /// and texture-relative targets).
+    pub fn decode(special: SpecialType) -> Option<GeneralizedAction> {
+        let (trigger, only_once) = decode_trigger(special);
+        let speed_index = ((special & SPEED_MASK) >> SPEED_SHIFT) as usize;
+        let mut lock = None;
+        let (monsters, move_effect) = if special > LAST_GENERALIZED {
+            return None;
+        } else if special >= FLOOR_BASE {
+            decode_floor(special, SPEEDS[speed_index])?
+        } else if special >= CEILING_BASE {
+            decode_ceiling(special, SPEEDS[speed_index])?
+        } else if special >= DOOR_BASE {
+            decode_door(special, SPEEDS[speed_index] * 2.0)
+        } else if special >= LOCKED_DOOR_BASE {
+            lock = Some(decode_lock(special));
+            (
+                false,
+                decode_locked_door(special, SPEEDS[speed_index] * 2.0),
+            )
+        } else {
+            return None;
+        };
+        Some(GeneralizedAction {
+            trigger,
+            only_once,
+            monsters,
+            move_effect,
+            lock,
+        })
+    }
+}
+
+fn decode_trigger(special: SpecialType) -> (TriggerType, bool) {
+    let bits = special & TRIGGER_MASK;
+    let trigger = match bits >> 1 {
+        0 => TriggerType::WalkOver,
+        1 => TriggerType::Switch,
+        2 => TriggerType::Gun,
+        _ => TriggerType::Push,
+    };
+    (trigger, bits & 1 == 0)
+}
+
+fn decode_floor(special: SpecialType, speed: f32) -> Option<(bool, MoveEffectDef)> {
+    let up = special & DIRECTION_MASK != 0;
+    let to = match (special & TARGET_MASK) >> TARGET_SHIFT {
+        0 => height(HeightRef::HighestFloor, 0),
+        1 => height(HeightRef::LowestFloor, 0),
+        2 if up => height(HeightRef::NextFloor, 0),
+        3 => height(HeightRef::LowestCeiling, 0),
+        4 => height(HeightRef::Ceiling, 0),
+        6 => height(HeightRef::Floor, signed(24, up)),
+        7 => height(HeightRef::Floor, signed(32, up)),
+        _ => return None,
+    };
+    Some((
+        monster_bit(special),
+        move_effect(speed, Some(to), None, special & CRUSH_MASK != 0),
+    ))
+}
+
+fn decode_ceiling(special: SpecialType, speed: f32) -> Option<(bool, MoveEffectDef)> {
+    let up = special & DIRECTION_MASK != 0;
+    let to = match (special & TARGET_MASK) >> TARGET_SHIFT {
+        0 => height(HeightRef::HighestCeiling, 0),
+        1 => height(HeightRef::LowestCeiling, 0),
+        3 => height(HeightRef::HighestFloor, 0),
+        4 => height(HeightRef::Floor, 0),
+        6 => height(HeightRef::Ceiling, signed(24, up)),
+        7 => height(HeightRef::Ceiling, signed(32, up)),
+        _ => return None,
+    };
+    Some((
+        monster_bit(special),
+        move_effect(speed, None, Some(to), special & CRUSH_MASK != 0),
+    ))
+}
+
+fn decode_door(special: SpecialType, speed: f32) -> (bool, MoveEffectDef) {
+    let open = height(HeightRef::LowestCeiling, -4);
+    let closed = height(HeightRef::Floor, 0);
+    let delay = DOOR_DELAYS[((special & DOOR_DELAY_MASK) >> DOOR_DELAY_SHIFT) as usize];
+    let (first, second, wait) = match (special & DOOR_KIND_MASK) >> DOOR_KIND_SHIFT {
+        0 => (open, Some(closed), delay),
+        1 => (open, None, 0.0),
+        2 => (closed, Some(open), delay),
+        _ => (closed, None, 0.0),
+    };
+    let mut effect = move_effect(speed, None, None, false);
+    effect.ceiling = Some(HeightEffectDef { first, second });
+    effect.wait = wait;
+    (special & DOOR_MONSTER_MASK != 0, effect)
+}
+
+fn decode_locked_door(special: SpecialType, speed: f32) -> MoveEffectDef {
+    let open = height(HeightRef::LowestCeiling, -4);
+    let mut effect = move_effect(speed, None, None, false);
+    effect.ceiling = Some(if special & LOCKED_KIND_MASK == 0 {
+        effect.wait = LOCKED_DOOR_DELAY;
+        HeightEffectDef {
+            first: open,
+            second: Some(height(HeightRef::Floor, 0)),
+        }
+    } else {
+        HeightEffectDef {
+            first: open,
+            second: None,
+        }
+    });
+    effect
+}
+
+fn decode_lock(special: SpecialType) -> Lock {
+    let either_kind = special & LOCK_EITHER_KIND_MASK != 0;
+    let key = match (special & LOCK_MASK) >> LOCK_SHIFT {
+        0 => return Lock::AnyKey,
+        1 => KeyType::RedCard,
+        2 => KeyType::BlueCard,
+        3 => KeyType::YellowCard,
+        4 => KeyType::RedSkull,
+        5 => KeyType::BlueSkull,
+        6 => KeyType::YellowSkull,
+        _ => return Lock::AllKeys { either_kind },
+    };
+    Lock::Key { key, either_kind }
+}
+
+/// Floors and ceilings reuse the model bit as a monster flag when they don't change textures.
+fn monster_bit(special: SpecialType) -> bool {
+    special & CHANGE_MASK == 0 && special & MODEL_MASK != 0
+}
+
+fn height(to: HeightRef, offset: WadCoord) -> HeightDef {
+    HeightDef { to, offset }
+}
+
+fn signed(offset: WadCoord, up: bool) -> WadCoord {
+    if up {
+        offset
+    } else {
+        -offset
+    }
+}
+
+fn move_effect(
+    units_per_tic: f32,
+    floor: Option<HeightDef>,
+    ceiling: Option<HeightDef>,
+    crush: bool,
+) -> MoveEffectDef {
+    let first_only = |first| HeightEffectDef {
+        first,
+        second: None,
+    };
+    MoveEffectDef {
+        floor: floor.map(first_only),
+        ceiling: ceiling.map(first_only),
+        repeat: false,
+        crush,
+        stairs: false,
+        wait: 0.0,
+        speed: units_per_tic * TICS_PER_SECOND / 100.0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::GeneralizedAction;
+    use crate::lock::{KeyType, Lock};
+    use crate::meta::{HeightRef, TriggerType};
+
+    #[test]
+    fn test_decode_generalized() {
+        assert!(GeneralizedAction::decode(1).is_none());
+        assert!(GeneralizedAction::decode(0x3000).is_none());
+
+        // Walk-once, normal speed, raise floor by 24.
+        let floor = GeneralizedAction::decode(0x6000 | 0x0008 | 0x0040 | (6 << 7)).unwrap();
+        assert!(floor.only_once);
+        let first = floor.move_effect.floor.unwrap().first;
+        match (floor.trigger, first.to) {
+            (TriggerType::WalkOver, HeightRef::Floor) => {}
+            other => panic!("unexpected floor action {:?}", other),
+        }
+        assert_eq!(first.offset, 24);
+        assert!(floor.move_effect.ceiling.is_none());
+
+        // Push-many, monster open-wait-close door with a 4 second delay.
+        let door = GeneralizedAction::decode(0x3c00 | 0x0007 | 0x0080 | (1 << 8)).unwrap();
+        assert!(!door.only_once);
+        assert!(door.monsters);
+        match door.trigger {
+            TriggerType::Push => {}
+            other => panic!("unexpected door trigger {:?}", other),
+        }
+        let ceiling = door.move_effect.ceiling.unwrap();
+        assert!(ceiling.second.is_some());
+        assert!((door.move_effect.wait - 4.0).abs() < 1e-6);
+        assert!(door.lock.is_none());
+
+        // Push-many open-stay door, locked by the blue skull key or keycard.
+        let locked = GeneralizedAction::decode(0x3800 | 0x0007 | 0x0020 | (5 << 6) | 0x0200);
+        let locked = locked.unwrap();
+        assert_eq!(
+            locked.lock,
+            Some(Lock::Key {
+                key: KeyType::BlueSkull,
+                either_kind: true,
+            })
+        );
+        assert!(locked.move_effect.ceiling.unwrap().second.is_none());
+    }
+}
@@ -1,5 +1,5 @@
 use super::errors::{ErrorKind, Result};
-use super::types::WadTextureHeader;
+use super::types::{Palette, WadTextureHeader};
 use byteorder::{LittleEndian, ReadBytesExt};
 use failchain::{ensure, ResultExt};
 use log::{debug, warn};
@@ -31,10 +31,31 @@ impl Image {
         })
     }
 
+    /// An opaque image of a single palette colour.
+    pub fn filled(width: usize, height: usize, index: u8) -> Result<Self> {
+        let mut image = Self::new(width, height)?;
+        for pixel in &mut image.pixels {
+            *pixel = u16::from(index);
+        }
+        Ok(image)
+    }
+
     pub fn new_from_header(header: &WadTextureHeader) -> Result<Self> {
         Self::new(header.width as usize, header.height as usize)
     }
 
+    /// Builds a composite texture (as defined in `TEXTURE1` and `TEXTURE2`) by drawing patches
+    /// onto a transparent canvas in order. Later patches are drawn over earlier ones, except
+    /// where they are transparent themselves, and anything outside the canvas is clipped.
+    pub fn compose(width: usize, height: usize, patches: &[PatchPlacement]) -> Result<Self> {
+        let mut image = Self::new(width, height)?;
+        for (i_patch, placement) in patches.iter().enumerate() {
+            // The canvas starts out transparent, so the first patch can be copied as is.
+            image.blit(placement.patch, placement.offset, i_patch == 0);
+        }
+        Ok(image)
+    }
+
     #[cfg_attr(feature = "cargo-clippy", allow(clippy::needless_range_loop))]
     pub fn from_buffer(buffer: &[u8]) -> Result<Self> {
         let mut reader = buffer;
@@ -281,4 +302,164 @@ impl Image {
     pub fn into_pixels(self) -> Vec<u16> {
         self.pixels
     }
+
+    /// Converts the image to RGBA using a palette. Pixels not covered by any run (the holes in
+    /// patches and sprites) become fully transparent black.
+    pub fn to_rgba(&self, palette: &Palette) -> RgbaImage {
+        let mut pixels = Vec::with_capacity(self.pixels.len() * 4);
+        for &pixel in &self.pixels {
+            if pixel & 0xff00 != 0 {
+                pixels.extend_from_slice(&[0, 0, 0, 0]);
+            } else {
+                let index = pixel as usize * 3;
+                pixels.extend_from_slice(&palette.0[index..index + 3]);
+                pixels.push(0xff);
+            }
+        }
+        RgbaImage {
+            width: self.width,
+            height: self.height,
+            x_offset: self.x_offset,
+            y_offset: self.y_offset,
+            pixels,
+        }
+    }
+
+    /// The mean colour of the image's opaque pixels looked up in a palette, or `None` if it's
+    /// entirely transparent.
+    pub fn average_color(&self, palette: &Palette) -> Option<[u8; 3]> {
+        let mut sums = [0u64; 3];
+        let mut count = 0u64;
+        for &pixel in self.pixels.iter().filter(|&&pixel| pixel & 0xff00 == 0) {
+            let index = pixel as usize * 3;
+            for (sum, &component) in sums.iter_mut().zip(&palette.0[index..index + 3]) {
+                *sum += u64::from(component);
+            }
+            count += 1;
+        }
+        if count == 0 {
+            return None;
+        }
+        Some([
+            (sums[0] / count) as u8,
+            (sums[1] / count) as u8,
+            (sums[2] / count) as u8,
+        ])
+    }
+}
+
+/// A patch and where to draw its top-left corner in a composite texture, see `Image::compose`.
+#[derive(Copy, Clone)]
+pub struct PatchPlacement<'a> {
+    pub patch: &'a Image,
+    pub offset: Vec2<isize>,
+}
+
+/// An `Image` with its colours looked up in a palette: four bytes per pixel, row by row.
+pub struct RgbaImage {
+    pub width: usize,
+    pub height: usize,
+    pub x_offset: isize,
+    pub y_offset: isize,
+    pub pixels: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Decodes a patch, sprite or other lump in DOOM's picture format, see `Image::from_buffer`.
+    pub fn from_patch(buffer: &[u8], palette: &Palette) -> Result<Self> {
+        Image::from_buffer(buffer).map(|image| image.to_rgba(palette))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Image, PatchPlacement, RgbaImage};
+    use crate::types::Palette;
+    use math::Vec2;
+
+    const CLEAR: u16 = 0xff00;
+
+    #[test]
+    fn test_patch_to_rgba() {
+        // A 2x3 patch with offsets (-1, 2). The first column has a run of two pixels starting at
+        // the top, the second a single pixel at the bottom.
+        let mut patch = vec![2, 0, 3, 0, 0xff, 0xff, 2, 0];
+        patch.extend_from_slice(&16u32.to_le_bytes());
+        patch.extend_from_slice(&23u32.to_le_bytes());
+        patch.extend_from_slice(&[0, 2, 0, 1, 2, 0, 0xff]);
+        patch.extend_from_slice(&[2, 1, 0, 3, 0, 0xff]);
+
+        let mut palette = Palette::default();
+        for index in 0..256 {
+            palette.0[index * 3] = index as u8;
+            palette.0[index * 3 + 2] = 0x80;
+        }
+
+        let image = RgbaImage::from_patch(&patch, &palette).unwrap();
+        assert_eq!((image.width, image.height), (2, 3));
+        assert_eq!((image.x_offset, image.y_offset), (-1, 2));
+        #[rustfmt::skip]
+        assert_eq!(
+            image.pixels,
+            vec![
+                1, 0, 0x80, 0xff,   0, 0, 0, 0,
+                2, 0, 0x80, 0xff,   0, 0, 0, 0,
+                0, 0, 0, 0,         3, 0, 0x80, 0xff,
+            ]
+        );
+
+        let image = Image::from_buffer(&patch).unwrap();
+        assert_eq!(image.average_color(&palette), Some([2, 0, 0x80]));
+        assert_eq!(Image::new(2, 2).unwrap().average_color(&palette), None);
+    }
+
+    #[test]
+    fn test_random_patches_dont_panic() {
+        let mut state = 0x9e37_79b9u32;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state
+        };
+        let palette = Palette::default();
+        for _ in 0..500 {
+            let len = random() as usize % 4097;
+            let mut buffer: Vec<u8> = (0..len).map(|_| random() as u8).collect();
+            // Keep the size in the header small half the time, so that parsing gets past it.
+            if buffer.len() >= 4 && random() % 2 == 0 {
+                buffer[1] = 0;
+                buffer[3] = 0;
+            }
+            let _ = Image::from_buffer(&buffer);
+            let _ = RgbaImage::from_patch(&buffer, &palette);
+        }
+    }
+
+    #[test]
+    fn test_compose() {
+        let mut holey = Image::new(2, 2).unwrap();
+        holey.pixels = vec![CLEAR, 1, 2, CLEAR];
+        let mut solid = Image::new(2, 2).unwrap();
+        solid.pixels = vec![5, 6, 7, 8];
+
+        // The second patch only covers the first column, clipped to the canvas, so the hole in
+        // the second column stays transparent.
+        let image = Image::compose(
+            3,
+            2,
+            &[
+                PatchPlacement {
+                    patch: &holey,
+                    offset: Vec2::new(0, 0),
+                },
+                PatchPlacement {
+                    patch: &solid,
+                    offset: Vec2::new(-1, 0),
+                },
+            ],
+        )
+        .unwrap();
+        assert_eq!(image.pixels(), &[6, 1, CLEAR, 8, CLEAR, CLEAR]);
+    }
 }
@@ -1,14 +1,23 @@
-use super::archive::Archive;
-use super::errors::Result;
-use super::types::{LightLevel, SectorId, VertexId, WadNode, WadSector};
-use super::types::{WadCoord, WadLinedef, WadSeg, WadSidedef, WadSubsector, WadThing, WadVertex};
-use super::util::from_wad_coords;
-use log::{error, info};
-use math::Pnt2f;
+use super::archive::{Archive, LumpBytes};
+use super::blockmap::Blockmap;
+use super::errors::{ErrorKind, Result};
+use super::reject::RejectMatrix;
+use super::types::{ChildId, LightLevel, LinedefId, SectorId, SectorTag, SegId, SubsectorId};
+use super::types::{ThingType, WadSubsector, WadThing, WadVertex};
+use super::types::{
+    VertexId, WadCoord, WadLinedef, WadName, WadNode, WadSector, WadSeg, WadSidedef,
+};
+use super::util::{from_wad_coords, parse_child_id};
+use super::visitor::{partition_line, SEG_TOLERANCE};
+use log::{error, info, warn};
+use math::{Line2f, Pnt2f};
 use std::cmp;
+use std::collections::HashMap;
 use std::mem;
+use std::ops::Range;
 use std::slice::Iter as SliceIter;
 use std::vec::Vec;
+use vec_map::VecMap;
 
 const THINGS_OFFSET: usize = 1;
 const LINEDEFS_OFFSET: usize = 2;
@@ -18,6 +27,63 @@ const SEGS_OFFSET: usize = 5;
 const SSECTORS_OFFSET: usize = 6;
 const NODES_OFFSET: usize = 7;
 const SECTORS_OFFSET: usize = 8;
+const REJECT_OFFSET: usize = 9;
+const BLOCKMAP_OFFSET: usize = 10;
+
+/// The lumps making up a level, read into memory so that the level can be parsed on another
+/// thread than the `Archive`'s.
+pub struct LevelLumps {
+    name: WadName,
+    things: LumpBytes,
+    linedefs: LumpBytes,
+    sidedefs: LumpBytes,
+    vertices: LumpBytes,
+    segs: LumpBytes,
+    subsectors: LumpBytes,
+    nodes: LumpBytes,
+    sectors: LumpBytes,
+    reject: Option<LumpBytes>,
+    blockmap: Option<LumpBytes>,
+}
+
+impl LevelLumps {
+    pub fn read(wad: &Archive, index: usize) -> Result<Self> {
+        let lump = wad.level_lump(index)?;
+        info!("Reading level data for '{}'...", lump.name());
+        let level_name = lump.name();
+        let start_index = lump.index();
+        let find = |offset, name: &[u8; 8]| {
+            wad.lump_by_index(start_index + offset)
+                .ok()
+                .filter(|lump| lump.name() == *name)
+        };
+        let required = |offset, name: &[u8; 8]| -> Result<LumpBytes> {
+            let lump = find(offset, name).ok_or_else(|| {
+                ErrorKind::missing_level_lump(
+                    level_name.as_ref(),
+                    &String::from_utf8_lossy(name).trim_end_matches('\0'),
+                )
+            })?;
+            lump.read_lump_bytes()
+        };
+        let optional = |offset, name: &[u8; 8]| {
+            find(offset, name).and_then(|lump| lump.read_lump_bytes().ok())
+        };
+        Ok(LevelLumps {
+            name: level_name,
+            things: required(THINGS_OFFSET, b"THINGS\0\0")?,
+            linedefs: required(LINEDEFS_OFFSET, b"LINEDEFS")?,
+            vertices: required(VERTICES_OFFSET, b"VERTEXES")?,
+            segs: required(SEGS_OFFSET, b"SEGS\0\0\0\0")?,
+            subsectors: required(SSECTORS_OFFSET, b"SSECTORS")?,
+            nodes: required(NODES_OFFSET, b"NODES\0\0\0")?,
+            sidedefs: required(SIDEDEFS_OFFSET, b"SIDEDEFS")?,
+            sectors: required(SECTORS_OFFSET, b"SECTORS\0")?,
+            reject: optional(REJECT_OFFSET, b"REJECT\0\0"),
+            blockmap: optional(BLOCKMAP_OFFSET, b"BLOCKMAP"),
+        })
+    }
+}
 
 pub struct Level {
     pub things: Vec<WadThing>,
@@ -28,37 +94,48 @@ pub struct Level {
     pub subsectors: Vec<WadSubsector>,
     pub nodes: Vec<WadNode>,
     pub sectors: Vec<WadSector>,
+
+    tagged_sectors: VecMap<Vec<SectorId>>,
+    thing_positions: HashMap<ThingType, Vec<Pnt2f>>,
+    blockmap: Blockmap,
+    reject: RejectMatrix,
 }
 
 impl Level {
     pub fn from_archive(wad: &Archive, index: usize) -> Result<Level> {
-        let lump = wad.level_lump(index)?;
-        info!("Reading level data for '{}'...", lump.name());
-        let start_index = lump.index();
-        let things = wad
-            .lump_by_index(start_index + THINGS_OFFSET)?
-            .decode_vec()?;
-        let linedefs = wad
-            .lump_by_index(start_index + LINEDEFS_OFFSET)?
-            .decode_vec()?;
-        let vertices = wad
-            .lump_by_index(start_index + VERTICES_OFFSET)?
-            .decode_vec()?;
-        let segs = wad.lump_by_index(start_index + SEGS_OFFSET)?.decode_vec()?;
-        let subsectors = wad
-            .lump_by_index(start_index + SSECTORS_OFFSET)?
-            .decode_vec()?;
-        let nodes = wad
-            .lump_by_index(start_index + NODES_OFFSET)?
-            .decode_vec()?;
-        let sidedefs = wad
-            .lump_by_index(start_index + SIDEDEFS_OFFSET)?
-            .decode_vec()?;
-        let sectors = wad
-            .lump_by_index(start_index + SECTORS_OFFSET)?
-            .decode_vec()?;
-
-        info!("Loaded level '{}':", lump.name());
+        Level::from_lumps(&LevelLumps::read(wad, index)?)
+    }
+
+    /// Parses a level from lumps already read from an archive, see `LevelLumps`.
+    pub fn from_lumps(lumps: &LevelLumps) -> Result<Level> {
+        let level_name = lumps.name;
+        let things: Vec<WadThing> = lumps.things.decode_vec()?;
+        let linedefs = lumps.linedefs.decode_vec()?;
+        let vertices = lumps.vertices.decode_vec()?;
+        let segs = lumps.segs.decode_vec()?;
+        let subsectors = lumps.subsectors.decode_vec()?;
+        let nodes = lumps.nodes.decode_vec()?;
+        let sidedefs = lumps.sidedefs.decode_vec()?;
+        let sectors: Vec<WadSector> = lumps.sectors.decode_vec()?;
+        let reject = lumps
+            .reject
+            .as_ref()
+            .map_or_else(Vec::new, |lump| lump.bytes().to_vec());
+        let reject = RejectMatrix::new(sectors.len(), reject);
+        if reject.is_empty() {
+            info!("Empty REJECT table, sight checks won't be rejected early.");
+        }
+        let blockmap = lumps
+            .blockmap
+            .as_ref()
+            .ok_or_else(|| ErrorKind::missing_level_lump(level_name.as_ref(), "BLOCKMAP").into())
+            .and_then(|lump| Blockmap::parse(lump.bytes()))
+            .unwrap_or_else(|error| {
+                warn!("{} Building a blockmap instead.", error);
+                Blockmap::build(&linedefs, &vertices)
+            });
+
+        info!("Loaded level '{}':", level_name);
         info!("    {:4} things", things.len());
         info!("    {:4} linedefs", linedefs.len());
         info!("    {:4} sidedefs", sidedefs.len());
@@ -68,6 +145,24 @@ impl Level {
         info!("    {:4} nodes", nodes.len());
         info!("    {:4} sectors", sectors.len());
 
+        let mut tagged_sectors = VecMap::new();
+        for (i_sector, sector) in sectors.iter().enumerate() {
+            if sector.tag > 0 {
+                tagged_sectors
+                    .entry(sector.tag as usize)
+                    .or_insert_with(Vec::new)
+                    .push(i_sector as SectorId);
+            }
+        }
+
+        let mut thing_positions = HashMap::new();
+        for thing in &things {
+            thing_positions
+                .entry(thing.thing_type)
+                .or_insert_with(Vec::new)
+                .push(from_wad_coords(thing.x, thing.y));
+        }
+
         Ok(Level {
             things,
             linedefs,
@@ -77,9 +172,58 @@ impl Level {
             subsectors,
             nodes,
             sectors,
+            tagged_sectors,
+            thing_positions,
+            blockmap,
+            reject,
         })
     }
 
+    /// Returns the ids of all the sectors with a given tag, which are the sectors targeted by
+    /// linedefs with that `sector_tag`. The zero tag is never matched.
+    pub fn sectors_with_tag(&self, tag: SectorTag) -> &[SectorId] {
+        self.tagged_sectors
+            .get(tag as usize)
+            .map_or(&[], |sector_ids| &sector_ids[..])
+    }
+
+    /// Iterates over every sector with its id, in the order of the SECTORS lump.
+    pub fn all_sectors(&self) -> impl Iterator<Item = (SectorId, &WadSector)> + '_ {
+        self.sectors
+            .iter()
+            .enumerate()
+            .map(|(i_sector, sector)| (i_sector as SectorId, sector))
+    }
+
+    /// Returns the positions (in world coordinates) of all the things of a given type, in the
+    /// order they appear in the level. Skill flags are not taken into account.
+    pub fn find_things(&self, thing_type: ThingType) -> &[Pnt2f] {
+        self.thing_positions
+            .get(&thing_type)
+            .map_or(&[], |positions| &positions[..])
+    }
+
+    pub fn blockmap(&self) -> &Blockmap {
+        &self.blockmap
+    }
+
+    /// The linedefs in one cell of the blockmap (see `Blockmap::cell`).
+    pub fn blockmap_cell(&self, block_x: i32, block_y: i32) -> &[LinedefId] {
+        self.blockmap.cell(block_x, block_y)
+    }
+
+    /// Returns true if the REJECT lump says that nothing in sector `to` can be seen from sector
+    /// `from`, so `check_sight` between them is sure to fail.
+    pub fn reject_quick(&self, from: SectorId, to: SectorId) -> bool {
+        self.reject.rejects(from, to)
+    }
+
+    /// Returns true if the level's REJECT lump is missing or all zeros, so `reject_quick` never
+    /// rejects anything.
+    pub fn reject_empty(&self) -> bool {
+        self.reject.is_empty()
+    }
+
     pub fn vertex(&self, id: VertexId) -> Option<Pnt2f> {
         self.vertices
             .get(id as usize)
@@ -150,6 +294,38 @@ impl Level {
         self.subsectors.get(index).cloned()
     }
 
+    pub fn num_subsectors(&self) -> usize {
+        self.subsectors.len()
+    }
+
+    /// Returns the range of segs which bound a subsector and the sector it's part of.
+    pub fn subsector(&self, id: SubsectorId) -> Option<Subsector> {
+        let subsector = self.subsectors.get(id as usize)?;
+        let first_seg = self.segs.get(subsector.first_seg as usize)?;
+        Some(Subsector {
+            segs: subsector.first_seg..subsector.first_seg + subsector.num_segs,
+            sector_id: self.seg_sidedef(first_seg)?.sector,
+        })
+    }
+
+    pub fn num_segs(&self) -> usize {
+        self.segs.len()
+    }
+
+    pub fn seg(&self, id: SegId) -> Option<Seg> {
+        self.segs.get(id as usize).map(|seg| Seg {
+            start_vertex: seg.start_vertex,
+            end_vertex: seg.end_vertex,
+            linedef_id: seg.linedef,
+            side: if seg.direction == 0 {
+                Side::Front
+            } else {
+                Side::Back
+            },
+            offset: seg.offset,
+        })
+    }
+
     pub fn ssector_segs(&self, ssector: WadSubsector) -> Option<&[WadSeg]> {
         let start = ssector.first_seg as usize;
         let end = start + ssector.num_segs as usize;
@@ -160,6 +336,141 @@ impl Level {
         }
     }
 
+    /// Finds the sector containing a point (in world coordinates) by walking down the BSP tree.
+    pub fn sector_at(&self, pos: Pnt2f) -> Option<&WadSector> {
+        let mut child_id = (self.nodes.len() - 1) as ChildId;
+        loop {
+            let (id, is_leaf) = parse_child_id(child_id);
+            if is_leaf {
+                let segs = self
+                    .ssector(id)
+                    .and_then(|subsector| self.ssector_segs(subsector))
+                    .and_then(|segs| if segs.is_empty() { None } else { Some(segs) });
+                let segs = if let Some(segs) = segs {
+                    segs
+                } else {
+                    return None;
+                };
+                let sector = if let Some(sector) = self.seg_sector(&segs[0]) {
+                    sector
+                } else {
+                    return None;
+                };
+                return if segs
+                    .iter()
+                    .filter_map(|seg| self.seg_vertices(seg))
+                    .map(|(v1, v2)| Line2f::from_two_points(v1, v2))
+                    .all(|line| line.signed_distance(pos) <= SEG_TOLERANCE)
+                {
+                    Some(sector)
+                } else {
+                    None
+                };
+            } else {
+                let node = if let Some(node) = self.nodes.get(id) {
+                    node
+                } else {
+                    return None;
+                };
+                let partition = partition_line(node);
+                if partition.signed_distance(pos) > 0.0f32 {
+                    child_id = node.left;
+                } else {
+                    child_id = node.right;
+                }
+            }
+        }
+    }
+
+    /// Returns whether the point at height `to_z` above `to` can be seen from `from_z` above
+    /// `from`, like DOOM's `P_CheckSight`. The line of sight is blocked by one-sided walls and by
+    /// any two-sided wall whose opening it passes above or below.
+    ///
+    /// Heights are in world units; `heights` gives a sector's current floor and ceiling, so that
+    /// moving floors and opened doors are taken into account. Only the BSP nodes on the line's
+    /// side(s) are walked, so most of the level is never looked at.
+    pub fn check_sight<F>(&self, from: Pnt2f, from_z: f32, to: Pnt2f, to_z: f32, heights: F) -> bool
+    where
+        F: Fn(SectorId) -> (f32, f32),
+    {
+        let sight = Line2f::from_two_points(from, to);
+        if sight.length == 0.0 || self.nodes.is_empty() {
+            return true;
+        }
+        let sight = SightLine {
+            line: sight,
+            from,
+            to,
+            from_z,
+            to_z,
+        };
+        self.cross_bsp_node((self.nodes.len() - 1) as ChildId, &sight, &heights)
+    }
+
+    fn cross_bsp_node<F>(&self, child_id: ChildId, sight: &SightLine, heights: &F) -> bool
+    where
+        F: Fn(SectorId) -> (f32, f32),
+    {
+        let (id, is_leaf) = parse_child_id(child_id);
+        if is_leaf {
+            return self.cross_subsector(id, sight, heights);
+        }
+        let node = match self.nodes.get(id) {
+            Some(node) => node,
+            None => return true,
+        };
+        let partition = partition_line(node);
+        let side = |pos| {
+            if partition.signed_distance(pos) > 0.0 {
+                node.left
+            } else {
+                node.right
+            }
+        };
+        let (from_side, to_side) = (side(sight.from), side(sight.to));
+        self.cross_bsp_node(from_side, sight, heights)
+            && (from_side == to_side || self.cross_bsp_node(to_side, sight, heights))
+    }
+
+    fn cross_subsector<F>(&self, id: usize, sight: &SightLine, heights: &F) -> bool
+    where
+        F: Fn(SectorId) -> (f32, f32),
+    {
+        let segs = match self
+            .ssector(id)
+            .and_then(|ssector| self.ssector_segs(ssector))
+        {
+            Some(segs) => segs,
+            None => return true,
+        };
+        for seg in segs {
+            let offset = match self
+                .seg_vertices(seg)
+                .map(|(v1, v2)| Line2f::from_two_points(v1, v2))
+                .and_then(|seg_line| sight.line.segment_intersect_offset(&seg_line))
+            {
+                Some(offset) => offset,
+                None => continue,
+            };
+            let sectors = self
+                .seg_sidedef(seg)
+                .and_then(|front| Some((front.sector, self.seg_back_sidedef(seg)?.sector)));
+            let (front, back) = match sectors {
+                Some(sectors) => sectors,
+                None => return false,
+            };
+            let ((front_floor, front_ceiling), (back_floor, back_ceiling)) =
+                (heights(front), heights(back));
+            let bottom = front_floor.max(back_floor);
+            let top = front_ceiling.min(back_ceiling);
+            let z = sight.from_z + (sight.to_z - sight.from_z) * offset / sight.line.length;
+            if top <= bottom || z < bottom || z > top {
+                return false;
+            }
+        }
+        true
+    }
+
     pub fn sector_id(&self, sector: &WadSector) -> SectorId {
         let sector_id = (sector as *const _ as usize - self.sectors.as_ptr() as usize)
             / mem::size_of::<WadSector>();
@@ -175,6 +486,44 @@ impl Level {
         }
     }
 
+    /// Returns the sectors which make up a staircase starting at `start`, in order.
+    ///
+    /// Each step is the sector behind the first two-sided linedef facing out of the previous
+    /// step which shares its floor flat, like DOOM's `EV_BuildStairs`.
+    pub fn stair_sectors(&self, start: SectorId) -> Vec<SectorId> {
+        let floor_texture = match self.sectors.get(start as usize) {
+            Some(sector) => sector.floor_texture,
+            None => return Vec::new(),
+        };
+        let mut steps = vec![start];
+        let mut current = start;
+        loop {
+            let next = self.linedefs.iter().find_map(|linedef| {
+                if !linedef.is_two_sided() {
+                    return None;
+                }
+                let front = self.right_sidedef(linedef)?.sector;
+                let back = self.left_sidedef(linedef)?.sector;
+                let back_sector = self.sectors.get(back as usize)?;
+                if front == current
+                    && back_sector.floor_texture == floor_texture
+                    && !steps.contains(&back)
+                {
+                    Some(back)
+                } else {
+                    None
+                }
+            });
+            match next {
+                Some(next) => {
+                    steps.push(next);
+                    current = next;
+                }
+                None => return steps,
+            }
+        }
+    }
+
     pub fn sector_min_light(&self, of: &WadSector) -> LightLevel {
         self.adjacent_sectors(of)
             .map(|sector| sector.light)
@@ -212,6 +561,40 @@ impl Level {
     }
 }
 
+/// The line of sight being traced by `Level::check_sight`.
+struct SightLine {
+    line: Line2f,
+    from: Pnt2f,
+    to: Pnt2f,
+    from_z: f32,
+    to_z: f32,
+}
+
+/// A convex subsector of the BSP tree, see `Level::subsector`.
+#[derive(Clone, Debug)]
+pub struct Subsector {
+    pub segs: Range<SegId>,
+    pub sector_id: SectorId,
+}
+
+/// Which side of its linedef a seg runs along: the front (right) or back (left) sidedef.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Side {
+    Front,
+    Back,
+}
+
+/// A seg (a piece of a linedef bounding a subsector), see `Level::seg`.
+#[derive(Copy, Clone, Debug)]
+pub struct Seg {
+    pub start_vertex: VertexId,
+    pub end_vertex: VertexId,
+    pub linedef_id: LinedefId,
+    pub side: Side,
+    /// Distance along the linedef to the start of the seg.
+    pub offset: u16,
+}
+
 #[derive(Copy, Clone, Debug)]
 pub struct NeighbourHeights {
     pub lowest_floor: WadCoord,
@@ -1,25 +1,39 @@
 mod archive;
+mod blockmap;
 mod errors;
+mod generalized;
 mod image;
 mod level;
 mod light;
+mod lock;
 mod meta;
 mod name;
+mod reject;
+mod skill;
+mod sprite;
+mod switches;
 mod visitor;
 
 pub mod tex;
 pub mod types;
 pub mod util;
 
-pub use self::archive::Archive;
+pub use self::archive::{Archive, LumpBytes};
+pub use self::blockmap::Blockmap;
 pub use self::errors::{Error, ErrorKind, Result};
-pub use self::image::Image;
-pub use self::level::Level;
+pub use self::generalized::GeneralizedAction;
+pub use self::image::{Image, PatchPlacement, RgbaImage};
+pub use self::level::{Level, LevelLumps, Seg, Side, Subsector};
 pub use self::light::{LightEffect, LightEffectKind, LightInfo};
-pub use self::meta::{MoveEffectDef, SkyMetadata, ThingMetadata, TriggerType, WadMetadata};
+pub use self::lock::{KeyType, Lock};
+pub use self::meta::{
+    ExitEffectDef, MoveEffectDef, SkyMetadata, ThingMetadata, TriggerType, WadMetadata,
+};
 pub use self::name::WadName;
-pub use self::tex::{OpaqueImage, TextureDirectory, TransparentImage};
+pub use self::skill::{Difficulty, SKILL_THING_FLAGS};
+pub use self::switches::{SwitchGame, SwitchPair, SwitchRegistry};
+pub use self::tex::{OpaqueImage, TextureDirectory, TransparentImage, ValidationWarning};
 pub use self::visitor::{
-    Branch, Decor, LevelAnalysis, LevelVisitor, LevelWalker, Marker, MoveEffect, ObjectId, SkyPoly,
-    SkyQuad, StaticPoly, StaticQuad, Trigger,
+    Branch, Decor, DynamicLight, LevelAnalysis, LevelVisitor, LevelWalker, Marker, MoveEffect,
+    ObjectId, SkyPoly, SkyQuad, StaticPoly, StaticQuad, ThingSpawn, Trigger,
 };
@@ -0,0 +1,161 @@
+//! Keys and the locked doors which need them.
+
+use super::types::ThingType;
+
+/// DOOM's six keys: a keycard and a skull key in each of three colors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeyType {
+    BlueCard,
+    RedCard,
+    YellowCard,
+    BlueSkull,
+    RedSkull,
+    YellowSkull,
+}
+
+impl KeyType {
+    pub const ALL: [KeyType; 6] = [
+        KeyType::BlueCard,
+        KeyType::RedCard,
+        KeyType::YellowCard,
+        KeyType::BlueSkull,
+        KeyType::RedSkull,
+        KeyType::YellowSkull,
+    ];
+
+    /// The key a thing gives when picked up, if it is one.
+    pub fn from_thing(thing_type: ThingType) -> Option<KeyType> {
+        Some(match thing_type {
+            5 => KeyType::BlueCard,
+            13 => KeyType::RedCard,
+            6 => KeyType::YellowCard,
+            40 => KeyType::BlueSkull,
+            38 => KeyType::RedSkull,
+            39 => KeyType::YellowSkull,
+            _ => return None,
+        })
+    }
+
+    /// The other key of the same color.
+    pub fn other_kind(self) -> KeyType {
+        match self {
+            KeyType::BlueCard => KeyType::BlueSkull,
+            KeyType::RedCard => KeyType::RedSkull,
+            KeyType::YellowCard => KeyType::YellowSkull,
+            KeyType::BlueSkull => KeyType::BlueCard,
+            KeyType::RedSkull => KeyType::RedCard,
+            KeyType::YellowSkull => KeyType::YellowCard,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyType::BlueCard => "blue keycard",
+            KeyType::RedCard => "red keycard",
+            KeyType::YellowCard => "yellow keycard",
+            KeyType::BlueSkull => "blue skull key",
+            KeyType::RedSkull => "red skull key",
+            KeyType::YellowSkull => "yellow skull key",
+        }
+    }
+
+    /// The key's color, as on the status bar and automap.
+    pub fn color(self) -> [f32; 3] {
+        match self {
+            KeyType::BlueCard => [0.0, 0.0, 1.0],
+            KeyType::RedCard => [1.0, 0.0, 0.0],
+            KeyType::YellowCard => [1.0, 1.0, 0.0],
+            KeyType::BlueSkull => [0.0, 0.6, 1.0],
+            KeyType::RedSkull => [1.0, 0.4, 0.4],
+            KeyType::YellowSkull => [1.0, 0.8, 0.4],
+        }
+    }
+}
+
+/// The keys needed to use a locked linedef.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Lock {
+    /// Any key at all will do.
+    AnyKey,
+    /// The given key, or when `either_kind` is set, also the other key of the same color.
+    Key { key: KeyType, either_kind: bool },
+    /// Every key, or when `either_kind` is set, one of each color.
+    AllKeys { either_kind: bool },
+}
+
+impl Lock {
+    /// The lock of a classic DOOM door, from the `lock` index in the metadata: blue, red or
+    /// yellow, where either the keycard or the skull key opens the door.
+    pub fn classic(index: u8) -> Option<Lock> {
+        let key = match index {
+            0 => KeyType::BlueCard,
+            1 => KeyType::RedCard,
+            2 => KeyType::YellowCard,
+            _ => return None,
+        };
+        Some(Lock::Key {
+            key,
+            either_kind: true,
+        })
+    }
+
+    /// Whether a player holding the keys for which `has` returns true can open the lock.
+    pub fn is_opened_by<F>(self, has: F) -> bool
+    where
+        F: Fn(KeyType) -> bool,
+    {
+        let has_key =
+            |key: KeyType, either_kind| has(key) || (either_kind && has(key.other_kind()));
+        match self {
+            Lock::AnyKey => KeyType::ALL.iter().any(|&key| has(key)),
+            Lock::Key { key, either_kind } => has_key(key, either_kind),
+            Lock::AllKeys { either_kind } => {
+                KeyType::ALL.iter().all(|&key| has_key(key, either_kind))
+            }
+        }
+    }
+
+    /// The message shown when trying to open the lock without the right keys.
+    pub fn message(self) -> String {
+        match self {
+            Lock::AnyKey => "Any key will open this door".to_owned(),
+            Lock::Key {
+                key,
+                either_kind: false,
+            } => format!("You need a {} to open this door", key.name()),
+            Lock::Key { key, .. } => {
+                let name = key.name();
+                format!(
+                    "You need a {} key to open this door",
+                    &name[..name.find(' ').unwrap_or_else(|| name.len())]
+                )
+            }
+            Lock::AllKeys { .. } => "You need all the keys to open this door".to_owned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{KeyType, Lock};
+
+    #[test]
+    fn test_lock_is_opened_by() {
+        let held = [KeyType::RedSkull];
+        let has = |key| held.contains(&key);
+        assert!(Lock::classic(1).unwrap().is_opened_by(has));
+        assert!(!Lock::classic(0).unwrap().is_opened_by(has));
+        assert!(Lock::AnyKey.is_opened_by(has));
+        assert!(!Lock::Key {
+            key: KeyType::RedCard,
+            either_kind: false,
+        }
+        .is_opened_by(has));
+        assert!(!Lock::AllKeys { either_kind: true }.is_opened_by(has));
+        assert!(Lock::AllKeys { either_kind: true }.is_opened_by(|key| key != KeyType::RedCard));
+        assert_eq!(
+            Lock::classic(2).unwrap().message(),
+            "You need a yellow key to open this door"
+        );
+    }
+}
@@ -30,6 +30,12 @@ pub struct AnimationMetadata {
     pub walls: Vec<Vec<WadName>>,
 }
 
+#[derive(Debug, Deserialize, Copy, Clone)]
+pub struct ThingLightMetadata {
+    pub radius: f32,
+    pub color: [f32; 3],
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ThingMetadata {
     pub thing_type: ThingType,
@@ -38,6 +44,7 @@ pub struct ThingMetadata {
     pub sequence: String,
     pub hanging: bool,
     pub radius: u32,
+    pub light: Option<ThingLightMetadata>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -93,6 +100,12 @@ pub struct MoveEffectDef {
     #[serde(default = "Default::default")]
     pub repeat: bool,
 
+    #[serde(default = "Default::default")]
+    pub crush: bool,
+
+    #[serde(default = "Default::default")]
+    pub stairs: bool,
+
     #[serde(default = "Default::default")]
     pub wait: f32,
 
@@ -125,6 +138,10 @@ pub struct LinedefMetadata {
 
     #[serde(rename = "exit")]
     pub exit_effect: Option<ExitEffectDef>,
+
+    /// Which key color locks the linedef (0 blue, 1 red, 2 yellow), see `Lock::classic`.
+    #[serde(default = "Default::default")]
+    pub lock: Option<u8>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -146,7 +163,7 @@ impl WadMetadata {
         let path = path.as_ref();
         File::open(path)
             .and_then(|mut file| file.read_to_string(&mut contents))
-            .chain_err(|| ErrorKind::on_metadata_read())?;
+            .chain_err(ErrorKind::on_metadata_read)?;
         WadMetadata::from_text(&contents)
     }
 
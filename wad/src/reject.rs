@@ -0,0 +1,68 @@
+//! The REJECT lump: a precomputed table of which sectors can't possibly see each other, letting
+//! most sight checks be skipped.
+
+use super::types::SectorId;
+
+pub struct RejectMatrix {
+    num_sectors: usize,
+    bits: Vec<u8>,
+    /// False when no bits are set, as in the null tables some editors write.
+    active: bool,
+}
+
+impl RejectMatrix {
+    /// Wraps the bytes of a REJECT lump for a level with `num_sectors` sectors.
+    ///
+    /// Bit `a * num_sectors + b` (counting from the lowest bit of the first byte) is set when
+    /// sector `b` can't be seen from sector `a`. Many PWADs ship an empty or zeroed lump, and
+    /// some a truncated one; any missing bits are taken to be zero, rejecting nothing.
+    pub(crate) fn new(num_sectors: usize, bits: Vec<u8>) -> Self {
+        let active = bits.iter().any(|&byte| byte != 0);
+        RejectMatrix {
+            num_sectors,
+            bits,
+            active,
+        }
+    }
+
+    /// Whether the matrix rejects nothing, so there's no point consulting it.
+    pub fn is_empty(&self) -> bool {
+        !self.active
+    }
+
+    /// Returns true if nothing in sector `to` can be seen from sector `from`. False doesn't mean
+    /// it can, only that a full sight check is needed to tell.
+    pub fn rejects(&self, from: SectorId, to: SectorId) -> bool {
+        let (from, to) = (from as usize, to as usize);
+        if !self.active || from >= self.num_sectors || to >= self.num_sectors {
+            return false;
+        }
+        let bit = from * self.num_sectors + to;
+        self.bits
+            .get(bit / 8)
+            .map_or(false, |&byte| byte & (1 << (bit % 8)) != 0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RejectMatrix;
+
+    #[test]
+    fn test_reject_matrix() {
+        // Three sectors: 0 and 2 can't see each other (bits 2 and 6).
+        let reject = RejectMatrix::new(3, vec![0b0100_0100]);
+        assert!(reject.rejects(0, 2));
+        assert!(reject.rejects(2, 0));
+        assert!(!reject.rejects(0, 1));
+        assert!(!reject.rejects(1, 2));
+        assert!(!reject.rejects(3, 0));
+
+        assert!(!reject.is_empty());
+
+        let empty = RejectMatrix::new(3, Vec::new());
+        assert!(empty.is_empty());
+        assert!(!empty.rejects(0, 2));
+        assert!(RejectMatrix::new(3, vec![0, 0]).is_empty());
+    }
+}
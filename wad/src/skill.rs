@@ -0,0 +1,43 @@
+use super::types::ThingFlags;
+
+/// The thing flag bits which select the skill levels a thing appears on.
+pub const SKILL_THING_FLAGS: ThingFlags = 0x0007;
+
+/// DOOM's skill levels, from "I'm too young to die" to "Nightmare!".
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Baby,
+    Easy,
+    Medium,
+    Hard,
+    Nightmare,
+}
+
+impl Difficulty {
+    /// Converts a 1-based skill number, as used by `-skill` in vanilla DOOM.
+    pub fn from_skill(skill: u8) -> Option<Difficulty> {
+        Some(match skill {
+            1 => Difficulty::Baby,
+            2 => Difficulty::Easy,
+            3 => Difficulty::Medium,
+            4 => Difficulty::Hard,
+            5 => Difficulty::Nightmare,
+            _ => return None,
+        })
+    }
+
+    /// The thing flags a thing must have (any of) to be spawned on this skill level.
+    pub fn thing_flags_mask(self) -> ThingFlags {
+        match self {
+            Difficulty::Baby | Difficulty::Easy => 0x0001,
+            Difficulty::Medium => 0x0002,
+            Difficulty::Hard | Difficulty::Nightmare => 0x0004,
+        }
+    }
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Difficulty::Hard
+    }
+}
@@ -0,0 +1,88 @@
+//! DOOM's sprite rotation naming scheme.
+//!
+//! Sprite lumps are named with a four letter prefix, a frame letter and a rotation digit, like
+//! `TROOA1`. Rotation `0` means the frame looks the same from every angle. Rotations 2-4 can be
+//! shared with their mirror images 8-6 by naming the lump with a second frame and rotation pair:
+//! `TROOA2A8` is rotation 2 as is and rotation 8 flipped horizontally.
+
+use super::types::WadName;
+
+/// Finds the sprite lump for a frame of `base` seen from `angle` (1-8, with 1 being the front
+/// and 0 meaning any angle), given a predicate telling whether a lump exists.
+///
+/// Returns the lump name and whether it needs to be flipped horizontally.
+pub(crate) fn sprite_rotation<F>(
+    base: WadName,
+    frame: u8,
+    angle: u8,
+    exists: F,
+) -> Option<(WadName, bool)>
+where
+    F: Fn(&WadName) -> bool,
+{
+    let mut prefix = base;
+    prefix.push(frame).ok()?;
+
+    // Single rotation sprites are used regardless of angle.
+    if let Some(name) = with_rotation(prefix, b'0').filter(&exists) {
+        return Some((name, false));
+    }
+
+    let angle = if angle == 0 { 1 } else { angle };
+    if angle > 8 {
+        return None;
+    }
+    let rotation = b'0' + angle;
+    let single = with_rotation(prefix, rotation)?;
+    if exists(&single) {
+        return Some((single, false));
+    }
+
+    // Look for lumps shared with the mirror image, with this rotation either first or second.
+    for other in b'1'..=b'8' {
+        let first = with_rotation(with_rotation(prefix, rotation)?, frame)
+            .and_then(|name| with_rotation(name, other));
+        if let Some(name) = first.filter(&exists) {
+            return Some((name, false));
+        }
+        let second = with_rotation(with_rotation(prefix, other)?, frame)
+            .and_then(|name| with_rotation(name, rotation));
+        if let Some(name) = second.filter(&exists) {
+            return Some((name, true));
+        }
+    }
+    None
+}
+
+fn with_rotation(mut name: WadName, byte: u8) -> Option<WadName> {
+    name.push(byte).ok().map(|_| name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::sprite_rotation;
+    use crate::types::WadName;
+
+    fn name(name: &str) -> WadName {
+        WadName::from_bytes(name.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_sprite_rotation() {
+        let lumps = ["TROOA1", "TROOA2A8", "TROOA3A7", "BAR1A0"]
+            .iter()
+            .map(|lump| name(lump))
+            .collect::<Vec<_>>();
+        let find = |base, frame, angle| {
+            sprite_rotation(name(base), frame, angle, |lump| lumps.contains(lump))
+        };
+
+        assert_eq!(find("TROO", b'A', 1), Some((name("TROOA1"), false)));
+        assert_eq!(find("TROO", b'A', 0), Some((name("TROOA1"), false)));
+        assert_eq!(find("TROO", b'A', 2), Some((name("TROOA2A8"), false)));
+        assert_eq!(find("TROO", b'A', 7), Some((name("TROOA3A7"), true)));
+        assert_eq!(find("TROO", b'A', 5), None);
+        assert_eq!(find("TROO", b'B', 1), None);
+        assert_eq!(find("BAR1", b'A', 6), Some((name("BAR1A0"), false)));
+    }
+}
@@ -0,0 +1,192 @@
+//! Switch textures: the pairs of wall textures a switch alternates between when used.
+
+use super::archive::Archive;
+use super::errors::Result;
+use super::name::WadName;
+use log::warn;
+use std::collections::HashMap;
+
+/// The size of an entry in a SWITCHES lump: two nine byte, null terminated texture names and the
+/// game they're for.
+const SWITCHES_ENTRY_SIZE: usize = 20;
+
+/// Which game a pair of switch textures first appears in. Pairs from later games are only listed
+/// in the WADs which have their textures.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SwitchGame {
+    Shareware,
+    Registered,
+    Commercial,
+    /// Any other value in a SWITCHES lump, kept as is.
+    Other(u16),
+}
+
+impl SwitchGame {
+    fn from_episode(episode: u16) -> Self {
+        match episode {
+            1 => SwitchGame::Shareware,
+            2 => SwitchGame::Registered,
+            3 => SwitchGame::Commercial,
+            other => SwitchGame::Other(other),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SwitchPair {
+    pub off: WadName,
+    pub on: WadName,
+    pub game: SwitchGame,
+}
+
+/// All the switch texture pairs of a WAD, from its SWITCHES lump or else DOOM's built-in list.
+pub struct SwitchRegistry {
+    pairs: Vec<SwitchPair>,
+    toggled: HashMap<WadName, WadName>,
+}
+
+impl SwitchRegistry {
+    /// Reads the WAD's SWITCHES lump (a Boom extension), falling back to `doom` without one.
+    pub fn from_archive(wad: &Archive) -> Result<SwitchRegistry> {
+        Ok(match wad.named_lump(b"SWITCHES")? {
+            Some(lump) => SwitchRegistry::from_lump(&lump.read_bytes()?),
+            None => SwitchRegistry::doom(),
+        })
+    }
+
+    /// Parses a SWITCHES lump. The list ends at an entry for game zero; entries with names which
+    /// aren't valid texture names are skipped.
+    pub fn from_lump(data: &[u8]) -> SwitchRegistry {
+        let mut pairs = Vec::new();
+        for entry in data.chunks_exact(SWITCHES_ENTRY_SIZE) {
+            let episode = u16::from_le_bytes([entry[18], entry[19]]);
+            if episode == 0 {
+                break;
+            }
+            match (
+                WadName::from_bytes(&entry[..9]),
+                WadName::from_bytes(&entry[9..18]),
+            ) {
+                (Ok(off), Ok(on)) => pairs.push(SwitchPair {
+                    off,
+                    on,
+                    game: SwitchGame::from_episode(episode),
+                }),
+                (Err(error), _) | (_, Err(error)) => {
+                    warn!("Skipping switch {}: {}", pairs.len(), error)
+                }
+            }
+        }
+        SwitchRegistry::new(pairs)
+    }
+
+    /// The switches built into DOOM and DOOM II.
+    pub fn doom() -> SwitchRegistry {
+        SwitchRegistry::new(
+            DOOM_SWITCHES
+                .iter()
+                .map(|&(off, on, episode)| SwitchPair {
+                    off: off.parse().expect("invalid built-in switch texture"),
+                    on: on.parse().expect("invalid built-in switch texture"),
+                    game: SwitchGame::from_episode(episode),
+                })
+                .collect(),
+        )
+    }
+
+    fn new(pairs: Vec<SwitchPair>) -> SwitchRegistry {
+        let mut toggled = HashMap::with_capacity(pairs.len() * 2);
+        for pair in &pairs {
+            toggled.insert(pair.off, pair.on);
+            toggled.insert(pair.on, pair.off);
+        }
+        SwitchRegistry { pairs, toggled }
+    }
+
+    pub fn pairs(&self) -> &[SwitchPair] {
+        &self.pairs
+    }
+
+    /// The texture a switch showing `texture` changes to when used, whichever way it is.
+    pub fn toggle(&self, texture: WadName) -> Option<WadName> {
+        self.toggled.get(&texture).cloned()
+    }
+}
+
+/// DOOM's `alphSwitchList`: off texture, on texture and game (1 for shareware, 2 for registered
+/// and 3 for DOOM II).
+const DOOM_SWITCHES: &[(&str, &str, u16)] = &[
+    ("SW1BRCOM", "SW2BRCOM", 1),
+    ("SW1BRN1", "SW2BRN1", 1),
+    ("SW1BRN2", "SW2BRN2", 1),
+    ("SW1BRNGN", "SW2BRNGN", 1),
+    ("SW1BROWN", "SW2BROWN", 1),
+    ("SW1COMM", "SW2COMM", 1),
+    ("SW1COMP", "SW2COMP", 1),
+    ("SW1DIRT", "SW2DIRT", 1),
+    ("SW1EXIT", "SW2EXIT", 1),
+    ("SW1GRAY", "SW2GRAY", 1),
+    ("SW1GRAY1", "SW2GRAY1", 1),
+    ("SW1METAL", "SW2METAL", 1),
+    ("SW1PIPE", "SW2PIPE", 1),
+    ("SW1SLAD", "SW2SLAD", 1),
+    ("SW1STARG", "SW2STARG", 1),
+    ("SW1STON1", "SW2STON1", 1),
+    ("SW1STON2", "SW2STON2", 1),
+    ("SW1STONE", "SW2STONE", 1),
+    ("SW1STRTN", "SW2STRTN", 1),
+    ("SW1BLUE", "SW2BLUE", 2),
+    ("SW1CMT", "SW2CMT", 2),
+    ("SW1GARG", "SW2GARG", 2),
+    ("SW1GSTON", "SW2GSTON", 2),
+    ("SW1HOT", "SW2HOT", 2),
+    ("SW1LION", "SW2LION", 2),
+    ("SW1SATYR", "SW2SATYR", 2),
+    ("SW1SKIN", "SW2SKIN", 2),
+    ("SW1VINE", "SW2VINE", 2),
+    ("SW1WOOD", "SW2WOOD", 2),
+    ("SW1PANEL", "SW2PANEL", 3),
+    ("SW1ROCK", "SW2ROCK", 3),
+    ("SW1MET2", "SW2MET2", 3),
+    ("SW1WDMET", "SW2WDMET", 3),
+    ("SW1BRIK", "SW2BRIK", 3),
+    ("SW1MOD1", "SW2MOD1", 3),
+    ("SW1ZIM", "SW2ZIM", 3),
+    ("SW1STON6", "SW2STON6", 3),
+    ("SW1TEK", "SW2TEK", 3),
+    ("SW1MARB", "SW2MARB", 3),
+    ("SW1SKULL", "SW2SKULL", 3),
+];
+
+#[cfg(test)]
+mod test {
+    use super::{SwitchGame, SwitchRegistry};
+    use crate::name::WadName;
+
+    fn name(name: &str) -> WadName {
+        name.parse().unwrap()
+    }
+
+    #[test]
+    fn test_switches_lump() {
+        let mut lump = Vec::new();
+        for &(off, on, episode) in &[("SW1TEST", "SW2TEST", 3u16), ("", "", 0), ("A", "B", 1)] {
+            for texture in &[off, on] {
+                let mut bytes = [0u8; 9];
+                bytes[..texture.len()].copy_from_slice(texture.as_bytes());
+                lump.extend_from_slice(&bytes);
+            }
+            lump.extend_from_slice(&episode.to_le_bytes());
+        }
+        let switches = SwitchRegistry::from_lump(&lump);
+        assert_eq!(switches.pairs().len(), 1);
+        assert_eq!(switches.pairs()[0].game, SwitchGame::Commercial);
+        assert_eq!(switches.toggle(name("SW1TEST")), Some(name("SW2TEST")));
+        assert_eq!(switches.toggle(name("SW2TEST")), Some(name("SW1TEST")));
+        assert_eq!(switches.toggle(name("A")), None);
+
+        let doom = SwitchRegistry::doom();
+        assert_eq!(doom.pairs().len(), 40);
+        assert_eq!(doom.toggle(name("sw2exit")), Some(name("SW1EXIT")));
+    }
+}
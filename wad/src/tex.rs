@@ -1,16 +1,18 @@
 use super::archive::Archive;
 use super::errors::{ErrorKind, Result};
-use super::image::Image;
+use super::image::{Image, PatchPlacement};
 use super::name::WadName;
+use super::sprite::sprite_rotation;
 use super::types::{Colormap, Palette, WadTextureHeader, WadTexturePatchRef};
 use bincode;
 use byteorder::{LittleEndian, ReadBytesExt};
 use failchain::{ensure, ResultExt};
 use indexmap::IndexMap;
-use log::{error, info};
+use log::{error, info, warn};
 use math::prelude::*;
 use math::{vec2, Pnt2f, Vec2, Vec2f};
 use std::cmp;
+use std::fmt;
 use std::mem;
 use std::time::Instant;
 
@@ -35,6 +37,52 @@ pub struct TextureDirectory {
     animated_flats: Vec<Vec<WadName>>,
 }
 
+/// A `TEXTURE1` or `TEXTURE2` entry referencing a patch which is missing from the archive (or
+/// couldn't be read), see `TextureDirectory::validate`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationWarning {
+    pub lump: WadName,
+    pub texture: WadName,
+    /// The patch's index in `PNAMES`.
+    pub patch_index: usize,
+    /// The patch's name, or `None` if the index is past the end of `PNAMES`.
+    pub patch: Option<WadName>,
+}
+
+impl ValidationWarning {
+    fn new(
+        lump: WadName,
+        header: &WadTextureHeader,
+        pref: &WadTexturePatchRef,
+        patches: &[(WadName, Option<Image>)],
+    ) -> Self {
+        let patch_index = pref.patch as usize;
+        ValidationWarning {
+            lump,
+            texture: header.name,
+            patch_index,
+            patch: patches.get(patch_index).map(|&(name, _)| name),
+        }
+    }
+}
+
+impl fmt::Display for ValidationWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.patch {
+            Some(patch) => write!(
+                f,
+                "{} texture `{}` references missing patch `{}` ({}).",
+                self.lump, self.texture, patch, self.patch_index
+            ),
+            None => write!(
+                f,
+                "{} texture `{}` references patch {}, past the end of PNAMES.",
+                self.lump, self.texture, self.patch_index
+            ),
+        }
+    }
+}
+
 pub struct MappedPalette {
     pub pixels: Vec<u8>,
     pub colormaps: usize,
@@ -67,6 +115,9 @@ impl TextureDirectory {
         // Read textures.
         let start_time = Instant::now();
         info!("Reading & assembling textures...");
+        let fallback_index = palettes
+            .first()
+            .map_or(0, |palette| nearest_palette_index(palette, FALLBACK_COLOR));
         let mut textures = IndexMap::new();
         let mut textures_buffer = Vec::new();
         for &lump_name in TEXTURE_LUMP_NAMES {
@@ -79,7 +130,13 @@ impl TextureDirectory {
             };
             textures_buffer.clear();
             lump.read_bytes_into(&mut textures_buffer)?;
-            let num_textures = read_textures(&textures_buffer, &patches, &mut textures)?;
+            let num_textures = read_textures(
+                lump.name(),
+                &textures_buffer,
+                &patches,
+                fallback_index,
+                &mut textures,
+            )?;
             info!(
                 "  {:4} textures in {}",
                 num_textures,
@@ -87,6 +144,8 @@ impl TextureDirectory {
             );
         }
         info!("Done in {:.2}ms.", start_time.elapsed().f64_milliseconds());
+        let num_custom_textures = read_custom_textures(wad, &mut textures)?;
+        info!("  {:4} textures in TX_START", num_custom_textures);
 
         // Read flats.
         let flats = read_flats(wad)?;
@@ -107,13 +166,65 @@ impl TextureDirectory {
         })
     }
 
+    /// Lists every `TEXTURE1` and `TEXTURE2` entry which references a missing or unreadable
+    /// `PNAMES` patch. `from_archive` logs these too and substitutes a magenta texture, so this
+    /// is for reporting problems with a PWAD without loading the whole directory.
+    pub fn validate(wad: &Archive) -> Result<Vec<ValidationWarning>> {
+        let patches = read_patches(wad)?;
+        let mut warnings = Vec::new();
+        for &lump_name in TEXTURE_LUMP_NAMES {
+            let lump = match wad.named_lump(lump_name)? {
+                Some(lump) => lump,
+                None => continue,
+            };
+            let definitions = read_texture_definitions(&lump.read_bytes()?)?;
+            for (header, patch_refs) in &definitions {
+                warnings.extend(
+                    patch_refs
+                        .iter()
+                        .filter(|pref| match patches.get(pref.patch as usize) {
+                            Some(&(_, Some(_))) => false,
+                            _ => true,
+                        })
+                        .map(|pref| ValidationWarning::new(lump.name(), header, pref, &patches)),
+                );
+            }
+        }
+        Ok(warnings)
+    }
+
     pub fn texture(&self, name: WadName) -> Option<&Image> {
         self.textures.get(&name)
     }
+
+    /// Finds the sprite image for `frame` of the sprite `base` seen from `angle`, like
+    /// `Archive::sprite_lump`. Returns the sprite's name and whether it should be flipped.
+    pub fn sprite(&self, base: WadName, frame: u8, angle: u8) -> Option<(WadName, bool)> {
+        sprite_rotation(base, frame, angle, |name| self.textures.contains_key(name))
+    }
+
     pub fn flat(&self, name: WadName) -> Option<&Flat> {
         self.flats.get(&name)
     }
 
+    /// Returns the flat at position `index` in the flats namespace (between `F_START` and
+    /// `F_END`), with its name. Animated flats are ranges of this namespace, so a frame is found
+    /// by adding its offset to the index of the animation's first flat (see `flat_index`).
+    pub fn flat_by_index(&self, index: usize) -> Option<(WadName, &Flat)> {
+        self.flats
+            .get_index(index)
+            .map(|(&name, flat)| (name, flat))
+    }
+
+    /// The position of a flat in the flats namespace, the inverse of `flat_by_index`.
+    pub fn flat_index(&self, name: WadName) -> Option<usize> {
+        self.flats.get_full(&name).map(|(index, _, _)| index)
+    }
+
+    pub fn num_flats(&self) -> usize {
+        self.flats.len()
+    }
+
     pub fn num_patches(&self) -> usize {
         self.patches.len()
     }
@@ -474,13 +585,11 @@ fn search_for_frame(search_for: WadName, animations: &[Vec<WadName>]) -> Option<
 }
 
 fn read_sprites(wad: &Archive, textures: &mut IndexMap<WadName, Image>) -> Result<usize> {
-    let start_index = wad.required_named_lump(b"S_START\0")?.index() + 1;
-    let end_index = wad.required_named_lump(b"S_END\0\0\0")?.index();
-    info!("Reading {} sprites....", end_index - start_index);
+    let lumps = wad.sprite_lumps().collect::<Vec<_>>();
+    info!("Reading {} sprites....", lumps.len());
     let start_time = Instant::now();
     let mut image_buffer = Vec::new();
-    for index in start_index..end_index {
-        let lump = wad.lump_by_index(index)?;
+    for lump in &lumps {
         image_buffer.clear();
         lump.read_bytes_into(&mut image_buffer)?;
         match Image::from_buffer(&image_buffer) {
@@ -494,14 +603,84 @@ fn read_sprites(wad: &Archive, textures: &mut IndexMap<WadName, Image>) -> Resul
         }
     }
     info!("Done in {:.2}ms.", start_time.elapsed().f64_milliseconds());
-    Ok(end_index - start_index)
+    Ok(lumps.len())
+}
+
+/// Reads the single patch textures in the `TX_START` namespace, which replace any `TEXTUREx`
+/// textures of the same name. Lumps which aren't DOOM format pictures (ZDoom also allows PNGs)
+/// are skipped.
+fn read_custom_textures(wad: &Archive, textures: &mut IndexMap<WadName, Image>) -> Result<usize> {
+    let mut num_textures = 0;
+    let mut image_buffer = Vec::new();
+    for lump in wad.custom_texture_lumps() {
+        image_buffer.clear();
+        lump.read_bytes_into(&mut image_buffer)?;
+        match Image::from_buffer(&image_buffer) {
+            Ok(texture) => {
+                textures.insert(lump.name(), texture);
+                num_textures += 1;
+            }
+            Err(e) => error!("Skipping texture {}: {}", lump.name(), e),
+        }
+    }
+    Ok(num_textures)
 }
 
 fn read_textures(
+    lump_name: WadName,
     lump_buffer: &[u8],
     patches: &[(WadName, Option<Image>)],
+    fallback_index: u8,
     textures: &mut IndexMap<WadName, Image>,
 ) -> Result<usize> {
+    let definitions = read_texture_definitions(lump_buffer)?;
+    for (header, patch_refs) in &definitions {
+        let mut placements = Vec::with_capacity(patch_refs.len());
+        let mut is_broken = false;
+        for pref in patch_refs {
+            let offset = Vec2::new(
+                pref.origin_x as isize,
+                if pref.origin_y <= 0 {
+                    0
+                } else {
+                    pref.origin_y as isize
+                },
+            );
+            match patches.get(pref.patch as usize) {
+                Some(&(_, Some(ref patch))) => {
+                    placements.push(PatchPlacement { patch, offset });
+                }
+                _ => {
+                    let warning = ValidationWarning::new(lump_name, header, pref, patches);
+                    warn!("{}", warning);
+                    is_broken = true;
+                }
+            }
+        }
+
+        let (width, height) = (header.width as usize, header.height as usize);
+        let image = if is_broken {
+            Image::filled(width, height, fallback_index)
+        } else {
+            Image::compose(width, height, &placements)
+        };
+        let image = match image {
+            Ok(image) => image,
+            Err(e) => {
+                error!("Skipping texture {}: {}", header.name, e);
+                continue;
+            }
+        };
+        textures.insert(header.name, image);
+    }
+    Ok(definitions.len())
+}
+
+/// Reads the header and patch references of every texture in a `TEXTUREx` lump. Textures and
+/// patch references which can't be read are logged and skipped.
+fn read_texture_definitions(
+    lump_buffer: &[u8],
+) -> Result<Vec<(WadTextureHeader, Vec<WadTexturePatchRef>)>> {
     let mut lump = lump_buffer;
     let num_textures = lump
         .read_u32::<LittleEndian>()
@@ -518,11 +697,11 @@ fn read_textures(
     );
     let mut offsets = &lump[..offsets_end];
 
+    let mut definitions = Vec::with_capacity(num_textures);
     for i_texture in 0..num_textures {
-        let offset = offsets
-            .read_u32::<LittleEndian>()
-            .expect("could not read from size-checked offset buffer in texture lump")
-            as usize;
+        let offset = offsets.read_u32::<LittleEndian>().chain_err(|| {
+            ErrorKind::InvalidWad(format!("Missing offset of texture {}.", i_texture))
+        })? as usize;
         ensure!(
             offset < lump_buffer.len(),
             ErrorKind::CorruptWad,
@@ -542,66 +721,93 @@ fn read_textures(
                 continue;
             }
         };
-        let mut image = match Image::new_from_header(&header) {
-            Ok(image) => image,
-            Err(e) => {
-                error!("Skipping texture {}: {}", header.name, e);
-                continue;
-            }
-        };
-
+        let mut patch_refs = Vec::with_capacity(header.num_patches as usize);
         for i_patch in 0..header.num_patches {
-            let pref: WadTexturePatchRef = match bincode::deserialize_from(&mut lump) {
-                Ok(image) => image,
-                Err(e) => {
-                    error!("Skipping patch {} in image {}: {}", i_patch, header.name, e);
-                    continue;
-                }
-            };
-            let offset = Vec2::new(
-                pref.origin_x as isize,
-                if pref.origin_y <= 0 {
-                    0
-                } else {
-                    pref.origin_y as isize
-                },
-            );
-            match patches.get(pref.patch as usize) {
-                Some(&(_, Some(ref patch))) => {
-                    image.blit(patch, offset, i_patch == 0);
-                }
-                Some(&(ref patch_name, None)) => {
-                    error!(
-                        "PatchRef {}, required by {} is missing.",
-                        patch_name, header.name
-                    );
-                }
-                None => {
-                    error!(
-                        "PatchRef index {} out of bounds ({}) in {}, skipping.",
-                        pref.patch,
-                        patches.len(),
-                        header.name
-                    );
-                }
+            match bincode::deserialize_from(&mut lump) {
+                Ok(pref) => patch_refs.push(pref),
+                Err(e) => error!("Skipping patch {} in image {}: {}", i_patch, header.name, e),
             }
         }
-
-        textures.insert(header.name, image);
+        definitions.push((header, patch_refs));
     }
-    Ok(num_textures)
+    Ok(definitions)
 }
 
+/// The palette index whose colour is closest to `color`, used for the fallback texture.
+fn nearest_palette_index(palette: &Palette, color: [u8; 3]) -> u8 {
+    let distance = |index: usize| {
+        palette.0[index * 3..index * 3 + 3]
+            .iter()
+            .zip(&color)
+            .map(|(&a, &b)| (i32::from(a) - i32::from(b)).pow(2))
+            .sum::<i32>()
+    };
+    (0..256).min_by_key(|&index| distance(index)).unwrap_or(0) as u8
+}
+
+/// Textures with missing patches are drawn in magenta instead, so they stand out in game.
+const FALLBACK_COLOR: [u8; 3] = [0xff, 0x00, 0xff];
+
 fn read_flats(wad: &Archive) -> Result<IndexMap<WadName, Flat>> {
-    let start = wad.required_named_lump(b"F_START\0")?.index();
-    let end = wad.required_named_lump(b"F_END\0\0\0")?.index();
     let mut flats = IndexMap::new();
-    for i_lump in start..end {
-        let lump = wad.lump_by_index(i_lump)?;
-        if lump.is_virtual() {
-            continue;
-        }
+    for lump in wad.flat_lumps() {
         flats.insert(lump.name(), lump.read_bytes()?);
     }
     Ok(flats)
 }
+
+#[cfg(test)]
+mod test {
+    use super::{nearest_palette_index, read_textures, ValidationWarning, FALLBACK_COLOR};
+    use crate::image::Image;
+    use crate::name::IntoWadName;
+    use crate::types::{Palette, WadTextureHeader, WadTexturePatchRef};
+    use indexmap::IndexMap;
+
+    #[test]
+    fn test_missing_patch_uses_fallback() {
+        let mut palette = Palette::default();
+        palette.0[7 * 3..7 * 3 + 3].copy_from_slice(&[0xf0, 0x10, 0xe0]);
+        assert_eq!(nearest_palette_index(&palette, FALLBACK_COLOR), 7);
+
+        // One 2x1 texture, `BROKEN`, made of patches 0 (which exists) and 1 (which doesn't).
+        let mut lump = Vec::new();
+        lump.extend_from_slice(&1u32.to_le_bytes());
+        lump.extend_from_slice(&8u32.to_le_bytes());
+        lump.extend_from_slice(b"BROKEN\0\0");
+        lump.extend_from_slice(&[0, 0, 0, 0, 2, 0, 1, 0, 0, 0, 0, 0, 2, 0]);
+        lump.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        lump.extend_from_slice(&[0, 0, 0, 0, 1, 0, 0, 0, 0, 0]);
+
+        let patch_name = |name: &str| name.into_wad_name().unwrap();
+        let patches = vec![
+            (patch_name("PATCH0"), Some(Image::filled(1, 1, 3).unwrap())),
+            (patch_name("PATCH1"), None),
+        ];
+        let mut textures = IndexMap::new();
+        let lump_name = patch_name("TEXTURE1");
+        assert_eq!(
+            read_textures(lump_name, &lump, &patches, 7, &mut textures).unwrap(),
+            1
+        );
+        let texture = &textures[&patch_name("BROKEN")];
+        assert_eq!(texture.pixels(), &[7, 7]);
+
+        let header: WadTextureHeader = bincode::deserialize(&lump[8..]).unwrap();
+
+        let warnings = (0..3)
+            .map(|patch| WadTexturePatchRef {
+                origin_x: 0,
+                origin_y: 0,
+                patch,
+                stepdir: 0,
+                colormap: 0,
+            })
+            .map(|pref| ValidationWarning::new(lump_name, &header, &pref, &patches))
+            .map(|warning| warning.patch)
+            .collect::<Vec<_>>();
+        let expected = [Some("PATCH0"), Some("PATCH1"), None];
+        let expected = expected.iter().map(|name| name.map(patch_name));
+        assert!(warnings.into_iter().eq(expected));
+    }
+}
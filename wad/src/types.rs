@@ -13,6 +13,7 @@ pub type ThingType = u16;
 pub type VertexId = u16;
 pub type WadCoord = i16;
 pub type SegId = u16;
+pub type SubsectorId = u16;
 pub type LinedefId = u16;
 pub type ChildId = u16;
 
@@ -39,6 +40,14 @@ pub struct WadThing {
     pub flags: ThingFlags,
 }
 
+impl WadThing {
+    /// Whether the thing is flagged as an ambush ("deaf"): a monster which isn't woken by noise,
+    /// only by seeing the player.
+    pub fn ambush(&self) -> bool {
+        self.flags & 0x0008 != 0
+    }
+}
+
 #[derive(Copy, Clone, Deserialize)]
 pub struct WadVertex {
     pub x: WadCoord,
@@ -1,12 +1,16 @@
+use super::generalized::GeneralizedAction;
 use super::level::{Level, NeighbourHeights};
 use super::light::{self, Contrast, LightInfo};
+use super::lock::Lock;
 use super::meta::{
-    ExitEffectDef, HeightDef, HeightEffectDef, HeightRef, MoveEffectDef, TriggerType, WadMetadata,
+    ExitEffectDef, HeightDef, HeightEffectDef, HeightRef, MoveEffectDef, ThingMetadata,
+    TriggerType, WadMetadata,
 };
+use super::skill::SKILL_THING_FLAGS;
 use super::tex::TextureDirectory;
 use super::types::{
-    ChildId, SectorId, SpecialType, ThingType, WadCoord, WadLinedef, WadName, WadNode, WadSector,
-    WadSeg, WadThing,
+    ChildId, SectorId, SpecialType, ThingFlags, ThingType, WadCoord, WadLinedef, WadName, WadNode,
+    WadSector, WadSeg, WadThing,
 };
 use super::util::{
     from_wad_coords, from_wad_height, is_sky_flat, is_untextured, parse_child_id, to_wad_height,
@@ -14,7 +18,7 @@ use super::util::{
 use indexmap::IndexMap;
 use log::{debug, error, info, warn};
 use math::prelude::*;
-use math::{Deg, Line2f, Pnt2f, Pnt3f, Radf, Vec2f};
+use math::{Deg, Line2f, Pnt2f, Pnt3f, Radf, Vec2f, Vec3f};
 use std::cmp;
 use std::cmp::Ordering;
 use std::f32::EPSILON;
@@ -27,10 +31,15 @@ pub struct StaticQuad<'a> {
     pub tex_start: (f32, f32),
     pub tex_end: (f32, f32),
     pub height_range: (f32, f32),
+    /// The floor and ceiling heights of the sector the wall faces.
+    pub sector_heights: (f32, f32),
     pub light_info: &'a LightInfo,
     pub scroll: f32,
     pub tex_name: Option<WadName>,
     pub blocker: bool,
+    /// Middle textures of two-sided lines (windows, grates etc.) may have transparent pixels and
+    /// need to be rendered after the opaque geometry.
+    pub masked: bool,
 }
 
 pub struct StaticPoly<'a> {
@@ -62,6 +71,24 @@ pub struct Decor<'a> {
     pub tex_name: WadName,
 }
 
+/// A thing which can move or disappear (a monster or a pickup), with the sprite it starts with.
+pub struct ThingSpawn<'a> {
+    pub thing_type: ThingType,
+    pub yaw: Radf,
+    pub radius: f32,
+    /// See `WadThing::ambush`.
+    pub ambush: bool,
+    pub decor: Decor<'a>,
+}
+
+/// A point light emitted by a thing, e.g. a torch or a lamp.
+#[derive(Copy, Clone, Debug)]
+pub struct DynamicLight {
+    pub origin: Pnt3f,
+    pub radius: f32,
+    pub color: Vec3f,
+}
+
 pub trait LevelVisitor: Sized {
     fn visit_wall_quad(&mut self, _quad: &StaticQuad) {
         // Default impl is empty to allow visitors to mix and match.
@@ -95,6 +122,20 @@ pub trait LevelVisitor: Sized {
         // Default impl is empty to allow visitors to mix and match.
     }
 
+    fn visit_monster(&mut self, monster: &ThingSpawn) {
+        // Visitors which don't animate monsters treat them as decorations.
+        self.visit_decor(&monster.decor);
+    }
+
+    fn visit_pickup(&mut self, pickup: &ThingSpawn) {
+        // Likewise for items which can be picked up.
+        self.visit_decor(&pickup.decor);
+    }
+
+    fn visit_dynamic_light(&mut self, _light: &DynamicLight) {
+        // Default impl is empty to allow visitors to mix and match.
+    }
+
     fn visit_bsp_root(&mut self, _line: &Line2f) {
         // Default impl is empty to allow visitors to mix and match.
     }
@@ -135,7 +176,7 @@ pub enum Branch {
 #[derive(Eq, PartialEq, Debug, Copy, Clone)]
 pub enum Marker {
     StartPos { player: usize },
-    TeleportStart,
+    DeathmatchStart,
     TeleportEnd,
 }
 
@@ -170,10 +211,11 @@ impl DynamicSectorInfo {
         next_dynamic_object_id: &mut ObjectId,
         level: &Level,
         sector_id: SectorId,
+        effect_def: Option<MoveEffectDef>,
         trigger: &mut Trigger,
     ) {
         let sector = &level.sectors[sector_id as usize];
-        let effect_def = match trigger.move_effect_def {
+        let effect_def = match effect_def {
             Some(effect_def) => effect_def,
             None => return,
         };
@@ -221,24 +263,28 @@ impl DynamicSectorInfo {
             let offset = from_wad_height(first_floor - sector.floor_height);
             trigger.move_effects.push(MoveEffect {
                 object_id: self.floor_id,
+                sector_id,
                 wait: effect_def.wait,
                 speed: effect_def.speed,
                 first_height_offset: offset,
                 second_height_offset: second_floor
                     .map(|floor| from_wad_height(floor - sector.floor_height)),
                 repeat,
+                crush: false,
             });
         }
 
         if let Some(first_ceiling) = first_ceiling {
             trigger.move_effects.push(MoveEffect {
                 object_id: self.ceiling_id,
+                sector_id,
                 wait: effect_def.wait,
                 speed: effect_def.speed,
                 first_height_offset: from_wad_height(first_ceiling - sector.ceiling_height),
                 second_height_offset: second_ceiling
                     .map(|ceiling| from_wad_height(ceiling - sector.ceiling_height)),
                 repeat,
+                crush: effect_def.crush,
             });
         }
     }
@@ -263,11 +309,13 @@ fn merge_range<I: IntoIterator<Item = WadCoord>>(
 #[derive(Debug, Copy, Clone)]
 pub struct MoveEffect {
     pub object_id: ObjectId,
+    pub sector_id: SectorId,
     pub first_height_offset: f32,
     pub second_height_offset: Option<f32>,
     pub speed: f32,
     pub wait: f32,
     pub repeat: bool,
+    pub crush: bool,
 }
 
 impl HeightDef {
@@ -317,6 +365,8 @@ pub struct Trigger {
     pub move_effect_def: Option<MoveEffectDef>,
     pub exit_effect: Option<ExitEffectDef>,
     pub move_effects: Vec<MoveEffect>,
+    /// The keys needed to activate the trigger, if it's locked.
+    pub lock: Option<Lock>,
 }
 
 pub struct LevelAnalysis {
@@ -344,40 +394,24 @@ impl LevelAnalysis {
         mem::replace(&mut self.triggers, Vec::new())
     }
 
+    /// Returns the object which moves the floor of the given sector, `ObjectId(0)` if static.
+    pub fn floor_id(&self, sector_id: SectorId) -> ObjectId {
+        self.dynamic_info
+            .get(&sector_id)
+            .map_or(ObjectId(0), |dynamic| dynamic.floor_id)
+    }
+
+    /// Returns the object which moves the ceiling of the given sector, `ObjectId(0)` if static.
+    pub fn ceiling_id(&self, sector_id: SectorId) -> ObjectId {
+        self.dynamic_info
+            .get(&sector_id)
+            .map_or(ObjectId(0), |dynamic| dynamic.ceiling_id)
+    }
+
     fn compute_dynamic_sectors(&mut self, level: &Level, meta: &WadMetadata) {
         info!("Computing dynamic sectors...");
         let mut num_dynamic_linedefs = 0;
 
-        let mut sector_tags_and_ids = level
-            .sectors
-            .iter()
-            .enumerate()
-            .filter_map(|(i_sector, sector)| {
-                let tag = sector.tag;
-                if tag > 0 {
-                    Some((tag, i_sector as SectorId))
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-        sector_tags_and_ids.sort_unstable();
-
-        let max_tag = if let Some(&(max_tag, _)) = sector_tags_and_ids.last() {
-            max_tag
-        } else {
-            return;
-        };
-        let mut tag_to_first_index = VecMap::with_capacity(max_tag as usize);
-        let mut last_tag = !0usize;
-        for (i_sector_tag_and_id, &(tag, _)) in sector_tags_and_ids.iter().enumerate() {
-            let tag = tag as usize;
-            if tag != last_tag {
-                tag_to_first_index.insert(tag, i_sector_tag_and_id);
-                last_tag = tag;
-            }
-        }
-
         let mut next_dynamic_object_id = ObjectId(1);
         for (i_linedef, linedef) in level.linedefs.iter().enumerate() {
             let mut trigger = if let Some(trigger) = self.linedef_to_trigger(level, meta, linedef) {
@@ -395,38 +429,30 @@ impl LevelAnalysis {
                         "Sector {} with zero tag marked as dynamic, required by manual linedef {}.",
                         left_sector_id, i_linedef
                     );
-                    self.dynamic_info
-                        .entry(left_sector_id)
-                        .or_insert_with(DynamicSectorInfo::default)
-                        .update(
-                            &mut next_dynamic_object_id,
-                            level,
-                            left_sector_id,
-                            &mut trigger,
-                        );
+                    self.add_sector_effects(
+                        &mut next_dynamic_object_id,
+                        level,
+                        left_sector_id,
+                        &mut trigger,
+                    );
                 }
                 self.triggers.push(trigger);
                 continue;
             }
 
-            if let Some(first_index) = tag_to_first_index.get(tag as usize) {
-                for &(current_tag, current_sector_id) in &sector_tags_and_ids[*first_index..] {
-                    if current_tag != tag {
-                        break;
-                    }
+            let tagged_sector_ids = level.sectors_with_tag(tag);
+            if !tagged_sector_ids.is_empty() {
+                for &current_sector_id in tagged_sector_ids {
                     debug!(
                         "Sector {} with the tag {} marked as dynamic, required by linedef {}.",
                         current_sector_id, tag, i_linedef
                     );
-                    self.dynamic_info
-                        .entry(current_sector_id)
-                        .or_insert_with(DynamicSectorInfo::default)
-                        .update(
-                            &mut next_dynamic_object_id,
-                            level,
-                            current_sector_id,
-                            &mut trigger,
-                        );
+                    self.add_sector_effects(
+                        &mut next_dynamic_object_id,
+                        level,
+                        current_sector_id,
+                        &mut trigger,
+                    );
                 }
             } else {
                 warn!(
@@ -449,6 +475,64 @@ impl LevelAnalysis {
         );
     }
 
+    fn add_sector_effects(
+        &mut self,
+        next_dynamic_object_id: &mut ObjectId,
+        level: &Level,
+        sector_id: SectorId,
+        trigger: &mut Trigger,
+    ) {
+        let effect_def = match trigger.move_effect_def {
+            Some(effect_def) if effect_def.stairs => effect_def,
+            effect_def => {
+                self.dynamic_info
+                    .entry(sector_id)
+                    .or_insert_with(DynamicSectorInfo::default)
+                    .update(
+                        next_dynamic_object_id,
+                        level,
+                        sector_id,
+                        effect_def,
+                        trigger,
+                    );
+                return;
+            }
+        };
+
+        // Stairs raise every step by the floor offset more than the one before it, starting from
+        // the floor of the first step.
+        let start_height = level.sectors[sector_id as usize].floor_height;
+        let step_height = effect_def.floor.map_or(0, |floor| floor.first.offset);
+        for (i_step, step_sector_id) in level.stair_sectors(sector_id).into_iter().enumerate() {
+            let step_sector = &level.sectors[step_sector_id as usize];
+            let height = start_height + step_height * (i_step as WadCoord + 1);
+            debug!(
+                "Sector {} is step {} of a staircase starting at {}.",
+                step_sector_id, i_step, sector_id
+            );
+            let step_effect_def = MoveEffectDef {
+                floor: Some(HeightEffectDef {
+                    first: HeightDef {
+                        to: HeightRef::Floor,
+                        offset: height - step_sector.floor_height,
+                    },
+                    second: None,
+                }),
+                ..effect_def
+            };
+            self.dynamic_info
+                .entry(step_sector_id)
+                .or_insert_with(DynamicSectorInfo::default)
+                .update(
+                    next_dynamic_object_id,
+                    level,
+                    step_sector_id,
+                    Some(step_effect_def),
+                    trigger,
+                );
+        }
+    }
+
     fn linedef_to_trigger(
         &self,
         level: &Level,
@@ -483,6 +567,21 @@ impl LevelAnalysis {
 
                 line,
                 move_effects: Vec::new(),
+                lock: meta.lock.and_then(Lock::classic),
+            }
+        } else if let Some(action) = GeneralizedAction::decode(special_type) {
+            Trigger {
+                trigger_type: action.trigger,
+
+                only_once: action.only_once,
+                move_effect_def: Some(action.move_effect),
+                exit_effect: None,
+                unimplemented: false,
+                special_type,
+
+                line,
+                move_effects: Vec::new(),
+                lock: action.lock,
             }
         } else {
             error!("Unknown linedef special type: {}", special_type);
@@ -497,6 +596,7 @@ impl LevelAnalysis {
 
                 line,
                 move_effects: Vec::new(),
+                lock: None,
             }
         })
     }
@@ -509,6 +609,7 @@ pub struct LevelWalker<'a, V: LevelVisitor + 'a> {
     visitor: &'a mut V,
     height_range: (WadCoord, WadCoord),
     bsp_lines: Vec<Line2f>,
+    thing_flags_mask: Option<ThingFlags>,
 
     dynamic_info: &'a IndexMap<SectorId, DynamicSectorInfo>,
 
@@ -536,6 +637,7 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             visitor,
             height_range: min_max_height(level),
             bsp_lines: Vec::with_capacity(32),
+            thing_flags_mask: None,
             subsector_points: Vec::with_capacity(32),
             subsector_seg_lines: Vec::with_capacity(32),
             light_cache: VecMap::with_capacity(level.sectors.len()),
@@ -544,6 +646,13 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
         }
     }
 
+    /// Only visits the things whose skill flags intersect `mask` (see `Difficulty`). Player
+    /// starts and other markers are always visited. By default, all things are visited.
+    pub fn thing_flags_mask(mut self, mask: ThingFlags) -> Self {
+        self.thing_flags_mask = Some(mask);
+        self
+    }
+
     pub fn walk(&mut self) {
         let root = match self.level.nodes.last() {
             Some(node) => node,
@@ -755,6 +864,7 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
                     texture_name: sidedef.middle_texture,
                     peg: if unpeg_lower { Peg::Bottom } else { Peg::Top },
                     blocker: true,
+                    masked: false,
                 });
                 if is_sky_flat(sector.ceiling_texture) {
                     self.sky_quad(info.ceiling_id, vertices, (ceiling, max));
@@ -794,6 +904,7 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
                     Peg::Top
                 },
                 blocker: true,
+                masked: false,
             });
             back_floor
         } else {
@@ -810,6 +921,7 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
                     texture_name: sidedef.upper_texture,
                     peg: if unpeg_upper { Peg::Top } else { Peg::Bottom },
                     blocker: true,
+                    masked: false,
                 });
             }
             back_ceiling
@@ -839,6 +951,7 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
                 Peg::Top
             },
             blocker: line.impassable(),
+            masked: true,
         });
     }
 
@@ -852,6 +965,7 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             texture_name,
             peg,
             blocker,
+            masked,
         } = quad;
         if low >= high {
             return;
@@ -924,8 +1038,7 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             t2 + f32::from(sidedef.y_offset),
         );
 
-        // TODO(cristicbz): Magic numbers below.
-        let scroll = if line.special_type == 0x30 { 35.0 } else { 0.0 };
+        let scroll = wall_scroll_rate(line.special_type);
 
         let (low, high) = (low - POLY_BIAS, high + POLY_BIAS);
 
@@ -934,9 +1047,14 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             tex_start: (s1, t1),
             tex_end: (s2, t2),
             height_range: (low, high),
+            sector_heights: (
+                from_wad_height(sector.floor_height),
+                from_wad_height(sector.ceiling_height),
+            ),
             light_info,
             tex_name: size.map(|_| texture_name),
             blocker,
+            masked,
             scroll,
             object_id,
         });
@@ -1025,59 +1143,22 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
             if let Some(marker) = Marker::from(thing.thing_type) {
                 let pos = Pnt3f::new(pos[0], from_wad_height(sector.floor_height), pos[1]);
                 self.visitor.visit_marker(pos, yaw.into(), marker);
-            } else if let Some(sector) = self.sector_at(pos) {
-                self.decor(thing, pos, sector);
+            } else if self.thing_included(thing) {
+                self.decor(thing, pos, yaw.into(), sector);
             }
         }
     }
 
+    fn thing_included(&self, thing: &WadThing) -> bool {
+        self.thing_flags_mask
+            .map_or(true, |mask| thing.flags & mask & SKILL_THING_FLAGS != 0)
+    }
+
     fn sector_at(&self, pos: Pnt2f) -> Option<&'a WadSector> {
-        let mut child_id = (self.level.nodes.len() - 1) as ChildId;
-        loop {
-            let (id, is_leaf) = parse_child_id(child_id);
-            if is_leaf {
-                let segs = self
-                    .level
-                    .ssector(id)
-                    .and_then(|subsector| self.level.ssector_segs(subsector))
-                    .and_then(|segs| if segs.is_empty() { None } else { Some(segs) });
-                let segs = if let Some(segs) = segs {
-                    segs
-                } else {
-                    return None;
-                };
-                let sector = if let Some(sector) = self.level.seg_sector(&segs[0]) {
-                    sector
-                } else {
-                    return None;
-                };
-                return if segs
-                    .iter()
-                    .filter_map(|seg| self.level.seg_vertices(seg))
-                    .map(|(v1, v2)| Line2f::from_two_points(v1, v2))
-                    .all(|line| line.signed_distance(pos) <= SEG_TOLERANCE)
-                {
-                    Some(sector)
-                } else {
-                    None
-                };
-            } else {
-                let node = if let Some(node) = self.level.nodes.get(id) {
-                    node
-                } else {
-                    return None;
-                };
-                let partition = partition_line(node);
-                if partition.signed_distance(pos) > 0.0f32 {
-                    child_id = node.left;
-                } else {
-                    child_id = node.right;
-                }
-            }
-        }
+        self.level.sector_at(pos)
     }
 
-    fn decor(&mut self, thing: &WadThing, pos: Pnt2f, sector: &WadSector) {
+    fn decor(&mut self, thing: &WadThing, pos: Pnt2f, yaw: Radf, sector: &WadSector) {
         let meta = match self.meta.find_thing(thing.thing_type) {
             Some(m) => m,
             None => {
@@ -1085,35 +1166,18 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
                 return;
             }
         };
-        let (name, size) = {
-            let mut sprite0 = meta.sprite;
-            // Ignore the error: if this fails, so will the `sprite0` and `sprite1` pushes below.
-            let _ = sprite0.push(meta.sequence.as_bytes()[0]);
-            let mut sprite1 = sprite0;
-            let sprite0 = sprite0.push(b'0').ok().map(|_| sprite0);
-            let sprite1 = sprite1.push(b'1').ok().map(|_| sprite1);
-
-            match (sprite0, sprite1) {
-                (Some(sprite0), Some(sprite1)) => {
-                    if let Some(image) = self.tex.texture(sprite0) {
-                        (sprite0, image.size())
-                    } else if let Some(image) = self.tex.texture(sprite1) {
-                        (sprite1, image.size())
-                    } else {
-                        warn!(
-                            "No such sprite {} for thing {}",
-                            meta.sprite, thing.thing_type
-                        );
-                        return;
-                    }
-                }
-                _ => {
-                    warn!(
-                        "Metadata sprite name ({}) for thing type {} is not a valid WadName.",
-                        meta.sprite, thing.thing_type
-                    );
-                    return;
-                }
+        let (name, size) = match self
+            .tex
+            .sprite(meta.sprite, meta.sequence.as_bytes()[0], 1)
+            .and_then(|(name, _)| Some((name, self.tex.texture(name)?.size())))
+        {
+            Some(sprite) => sprite,
+            None => {
+                warn!(
+                    "No such sprite {} for thing {}",
+                    meta.sprite, thing.thing_type
+                );
+                return;
             }
         };
         let size = Vec2f::new(
@@ -1144,14 +1208,51 @@ impl<'a, V: LevelVisitor> LevelWalker<'a, V> {
         };
         let half_width = size[0] * 0.5;
 
-        self.visitor.visit_decor(&Decor {
+        let decor = Decor {
             object_id,
             low,
             high,
             half_width,
             light_info: light_info(&mut self.light_cache, self.level, sector),
             tex_name: name,
-        });
+        };
+        let things = &self.meta.things;
+        let is_of_type = |list: &[ThingMetadata]| {
+            list.iter()
+                .any(|listed| listed.thing_type == thing.thing_type)
+        };
+        let is_monster = is_of_type(&things.monsters);
+        let is_pickup = [
+            &things.weapons,
+            &things.powerups,
+            &things.artifacts,
+            &things.ammo,
+            &things.keys,
+        ]
+        .iter()
+        .any(|list| is_of_type(list));
+        let spawn = ThingSpawn {
+            thing_type: thing.thing_type,
+            yaw,
+            radius: from_wad_height(meta.radius as i16),
+            ambush: thing.ambush(),
+            decor,
+        };
+        if is_monster {
+            self.visitor.visit_monster(&spawn);
+        } else if is_pickup {
+            self.visitor.visit_pickup(&spawn);
+        } else {
+            self.visitor.visit_decor(&spawn.decor);
+        }
+
+        if let Some(light) = meta.light {
+            self.visitor.visit_dynamic_light(&DynamicLight {
+                origin: low + (high - low) * 0.5,
+                radius: light.radius / 100.0,
+                color: Vec3f::from(light.color),
+            });
+        }
     }
 }
 
@@ -1165,7 +1266,7 @@ fn light_info<'a>(
         .or_insert_with(|| light::new_light(level, sector))
 }
 
-fn partition_line(node: &WadNode) -> Line2f {
+pub(crate) fn partition_line(node: &WadNode) -> Line2f {
     Line2f::from_two_points(
         from_wad_coords(node.line_x, node.line_y),
         from_wad_coords(node.line_x + node.step_x, node.line_y + node.step_y),
@@ -1173,8 +1274,25 @@ fn partition_line(node: &WadNode) -> Line2f {
 }
 
 // Distance on the wrong side of a BSP and seg line allowed.
+/// How fast a linedef special scrolls its wall textures, in texels per second; positive rates
+/// move the texture left.
+fn wall_scroll_rate(special_type: SpecialType) -> f32 {
+    match special_type {
+        SPECIAL_SCROLL_LEFT => SCROLL_TEXELS_PER_TIC * TICS_PER_SECOND,
+        SPECIAL_SCROLL_RIGHT => -SCROLL_TEXELS_PER_TIC * TICS_PER_SECOND,
+        _ => 0.0,
+    }
+}
+
+/// DOOM's scrolling wall special.
+const SPECIAL_SCROLL_LEFT: SpecialType = 48;
+/// Boom's mirror image of `SPECIAL_SCROLL_LEFT`.
+const SPECIAL_SCROLL_RIGHT: SpecialType = 85;
+const SCROLL_TEXELS_PER_TIC: f32 = 1.0;
+const TICS_PER_SECOND: f32 = 35.0;
+
 const BSP_TOLERANCE: f32 = 1e-3;
-const SEG_TOLERANCE: f32 = 0.1;
+pub(crate) const SEG_TOLERANCE: f32 = 0.1;
 
 // All polygons are `fattened' by this amount to fill in thin gaps between them.
 const POLY_BIAS: f32 = 0.64 * 3e-4;
@@ -1322,6 +1440,21 @@ impl<'a, 'b, A: LevelVisitor, B: LevelVisitor> LevelVisitor for VisitorChain<'a,
         self.second.visit_decor(decor);
     }
 
+    fn visit_monster(&mut self, monster: &ThingSpawn) {
+        self.first.visit_monster(monster);
+        self.second.visit_monster(monster);
+    }
+
+    fn visit_pickup(&mut self, pickup: &ThingSpawn) {
+        self.first.visit_pickup(pickup);
+        self.second.visit_pickup(pickup);
+    }
+
+    fn visit_dynamic_light(&mut self, light: &DynamicLight) {
+        self.first.visit_dynamic_light(light);
+        self.second.visit_dynamic_light(light);
+    }
+
     fn visit_bsp_root(&mut self, line: &Line2f) {
         self.first.visit_bsp_root(line);
         self.second.visit_bsp_root(line);
@@ -1358,13 +1491,14 @@ struct InternalWallQuad<'a> {
     texture_name: WadName,
     peg: Peg,
     blocker: bool,
+    masked: bool,
 }
 
 const THING_TYPE_PLAYER1_START: ThingType = 1;
 const THING_TYPE_PLAYER2_START: ThingType = 2;
 const THING_TYPE_PLAYER3_START: ThingType = 3;
 const THING_TYPE_PLAYER4_START: ThingType = 4;
-const THING_TYPE_TELEPORT_START: ThingType = 11;
+const THING_TYPE_DEATHMATCH_START: ThingType = 11;
 const THING_TYPE_TELEPORT_END: ThingType = 14;
 
 impl Marker {
@@ -1374,7 +1508,7 @@ impl Marker {
             THING_TYPE_PLAYER2_START => Some(Marker::StartPos { player: 1 }),
             THING_TYPE_PLAYER3_START => Some(Marker::StartPos { player: 2 }),
             THING_TYPE_PLAYER4_START => Some(Marker::StartPos { player: 3 }),
-            THING_TYPE_TELEPORT_START => Some(Marker::TeleportStart),
+            THING_TYPE_DEATHMATCH_START => Some(Marker::DeathmatchStart),
             THING_TYPE_TELEPORT_END => Some(Marker::TeleportEnd),
             _ => None,
         }